@@ -8,6 +8,7 @@ uniform float time;
 uniform mat4 view;
 uniform mat4 projection;
 uniform vec4 blend;
+uniform vec2 screen_size;
 
 #define DITTER_DIVIDER 128.0
 
@@ -83,8 +84,6 @@ vec3 rain(vec3 base, vec2 screen_pos)
 
 void main()
 {
-    const vec2 screen_size = vec2(1280.0, 720.0);
-
     vec2 screen_pos = UV * screen_size;
     
     vec3 result = gradient(screen_pos, screen_size);
@@ -8,12 +8,12 @@ uniform float time;
 uniform vec4 blend;
 uniform sampler2D sampler0;
 uniform mat4 view;
+uniform vec2 screen_size;
 
 void main()
 {
     // I call this, "the-arbritrary-numbers-until-it-looks-good" shader
     // .. For the "lake" reflection, by the way
-    const vec2 screen_size = vec2(1280.0, 720.0);
     vec2 screen_pos = UV * screen_size;
     vec2 world_pos = -vec2(view[3][0], view[3][1]) + vec2(screen_pos.x, screen_pos.y);
 
@@ -23,7 +23,7 @@ void main()
 
     float camera_offset = -200.0 - view[3][1];
 
-    vec2 sample_coord = vec2(0.0, 0.42 + camera_offset / 360.0) + distort;
+    vec2 sample_coord = vec2(0.0, 0.42 + camera_offset / (screen_size.y * 0.5)) + distort;
     sample_coord = (vec2((UV.x + 0.005) * 0.99, UV.y) + sample_coord) * vec2(1.0, -1.0);
     vec4 lake = texture(sampler0, sample_coord);
 
@@ -40,7 +40,4 @@ void main()
     result.rgb = mix(vec3(0.0, 0.025, 0.1), result.rgb, d);
     
     output0 = result;
-
-    // Fading
-    output0.rgb *= blend.a;
 }
\ No newline at end of file
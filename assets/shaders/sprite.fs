@@ -0,0 +1,37 @@
+#version 330
+
+in vec2 UV;
+in vec4 Tint;
+flat in float TextureSlot;
+
+out vec4 output0;
+
+uniform float time;
+uniform sampler2D sampler0;
+uniform sampler2D sampler1;
+uniform sampler2D sampler2;
+uniform sampler2D sampler3;
+uniform sampler2D sampler4;
+uniform sampler2D sampler5;
+uniform sampler2D sampler6;
+uniform sampler2D sampler7;
+
+void main()
+{
+    // Derivatives aren't defined inside the branches, so they're taken up front
+    vec2 dx = dFdx(UV);
+    vec2 dy = dFdy(UV);
+    int slot = int(TextureSlot + 0.5);
+    vec4 color;
+
+    if (slot == 0) color = textureGrad(sampler0, UV, dx, dy);
+    else if (slot == 1) color = textureGrad(sampler1, UV, dx, dy);
+    else if (slot == 2) color = textureGrad(sampler2, UV, dx, dy);
+    else if (slot == 3) color = textureGrad(sampler3, UV, dx, dy);
+    else if (slot == 4) color = textureGrad(sampler4, UV, dx, dy);
+    else if (slot == 5) color = textureGrad(sampler5, UV, dx, dy);
+    else if (slot == 6) color = textureGrad(sampler6, UV, dx, dy);
+    else color = textureGrad(sampler7, UV, dx, dy);
+
+    output0 = color * Tint;
+}
\ No newline at end of file
@@ -0,0 +1,25 @@
+#version 330
+
+in vec2 position;
+in vec2 uv;
+in vec4 tint;
+in vec4 basis;
+in vec3 offset;
+in float texture_slot;
+
+out vec2 UV;
+out vec4 Tint;
+flat out float TextureSlot;
+
+uniform float time;
+uniform mat4 view;
+uniform mat4 projection;
+
+void main() 
+{
+    mat4 model = mat4(vec4(basis.xy, 0.0, 0.0), vec4(basis.zw, 0.0, 0.0), vec4(0.0, 0.0, 1.0, 0.0), vec4(offset, 1.0));
+    gl_Position = projection * view * model * vec4(position, 0.0, 1.0);
+    UV = uv;
+    Tint = tint;
+    TextureSlot = texture_slot;
+}
\ No newline at end of file
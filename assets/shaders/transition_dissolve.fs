@@ -0,0 +1,14 @@
+#version 330
+
+in vec2 UV;
+
+out vec4 output0;
+
+uniform vec4 blend;
+uniform sampler2D sampler0;
+uniform sampler2D sampler1;
+
+void main()
+{
+    output0 = mix(texture(sampler0, UV), texture(sampler1, UV), blend.a);
+}
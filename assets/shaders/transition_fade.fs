@@ -0,0 +1,19 @@
+#version 330
+
+in vec2 UV;
+
+out vec4 output0;
+
+uniform vec4 blend;
+uniform sampler2D sampler0;
+uniform sampler2D sampler1;
+
+void main()
+{
+    // First half fades the old states into the color, the second half fades the color into the new ones
+    vec4 from = texture(sampler0, UV);
+    vec4 to = texture(sampler1, UV);
+    vec4 color = vec4(blend.rgb, 1.0);
+
+    output0 = blend.a < 0.5 ? mix(from, color, blend.a * 2.0) : mix(color, to, blend.a * 2.0 - 1.0);
+}
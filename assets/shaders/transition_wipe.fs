@@ -0,0 +1,19 @@
+#version 330
+
+in vec2 UV;
+
+out vec4 output0;
+
+uniform vec4 blend;
+uniform sampler2D sampler0;
+uniform sampler2D sampler1;
+
+void main()
+{
+    // Sweep from left to right, with a soft edge so it doesn't look like a tear
+    const float edge = 0.1;
+    float t = blend.a * (1.0 + edge);
+    float m = smoothstep(t - edge, t, UV.x);
+
+    output0 = mix(texture(sampler1, UV), texture(sampler0, UV), m);
+}
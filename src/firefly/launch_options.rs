@@ -0,0 +1,275 @@
+use crate::snuff::core;
+use crate::snuff::core::GameStateKey;
+use crate::firefly::StateKey;
+
+use clap::{App, Arg};
+
+// Display settings are only set when given, anything else comes from the user's config
+pub struct LaunchOptions {
+    pub resolution: Option<(u16, u16)>,
+    pub scaling: Option<core::ScalingPolicy>,
+    pub mode: Option<core::WindowMode>,
+    pub monitor: Option<String>,
+    pub vsync: Option<bool>,
+    pub asset_root: Option<String>,
+    pub start_state: StateKey,
+    pub seed: u64,
+    pub mute: bool,
+    pub headless_frames: Option<u32>,
+    pub script: Option<String>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub capture: Option<String>,
+    pub capture_fps: f32,
+}
+
+//---------------------------------------------------------------------------------------------------
+fn parse_resolution(value: &str) -> Option<(u16, u16)> {
+    let mut parts = value.split('x');
+
+    let width = parts.next()?.trim().parse().ok()?;
+    let height = parts.next()?.trim().parse().ok()?;
+
+    if parts.next().is_some() || width == 0 || height == 0 {
+        return None;
+    }
+
+    Some((width, height))
+}
+
+impl LaunchOptions {
+    //---------------------------------------------------------------------------------------------------
+    pub fn from_args() -> LaunchOptions {
+        let state_names: Vec<String> = StateKey::all()
+            .iter()
+            .map(|key| format!("{:?}", key))
+            .collect();
+
+        let matches = App::new("Firefly - Reflection")
+            .version(env!("CARGO_PKG_VERSION"))
+            .arg(Arg::with_name("asset-root")
+                .long("asset-root")
+                .value_name("DIR")
+                .help("Directory to load the assets from, used as the working directory"))
+            .arg(Arg::with_name("working-dir")
+                .value_name("DIR")
+                .hidden(true)
+                .conflicts_with("asset-root"))
+            .arg(Arg::with_name("resolution")
+                .long("resolution")
+                .short("r")
+                .value_name("WIDTHxHEIGHT")
+                .help("Size of the window, or of the frames when running headless"))
+            .arg(Arg::with_name("scaling")
+                .long("scaling")
+                .value_name("POLICY")
+                .help("How the game is scaled up to the window, one of: integer, fit, stretch"))
+            .arg(Arg::with_name("fullscreen")
+                .long("fullscreen")
+                .short("f")
+                .help("Starts in fullscreen"))
+            .arg(Arg::with_name("borderless")
+                .long("borderless")
+                .conflicts_with("fullscreen")
+                .help("Starts in a borderless window covering the monitor"))
+            .arg(Arg::with_name("windowed")
+                .long("windowed")
+                .conflicts_with_all(&["fullscreen", "borderless"])
+                .help("Starts in a window"))
+            .arg(Arg::with_name("monitor")
+                .long("monitor")
+                .value_name("NAME")
+                .help("Monitor to go fullscreen or borderless on, by name or index"))
+            .arg(Arg::with_name("vsync")
+                .long("vsync")
+                .help("Waits for vertical sync when presenting frames"))
+            .arg(Arg::with_name("no-vsync")
+                .long("no-vsync")
+                .conflicts_with("vsync")
+                .help("Presents frames as soon as they are done"))
+            .arg(Arg::with_name("state")
+                .long("state")
+                .short("s")
+                .value_name("STATE")
+                .help(&format!("State to start in, one of: {}", state_names.join(", "))))
+            .arg(Arg::with_name("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("Seed for the random number generator, so the world is the same every run"))
+            .arg(Arg::with_name("mute")
+                .long("mute")
+                .short("m")
+                .help("Disables all audio output"))
+            .arg(Arg::with_name("headless")
+                .long("headless")
+                .value_name("FRAMES")
+                .help("Runs the given number of frames without a window or audio, then exits"))
+            .arg(Arg::with_name("script")
+                .long("script")
+                .value_name("FILE")
+                .requires("headless")
+                .help("Input script to feed to a headless run, see InputScript"))
+            .arg(Arg::with_name("record")
+                .long("record")
+                .value_name("FILE")
+                .help("Records all input to the given file when the game exits"))
+            .arg(Arg::with_name("replay")
+                .long("replay")
+                .value_name("FILE")
+                .conflicts_with_all(&["record", "script", "state", "seed"])
+                .help("Plays back a recording, starting with the same state and seed it was made with"))
+            .arg(Arg::with_name("capture")
+                .long("capture")
+                .value_name("DIR")
+                .conflicts_with("headless")
+                .help("Saves every frame as a numbered PNG in the given directory"))
+            .arg(Arg::with_name("capture-fps")
+                .long("capture-fps")
+                .value_name("FPS")
+                .requires("capture")
+                .help("Frame rate of the capture, the game runs at exactly this rate while capturing [default: 60]"))
+            .get_matches();
+
+        let resolution = matches.value_of("resolution").map(|resolution| {
+            parse_resolution(resolution).unwrap_or_else(|| {
+                println!("[LaunchOptions] Invalid resolution '{}', expected something like 1280x720", resolution);
+                std::process::exit(1);
+            })
+        });
+
+        let scaling = matches.value_of("scaling").map(|scaling| {
+            core::ScalingPolicy::from_name(scaling).unwrap_or_else(|| {
+                println!("[LaunchOptions] Unknown scaling policy '{}', expected one of: integer, fit, stretch", scaling);
+                std::process::exit(1);
+            })
+        });
+
+        let flag = |on: &str, off: &str| {
+            if matches.is_present(on) {
+                Some(true)
+            } else if matches.is_present(off) {
+                Some(false)
+            } else {
+                None
+            }
+        };
+
+        let mode = if matches.is_present("fullscreen") {
+            Some(core::WindowMode::Fullscreen)
+        } else if matches.is_present("borderless") {
+            Some(core::WindowMode::Borderless)
+        } else if matches.is_present("windowed") {
+            Some(core::WindowMode::Windowed)
+        } else {
+            None
+        };
+
+        let start_state = match matches.value_of("state") {
+            Some(name) => StateKey::from_name(name).unwrap_or_else(|| {
+                println!("[LaunchOptions] Unknown state '{}', expected one of: {}", name, state_names.join(", "));
+                std::process::exit(1);
+            }),
+            None => StateKey::Menu,
+        };
+
+        let seed = match matches.value_of("seed") {
+            Some(seed) => seed.parse().unwrap_or_else(|_| {
+                println!("[LaunchOptions] Invalid seed '{}', expected a positive number", seed);
+                std::process::exit(1);
+            }),
+            None => rand::random(),
+        };
+
+        let headless_frames = matches.value_of("headless").map(|frames| {
+            frames.parse().unwrap_or_else(|_| {
+                println!("[LaunchOptions] Invalid frame count '{}', expected a positive number", frames);
+                std::process::exit(1);
+            })
+        });
+
+        let capture_fps = match matches.value_of("capture-fps") {
+            Some(fps) => match fps.parse() {
+                Ok(fps) if fps > 0.0 => fps,
+                _ => {
+                    println!("[LaunchOptions] Invalid capture frame rate '{}', expected a number greater than zero", fps);
+                    std::process::exit(1);
+                }
+            },
+            None => 60.0,
+        };
+
+        LaunchOptions {
+            resolution,
+            scaling,
+            mode,
+            monitor: matches.value_of("monitor").map(String::from),
+            vsync: flag("vsync", "no-vsync"),
+            asset_root: matches
+                .value_of("asset-root")
+                .or_else(|| matches.value_of("working-dir"))
+                .map(String::from),
+            start_state,
+            seed,
+            mute: matches.is_present("mute"),
+            headless_frames,
+            script: matches.value_of("script").map(String::from),
+            record: matches.value_of("record").map(String::from),
+            replay: matches.value_of("replay").map(String::from),
+            capture: matches.value_of("capture").map(String::from),
+            capture_fps,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn apply_display(&self, display: &mut core::DisplayConfig) {
+        if let Some((width, height)) = self.resolution {
+            display.width = width;
+            display.height = height;
+        }
+
+        display.scaling = self.scaling.unwrap_or(display.scaling);
+
+        display.mode = self.mode.unwrap_or(display.mode);
+
+        if self.monitor.is_some() {
+            display.monitor = self.monitor.clone();
+        }
+        display.vsync = self.vsync.unwrap_or(display.vsync);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // An empty script when none was given, so a headless run just lets the game play by itself
+    pub fn input_script(&self) -> core::InputScript {
+        let path = match &self.script {
+            Some(path) => path,
+            None => return core::InputScript::new(),
+        };
+
+        core::InputScript::from_file(path).unwrap_or_else(|e| {
+            println!("[LaunchOptions] Could not load input script '{}': {}", path, e);
+            std::process::exit(1);
+        })
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn replay_recording(&self) -> Option<core::InputRecording> {
+        let path = self.replay.as_ref()?;
+
+        Some(core::InputRecording::from_file(path).unwrap_or_else(|e| {
+            println!("[LaunchOptions] Could not load input recording '{}': {}", path, e);
+            std::process::exit(1);
+        }))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn apply_asset_root(&self) {
+        if let Some(asset_root) = &self.asset_root {
+            if let Err(e) = std::env::set_current_dir(asset_root) {
+                println!("[main] Invalid asset root '{}': {}", asset_root, e);
+                std::process::exit(1);
+            }
+
+            println!("[main] Set working directory to '{}'", asset_root);
+        }
+    }
+}
@@ -1,8 +1,31 @@
+use crate::snuff::core;
+
+mod launch_options;
+pub mod settings;
 mod states;
 mod objects;
+pub mod systems;
+
+crate::game_state_keys!(StateKey { Menu, Main });
 
 pub use {
+    launch_options::LaunchOptions,
     states::MenuState,
     states::MainState,
     objects::Player
-};
\ No newline at end of file
+};
+
+//---------------------------------------------------------------------------------------------------
+// Shared by the game and the headless tests, so both run the same states
+pub fn create_state(
+    key: StateKey,
+    window: &mut core::Window,
+    audio_device: &core::AudioDevice,
+    config: &core::Config,
+    seed: u64,
+) -> Box<dyn core::GameState<StateKey>> {
+    match key {
+        StateKey::Menu => Box::new(MenuState::new(window, audio_device, config)),
+        StateKey::Main => Box::new(MainState::new(window, audio_device, config, seed)),
+    }
+}
@@ -1,30 +1,26 @@
 use crate::snuff::gfx;
 use crate::snuff::core;
+use crate::snuff::ecs;
 use crate::firefly::objects;
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
 pub struct Firefly {
-    transform: core::Transform,
-    textures: Vec<gfx::Texture2D>,
+    // Attached to whatever the firefly follows, the hierarchy carries it along and the firefly chases it
+    anchor: ecs::Entity,
+    textures: Vec<Rc<gfx::Texture2D>>,
     current_texture: usize,
     flip_timer: f32,
     offset_timer: f32,
     started_following: bool
 }
 
-impl objects::GameObject for Firefly {
-    fn render_data(&mut self) -> objects::RenderData {
-        objects::RenderData {
-            textures: vec![&self.textures[self.current_texture]],
-            transform: &mut self.transform
-        }
-    }
-}
-
 impl Firefly {
 
-    pub fn new(display: &glium::Display) -> Firefly {
+    pub fn new(device: &gfx::Device, anchor: ecs::Entity) -> Firefly {
         let mut firefly = Firefly {
-            transform: core::Transform::new(),
+            anchor,
             textures: Vec::new(),
             current_texture: 0,
             flip_timer: 0.0,
@@ -32,25 +28,49 @@ impl Firefly {
             started_following: false
         };
 
-        firefly.textures.push(gfx::Texture2D::from_image(display, "assets/textures/characters/firefly_up.png"));
-        firefly.textures.push(gfx::Texture2D::from_image(display, "assets/textures/characters/firefly_down.png"));
-
-        firefly.transform.set_size_2d(&(firefly.textures[0].dimensions_f() * 0.25));
+        firefly.textures.push(Rc::new(gfx::Texture2D::from_image(device, "assets/textures/characters/firefly_up.png")));
+        firefly.textures.push(Rc::new(gfx::Texture2D::from_image(device, "assets/textures/characters/firefly_down.png")));
 
         firefly
     }
 
-    pub fn update(&mut self, player_transform: &core::Transform, end_offset: f32, dt: f32) {
-        let player_x = player_transform.translation().x;
-        if player_x < 2.5 * 1280.0 && !self.started_following {
-            self.transform.set_translation_2d_f(player_x, 900.0);
-            return;
+    pub fn spawn(world: &mut ecs::World, device: &gfx::Device, shader: &Rc<RefCell<gfx::ShaderProgram>>, follow: ecs::Entity) -> ecs::Entity {
+        let anchor = world.create();
+        world
+            .add(anchor, core::Transform::new())
+            .attach(anchor, follow, ecs::KeepTransform::Local);
+
+        let firefly = Firefly::new(device, anchor);
+
+        let mut transform = core::Transform::new();
+        transform.set_size_2d(&(firefly.textures[0].dimensions_f() * 0.25));
+
+        let entity = world.create();
+        world
+            .add(entity, transform)
+            .add(entity, ecs::Sprite::new(&firefly.textures[0], shader, objects::FIREFLY_LAYER))
+            .add(entity, ecs::Interpolated::new())
+            .add(entity, firefly);
+
+        entity
+    }
+
+    pub fn anchor(&self) -> ecs::Entity {
+        self.anchor
+    }
+
+    // Moves the anchor around what it's attached to, and returns where it ended up in the world
+    pub fn update_anchor(&mut self, anchor: &mut core::Transform, end_offset: f32, dt: f32) -> nalgebra_glm::Vec2 {
+        let followed = anchor.parent_to_world();
+        let followed_x = followed[(0, 3)];
+
+        if followed_x < 2.5 * 1280.0 && !self.started_following {
+            anchor.set_translation_2d_f(0.0, 0.0);
+            return anchor.world_translation_2d();
         }
 
         self.started_following = true;
 
-        let damping = 3.0;
-
         self.offset_timer += dt * 0.25;
         if self.offset_timer > 1.0 {
             self.offset_timer = 0.0;
@@ -60,14 +80,25 @@ impl Firefly {
         let twirl_ratio = self.offset_timer * 6.28;
         let horizontal_offset = twirl_ratio.sin() * twirl;
         let vertical_offset = twirl_ratio.cos() * twirl * 0.25;
-        let diverge = if player_x > 3.75 * 1280.0 && player_x < 4.5 * 1280.0 { 550.0 } else { 0.0 };
-        
-        let offset = nalgebra_glm::vec2(horizontal_offset, 75.0 + vertical_offset + diverge + end_offset);
+        let diverge = if followed_x > 3.75 * 1280.0 && followed_x < 4.5 * 1280.0 { 550.0 } else { 0.0 };
 
-        let mut t = self.transform.translation_2d();
-        t = nalgebra_glm::lerp(&t, &(player_transform.translation_2d() + offset), 1.0 - f32::powf(1.0 / damping, dt));
+        anchor.set_translation_2d_f(horizontal_offset, 75.0 + vertical_offset + diverge + end_offset);
+        anchor.world_translation_2d()
+    }
 
-        self.transform.set_translation_2d(&t);
+    pub fn update(&mut self, transform: &mut core::Transform, sprite: &mut ecs::Sprite, target: &nalgebra_glm::Vec2, dt: f32) {
+        // Waits up in the trees until the player gets close enough
+        if !self.started_following {
+            transform.set_translation_2d_f(target.x, 900.0);
+            return;
+        }
+
+        let damping = 3.0;
+
+        let mut t = transform.translation_2d();
+        t = nalgebra_glm::lerp(&t, target, 1.0 - f32::powf(1.0 / damping, dt));
+
+        transform.set_translation_2d(&t);
 
         let flutter_speed = 20.0;
         self.flip_timer += dt * flutter_speed;
@@ -76,5 +107,7 @@ impl Firefly {
             self.flip_timer -= 1.0;
             self.current_texture = if self.current_texture == 0 { 1 } else { 0 };
         }
+
+        sprite.texture = self.textures[self.current_texture].clone();
     }
 }
\ No newline at end of file
@@ -1,9 +1,13 @@
-mod game_object;
 mod player;
 mod fly;
 
 pub use {
-    game_object::GameObject, game_object::RenderData, game_object::BackgroundObject,
     player::Player,
     fly::Firefly
-};
\ No newline at end of file
+};
+
+// Sprite layers, from back to front
+pub const TREE_LAYER: i32 = 0;
+pub const BACKGROUND_LAYER: i32 = 1;
+pub const PLAYER_LAYER: i32 = 2;
+pub const FIREFLY_LAYER: i32 = 3;
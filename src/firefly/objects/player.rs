@@ -1,7 +1,12 @@
 use crate::snuff::core;
 use crate::snuff::gfx;
+use crate::snuff::ecs;
+use crate::firefly;
 use crate::firefly::objects;
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
 #[derive(PartialEq)]
 enum PlayerState {
     Initial,
@@ -11,53 +16,88 @@ enum PlayerState {
 
 pub struct Player {
     state: PlayerState,
-    texture: gfx::Texture2D,
-    transform: core::Transform,
-    velocity: nalgebra_glm::Vec2,
+    // The child entity that's drawn, so squishing and flipping it doesn't carry over to what follows the player
+    body: ecs::Entity,
     grounded: bool,
+    landed: bool,
     hop_force: f32,
     acceleration: f32,
     squish_timer: f32,
-    border: nalgebra_glm::Vec3
-}
-
-impl objects::GameObject for Player {
-    fn render_data(&mut self) -> objects::RenderData {
-        objects::RenderData {
-            textures: vec![&self.texture],
-            transform: &mut self.transform
-        }
-    }
+    border: nalgebra_glm::Vec3,
+    hop_buffer: core::InputBuffer,
+    // How long a hop is still allowed after leaving the ground without hopping
+    coyote_time: f32,
+    // How long the player has to stay on the ground before holding a direction hops again
+    hop_chain_delay: f32,
+    air_time: f32,
+    // How long the player was in the air before the last landing
+    landing_air_time: f32,
+    ground_time: f32,
+    hopped: bool
 }
 
 impl Player {
-    pub fn new(display: &glium::Display) -> Player {
-        let mut player = Player {
+    pub fn new(body: ecs::Entity) -> Player {
+        Player {
             state: PlayerState::Initial,
-            texture: gfx::Texture2D::from_image(display, "assets/textures/characters/player.png"),
-            transform: core::Transform::new(),
-            velocity: nalgebra_glm::vec2(0.0, 0.0),
-            grounded: false,
+            body,
+            // Starts on the ground, so spawning isn't a landing
+            grounded: true,
+            landed: false,
             hop_force: 300.0,
             acceleration: 1000.0,
             squish_timer: 1.0,
-            border: nalgebra_glm::vec3(-700.0, -300.0, 7300.0)
-        };
+            border: nalgebra_glm::vec3(-700.0, -300.0, 7300.0),
+            hop_buffer: core::InputBuffer::new(0.15),
+            coyote_time: 0.1,
+            hop_chain_delay: 0.0,
+            air_time: 0.0,
+            landing_air_time: 0.0,
+            ground_time: 0.0,
+            hopped: false
+        }
+    }
 
-        player.transform
-            .set_size_2d(&player.texture.dimensions_f())
-            .set_translation_2d_f(player.border.x, player.border.y)
+    pub fn spawn(world: &mut ecs::World, device: &gfx::Device, shader: &Rc<RefCell<gfx::ShaderProgram>>, config: &core::Config) -> ecs::Entity {
+        let texture = Rc::new(gfx::Texture2D::from_image(device, "assets/textures/characters/player.png"));
+
+        let mut body_transform = core::Transform::new();
+        body_transform
+            .set_size_2d(&texture.dimensions_f())
             .set_anchor_2d_f(0.0, 0.25);
 
-        player
-    }
+        let body = world.create();
+        world
+            .add(body, body_transform)
+            .add(body, ecs::Sprite::new(&texture, shader, objects::PLAYER_LAYER))
+            .add(body, ecs::Interpolated::new());
+
+        let mut player = Player::new(body);
+        if let Some(duration) = config.tunable(firefly::settings::HOP_BUFFER_DURATION) {
+            player.set_hop_buffer_duration(duration);
+        }
+        if let Some(coyote_time) = config.tunable(firefly::settings::COYOTE_TIME) {
+            player.set_coyote_time(coyote_time);
+        }
+        if let Some(delay) = config.tunable(firefly::settings::HOP_CHAIN_DELAY) {
+            player.set_hop_chain_delay(delay);
+        }
+
+        let mut transform = core::Transform::new();
+        transform.set_translation_2d_f(player.border.x, player.border.y);
 
-    pub fn texture(&mut self) -> &gfx::Texture2D {
-        &self.texture
+        let entity = world.create();
+        world
+            .add(entity, transform)
+            .add(entity, ecs::Velocity::new(0.0, 0.0))
+            .add(entity, player)
+            .attach(body, entity, ecs::KeepTransform::Local);
+
+        entity
     }
 
-    pub fn transform(&mut self) -> &mut core::Transform {
-        &mut self.transform
+    pub fn body(&self) -> ecs::Entity {
+        self.body
     }
 
     pub fn border(&self) -> nalgebra_glm::Vec3 {
@@ -68,7 +108,38 @@ impl Player {
         return self.state == PlayerState::End;
     }
 
-    fn update_animations(&mut self, dt: f32) {
+    // Whether the player touched the ground during the last update
+    pub fn has_landed(&self) -> bool {
+        self.landed
+    }
+
+    pub fn landing_air_time(&self) -> f32 {
+        self.landing_air_time
+    }
+
+    // A press this long before landing still hops
+    pub fn set_hop_buffer_duration(&mut self, duration: f32) -> &mut Player {
+        self.hop_buffer.set_duration(duration);
+        self
+    }
+
+    pub fn set_coyote_time(&mut self, coyote_time: f32) -> &mut Player {
+        self.coyote_time = coyote_time.max(0.0);
+        self
+    }
+
+    pub fn set_hop_chain_delay(&mut self, delay: f32) -> &mut Player {
+        self.hop_chain_delay = delay.max(0.0);
+        self
+    }
+
+    // Leaving the ground without hopping still allows a hop for a moment, but only one per time in the air
+    fn can_hop(&self) -> bool {
+        self.grounded || (!self.hopped && self.air_time <= self.coyote_time)
+    }
+
+    // Only touches the body, after update has moved the player itself
+    pub fn update_animations(&mut self, body: &mut core::Transform, velocity: &nalgebra_glm::Vec2, dt: f32) {
 
         // Variables
         let pi = 3.14159;
@@ -77,12 +148,12 @@ impl Player {
 
         // Flipping
         let mut speed_scale = 1.0;
-        if self.velocity.x != 0.0 {
-            speed_scale = self.velocity.x.abs() / self.velocity.x;
+        if velocity.x != 0.0 {
+            speed_scale = velocity.x.abs() / velocity.x;
         }
         
         // Hopping
-        let hop_ratio = self.velocity.y.max(0.0).min(300.0) / 300.0;
+        let hop_ratio = velocity.y.clamp(0.0, 300.0) / 300.0;
         let hop_scale = 1.0 + (hop_ratio * pi * 2.0).sin() * 0.125;
         let hop_rot = (hop_ratio * pi).sin() * -0.25;
 
@@ -95,127 +166,227 @@ impl Player {
         let hop_squish = (self.squish_timer * pi).sin() * squish_scale;
 
         // Apply
-        self.transform
+        body
             .set_scale_2d_f(speed_scale * (1.0 + hop_squish), hop_scale - hop_squish * 0.5)
             .set_orientation(hop_rot);
     }
 
-    pub fn hop_right(&mut self)
+    pub fn hop_right(&mut self, velocity: &mut nalgebra_glm::Vec2)
     {
-        if !self.grounded {
+        if !self.can_hop() {
             return;
         }
 
-        if self.velocity.x < 0.0 {
-            self.velocity.x *= 0.75;
+        if velocity.x < 0.0 {
+            velocity.x *= 0.75;
         }
-        self.velocity.x += self.acceleration;
-        self.velocity.y = self.hop_force;
+        velocity.x += self.acceleration;
+        velocity.y = self.hop_force;
         self.grounded = false;
+        self.hopped = true;
     }
 
-    pub fn hop_left(&mut self)
+    pub fn hop_left(&mut self, velocity: &mut nalgebra_glm::Vec2)
     {
-        if !self.grounded {
+        if !self.can_hop() {
             return;
         }
 
-        if self.velocity.x > 0.0 {
-            self.velocity.x *= 0.75;
+        if velocity.x > 0.0 {
+            velocity.x *= 0.75;
         }
-        self.velocity.x -= self.acceleration;
-        self.velocity.y = self.hop_force;
+        velocity.x -= self.acceleration;
+        velocity.y = self.hop_force;
         self.grounded = false;
+        self.hopped = true;
     }
 
-    pub fn damp_velocity(&mut self, dt: f32) {
+    pub fn damp_velocity(&mut self, velocity: &mut nalgebra_glm::Vec2, dt: f32) {
         let damping = 50.0;
-        self.velocity.x = nalgebra_glm::lerp_scalar(self.velocity.x, 0.0, 1.0 - f32::powf(1.0 / damping, dt));
+        velocity.x = nalgebra_glm::lerp_scalar(velocity.x, 0.0, 1.0 - f32::powf(1.0 / damping, dt));
     }
 
-    pub fn update_velocity(&mut self, dt: f32) {
+    // The velocity has already been applied to the transform by the time this runs
+    pub fn update_velocity(&mut self, transform: &mut core::Transform, velocity: &mut nalgebra_glm::Vec2, dt: f32) {
         // Variables
 
         let fall_speed = 1000.0;
 
-        let mut t = self.transform.translation_2d() + self.velocity * dt;
+        let mut t = transform.translation_2d();
+        self.landed = false;
 
         // Make sure we don't fall through the ground..
         if t.y <= self.border.y && !self.grounded {
             t.y = self.border.y;
-            self.velocity.y = 0.0;
+            velocity.y = 0.0;
             self.grounded = true;
+            self.landed = true;
             self.squish_timer = 0.0;
+            self.hopped = false;
+            self.landing_air_time = self.air_time;
+            self.air_time = 0.0;
+            self.ground_time = 0.0;
         }
 
         // And that we cannot walk too far back or forward..
         if t.x < self.border.x {
             t.x = self.border.x;
-            self.velocity.x = -self.velocity.x;
+            velocity.x = -velocity.x;
         }
         
         if t.x > self.border.z {
             t.x = self.border.z;
-            self.velocity.x = 0.0;
+            velocity.x = 0.0;
             self.state = PlayerState::End;
         }
 
         if self.grounded {
-            self.damp_velocity(dt);
+            self.damp_velocity(velocity, dt);
         }
         else {
-            self.velocity.y -= dt * fall_speed;
+            velocity.y -= dt * fall_speed;
+        }
+
+        // Landing this update counts as no time at all
+        if self.grounded && !self.landed {
+            self.ground_time += dt;
+        }
+        else if !self.grounded {
+            self.air_time += dt;
         }
         
         // Set the new translation
-        self.transform.set_translation_2d(&t);
+        transform.set_translation_2d(&t);
     }
 
-    pub fn clamp_velocity(&mut self) {
+    pub fn clamp_velocity(&mut self, velocity: &mut nalgebra_glm::Vec2) {
         let max_speed = 200.0;
         let max_fall_speed = 2000.0;
 
-        self.velocity = nalgebra_glm::clamp_vec(
-            &self.velocity, 
+        *velocity = nalgebra_glm::clamp_vec(
+            velocity, 
             &nalgebra_glm::vec2(-max_speed, -max_fall_speed), 
             &nalgebra_glm::vec2(max_speed, self.hop_force));
     }
 
-    pub fn update_input(&mut self, window: &core::Window) {
-        if window.is_key_down(glium::glutin::VirtualKeyCode::Right) {
-            self.hop_right();
+    pub fn update_input(&mut self, velocity: &mut nalgebra_glm::Vec2, window: &core::Window) {
+        let hops = [firefly::settings::HOP_RIGHT, firefly::settings::HOP_LEFT];
+        self.hop_buffer.buffer_pressed(window, &hops);
+
+        if !self.can_hop() {
+            return;
         }
-        else if window.is_key_down(glium::glutin::VirtualKeyCode::Left) {
-            self.hop_left();
+
+        // A press always goes through as soon as it can, holding only hops again once the chain delay has passed
+        match self.hop_buffer.consume_latest(&hops) {
+            Some(hop) if hop == firefly::settings::HOP_RIGHT => self.hop_right(velocity),
+            Some(_) => self.hop_left(velocity),
+            None if self.ground_time < self.hop_chain_delay => {},
+            None if window.is_action_down(firefly::settings::HOP_RIGHT) => self.hop_right(velocity),
+            None if window.is_action_down(firefly::settings::HOP_LEFT) => self.hop_left(velocity),
+            None => {}
         }
     }
 
-    pub fn initial_state(&mut self) {
+    pub fn initial_state(&mut self, velocity: &mut nalgebra_glm::Vec2) {
         // Hop right once, then give control to the user
-        self.hop_right();
+        self.hop_right(velocity);
         self.state = PlayerState::User;
     }
 
-    pub fn update(&mut self, dt: f32, window: &core::Window) {
+    pub fn update(&mut self, transform: &mut core::Transform, velocity: &mut nalgebra_glm::Vec2, dt: f32, window: &core::Window) {
+        self.hop_buffer.update(dt);
 
         // Constant accelerations
-        self.update_velocity(dt);
+        self.update_velocity(transform, velocity, dt);
 
         match &self.state {
             PlayerState::Initial => {
-                self.initial_state();
+                self.initial_state(velocity);
             },
             PlayerState::User => {
                 // Input
-                self.update_input(window);
+                self.update_input(velocity, window);
             },
             PlayerState::End => {}
         }
 
         // Clamp before end of frame
-        self.clamp_velocity();
+        self.clamp_velocity(velocity);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Powers of two keep the air time exact, so the edge of the window can be hit on the dot
+    const DT: f32 = 0.125;
+
+    // A player that has just walked off a ledge, high enough not to land during a test
+    fn falling_player(coyote_time: f32) -> (Player, core::Transform, nalgebra_glm::Vec2) {
+        let mut world = ecs::World::new();
+        let mut player = Player::new(world.create());
+        player.set_coyote_time(coyote_time);
+        player.grounded = false;
+
+        let mut transform = core::Transform::new();
+        transform.set_translation_2d_f(0.0, 1000.0);
+
+        (player, transform, nalgebra_glm::vec2(0.0, 0.0))
+    }
+
+    #[test]
+    fn hops_are_allowed_until_the_coyote_time_runs_out() {
+        let (mut player, mut transform, mut velocity) = falling_player(0.5);
+
+        for _ in 0..4 {
+            player.update_velocity(&mut transform, &mut velocity, DT);
+        }
+        assert_eq!(player.air_time, 0.5);
+        assert!(player.can_hop());
+
+        player.update_velocity(&mut transform, &mut velocity, DT);
+        assert!(!player.can_hop());
+    }
+
+    #[test]
+    fn coyote_time_only_allows_one_hop() {
+        let (mut player, mut transform, mut velocity) = falling_player(0.5);
+        player.update_velocity(&mut transform, &mut velocity, DT);
+
+        player.hop_right(&mut velocity);
+        assert_eq!(velocity.y, player.hop_force);
+        assert!(!player.can_hop());
+
+        velocity.y = 0.0;
+        player.hop_left(&mut velocity);
+        assert_eq!(velocity.y, 0.0);
+    }
 
-        // Update all animations accordingly
-        self.update_animations(dt);
+    #[test]
+    fn hopping_off_the_ground_uses_up_the_coyote_time() {
+        let mut world = ecs::World::new();
+        let mut player = Player::new(world.create());
+        let mut velocity = nalgebra_glm::vec2(0.0, 0.0);
+
+        player.hop_right(&mut velocity);
+
+        assert!(!player.grounded);
+        assert_eq!(player.air_time, 0.0);
+        assert!(!player.can_hop());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn landing_gives_the_hop_back() {
+        let (mut player, mut transform, mut velocity) = falling_player(0.0);
+        player.hop_right(&mut velocity);
+        player.update_velocity(&mut transform, &mut velocity, DT);
+        assert!(!player.can_hop());
+
+        transform.set_translation_2d_f(0.0, player.border.y);
+        player.update_velocity(&mut transform, &mut velocity, DT);
+
+        assert!(player.has_landed());
+        assert!(player.can_hop());
+    }
+}
@@ -0,0 +1,51 @@
+use crate::snuff::core;
+
+use crate::snuff::core::{AxisDirection, Binding, GamepadAxis, GamepadButton};
+use glium::glutin::VirtualKeyCode;
+
+pub const APP_NAME: &str = "firefly-reflection";
+
+pub const MUSIC_BUS: &str = "music";
+pub const AMBIENCE_BUS: &str = "ambience";
+
+pub const HOP_LEFT: &str = "hop_left";
+pub const HOP_RIGHT: &str = "hop_right";
+pub const SKIP: &str = "skip";
+pub const RESTART: &str = "restart";
+pub const QUIT: &str = "quit";
+
+pub const HOP_BUFFER_DURATION: &str = "hop_buffer_duration";
+pub const COYOTE_TIME: &str = "coyote_time";
+pub const HOP_CHAIN_DELAY: &str = "hop_chain_delay";
+
+//---------------------------------------------------------------------------------------------------
+pub fn add_defaults(config: &mut core::Config) {
+    config
+        .add_default_volume(MUSIC_BUS, 1.0)
+        .add_default_volume(AMBIENCE_BUS, 1.0)
+        .add_default_tunable(HOP_BUFFER_DURATION, 0.15)
+        .add_default_tunable(COYOTE_TIME, 0.1)
+        .add_default_tunable(HOP_CHAIN_DELAY, 0.0)
+        .add_default_bindings(HOP_LEFT, &[
+            Binding::Key(VirtualKeyCode::Left),
+            Binding::GamepadButton(GamepadButton::DPadLeft),
+            Binding::GamepadAxis(GamepadAxis::LeftX, AxisDirection::Negative),
+        ])
+        .add_default_bindings(HOP_RIGHT, &[
+            Binding::Key(VirtualKeyCode::Right),
+            Binding::GamepadButton(GamepadButton::DPadRight),
+            Binding::GamepadAxis(GamepadAxis::LeftX, AxisDirection::Positive),
+        ])
+        .add_default_bindings(SKIP, &[
+            Binding::Key(VirtualKeyCode::Space),
+            Binding::GamepadButton(GamepadButton::South),
+        ])
+        .add_default_bindings(RESTART, &[
+            Binding::Key(VirtualKeyCode::R),
+            Binding::GamepadButton(GamepadButton::Select),
+        ])
+        .add_default_bindings(QUIT, &[
+            Binding::Key(VirtualKeyCode::Escape),
+            Binding::GamepadButton(GamepadButton::Start),
+        ]);
+}
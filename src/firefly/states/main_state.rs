@@ -1,116 +1,118 @@
 use crate::snuff::core;
+use crate::snuff::ecs;
 use crate::snuff::gfx;
+use crate::firefly;
+use crate::firefly::StateKey;
 use crate::firefly::objects;
-use crate::firefly::objects::GameObject;
 use rand::prelude::*;
 
-use std::fs::File;
-use std::io::BufReader;
-
-#[derive(PartialEq)]
-enum FadeMode {
-    In,
-    Out,
-    Done
-}
+use std::cell::RefCell;
+use std::rc::Rc;
 
 pub struct MainState {
     camera: core::Camera,
+    // Where the camera was at the start of the tick, to draw it in between
+    previous_camera_translation: Option<nalgebra_glm::Vec3>,
+    camera_controller: core::CameraController,
+    camera_shake: core::CameraShake,
     fullscreen_quad: gfx::Mesh,
     quad: gfx::Mesh,
+    sprite_batch: gfx::SpriteBatch,
     color_target: gfx::Texture2D,
     background_shader: gfx::ShaderProgram,
     fullscreen_shader: gfx::ShaderProgram,
-    shader: gfx::ShaderProgram,
-    tree_shader: gfx::ShaderProgram,
     widget_shader: gfx::ShaderProgram,
-    fade: f32,
-    fade_timer: f32,
-    fade_from: f32,
-    fade_mode: FadeMode,
-    player: objects::Player,
-    backgrounds: Vec<objects::BackgroundObject>,
-    background_textures: Vec<gfx::Texture2D>,
-    trees: Vec<objects::BackgroundObject>,
-    tree_textures: Vec<gfx::Texture2D>,
+    world: ecs::World,
+    player: ecs::Entity,
     text_textures: Vec<gfx::Texture2D>,
     text_timer: f32,
     current_text: usize,
+    audio_device: core::AudioDevice,
     music_sink: rodio::Sink,
     rain_sink: rodio::Sink,
-    first_frame: bool,
+    music_volume: f32,
+    ambience_volume: f32,
     audio_swapped: bool,
     raininess: f32,
-    firefly: objects::Firefly,
     end_offset: f32,
     color_blend_in: f32
 }
 
 impl MainState {
-    pub fn new(window: &mut core::Window) -> MainState {
-        let display = window.display();
-        let audio_device = &rodio::default_output_device().unwrap();
+    pub fn new(window: &mut core::Window, audio_device: &core::AudioDevice, config: &core::Config, seed: u64) -> MainState {
+        let logical_size = window.logical_size_f();
+        let (logical_width, logical_height) = (window.logical_width(), window.logical_height());
+        let device = window.device();
+
+        let shader = Rc::new(RefCell::new(gfx::ShaderProgram::from_source(device, "assets/shaders/sprite.vs".to_string(), "assets/shaders/sprite.fs".to_string()).unwrap()));
+        let tree_shader = Rc::new(RefCell::new(gfx::ShaderProgram::from_source(device, "assets/shaders/tree.vs".to_string(), "assets/shaders/sprite.fs".to_string()).unwrap()));
+
+        let mut world = ecs::World::new();
+        world
+            .register::<objects::Player>()
+            .register::<objects::Firefly>();
+
+        let player = objects::Player::spawn(&mut world, device, &shader, config);
+        objects::Firefly::spawn(&mut world, device, &shader, player);
 
         let mut main_state = MainState {
             camera: core::Camera::new(),
-            fullscreen_quad: gfx::Mesh::create_quad(display, false),
-            quad: gfx::Mesh::create_quad(display, true),
-            color_target: gfx::Texture2D::empty(display, 1280, 720),
-            background_shader: gfx::ShaderProgram::from_source(display, "assets/shaders/fullscreen.vs".to_string(), "assets/shaders/background.fs".to_string()).unwrap(),
-            fullscreen_shader: gfx::ShaderProgram::from_source(display, "assets/shaders/fullscreen.vs".to_string(), "assets/shaders/fullscreen.fs".to_string()).unwrap(),
-            shader: gfx::ShaderProgram::from_source(display, "assets/shaders/simple.vs".to_string(), "assets/shaders/simple.fs".to_string()).unwrap(),
-            tree_shader: gfx::ShaderProgram::from_source(display, "assets/shaders/tree.vs".to_string(), "assets/shaders/simple.fs".to_string()).unwrap(),
-            widget_shader: gfx::ShaderProgram::from_source(display, "assets/shaders/widget.vs".to_string(), "assets/shaders/widget.fs".to_string()).unwrap(),
-            fade: 1.0,
-            fade_timer: 0.0,
-            fade_from: 1.0,
-            fade_mode: FadeMode::In,
-            player: objects::Player::new(display),
-            backgrounds: Vec::new(),
-            background_textures: Vec::new(),
-            trees: Vec::new(),
-            tree_textures: Vec::new(),
+            previous_camera_translation: None,
+            camera_controller: core::CameraController::new(),
+            camera_shake: core::CameraShake::new(),
+            fullscreen_quad: gfx::Mesh::create_quad(device, false),
+            quad: gfx::Mesh::create_quad(device, true),
+            sprite_batch: gfx::SpriteBatch::new(),
+            color_target: gfx::Texture2D::empty(device, logical_width, logical_height),
+            background_shader: gfx::ShaderProgram::from_source(device, "assets/shaders/fullscreen.vs".to_string(), "assets/shaders/background.fs".to_string()).unwrap(),
+            fullscreen_shader: gfx::ShaderProgram::from_source(device, "assets/shaders/fullscreen.vs".to_string(), "assets/shaders/fullscreen.fs".to_string()).unwrap(),
+            widget_shader: gfx::ShaderProgram::from_source(device, "assets/shaders/widget.vs".to_string(), "assets/shaders/widget.fs".to_string()).unwrap(),
+            world,
+            player,
             text_textures: Vec::new(),
             text_timer: 0.0,
             current_text: 0,
-            music_sink: rodio::Sink::new(audio_device),
-            rain_sink: rodio::Sink::new(audio_device),
-            first_frame: true,
+            audio_device: audio_device.clone(),
+            music_sink: audio_device.create_sink(),
+            rain_sink: audio_device.create_sink(),
+            music_volume: config.volume(firefly::settings::MUSIC_BUS),
+            ambience_volume: config.volume(firefly::settings::AMBIENCE_BUS),
             audio_swapped: false,
             raininess: 1.0,
-            firefly: objects::Firefly::new(display),
             end_offset: 0.0,
             color_blend_in: 0.0
         };
 
         let num_backgrounds = 7;
-        let background_offset = main_state.player.border().y + 160.0;
+        let border = main_state.player_border();
+        let background_offset = border.y + 160.0;
 
         for i in 0..num_backgrounds {
-            let mut background = objects::BackgroundObject {
-                background_index: i,
-                transform: core::Transform::new()
-            };
+            let texture = Rc::new(
+                gfx::Texture2D::from_image(device, &format!("assets/textures/backgrounds/{}.png", i)[..]).with_nearest_filter());
 
-            background.transform
+            let mut transform = core::Transform::new();
+            transform
                 .set_size_2d_f(1280.0, 720.0)
                 .set_translation_2d_f((i as f32) * 1280.0, background_offset);
 
-            main_state.backgrounds.push(background);
-            main_state.background_textures.push(
-                gfx::Texture2D::from_image(display, &format!("assets/textures/backgrounds/{}.png", i)[..]).with_nearest_filter());
+            let background = main_state.world.create();
+            main_state.world
+                .add(background, transform)
+                .add(background, ecs::Sprite::new(&texture, &shader, objects::BACKGROUND_LAYER));
         }
 
         let num_tree_textures = 4;
+        let mut tree_textures = Vec::new();
         for i in 0..num_tree_textures {
-            main_state.tree_textures.push(
-                gfx::Texture2D::from_image(display, &format!("assets/textures/trees/{}.png", i)[..]).with_nearest_filter());
+            tree_textures.push(Rc::new(
+                gfx::Texture2D::from_image(device, &format!("assets/textures/trees/{}.png", i)[..]).with_nearest_filter()));
         }
 
         let num_text_textures = 16;
         for i in 0..num_text_textures {
             main_state.text_textures.push(
-                gfx::Texture2D::from_image(display, &format!("assets/textures/story/{}.png", i)[..]).with_nearest_filter());
+                gfx::Texture2D::from_image(device, &format!("assets/textures/story/{}.png", i)[..]).with_nearest_filter());
         }
 
         let num_trees = 80;
@@ -119,142 +121,114 @@ impl MainState {
         let max_offset = 30.0;
         let f_num_trees = num_tree_textures as f32;
 
-        let mut rng = rand::thread_rng();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
         for _ in 0..num_trees {
             let mut background_index: f32 = rng.gen();
             background_index = background_index * f_num_trees;
 
-            let mut tree = objects::BackgroundObject {
-                background_index: background_index as usize,
-                transform: core::Transform::new()
-            };
+            let texture = &tree_textures[background_index as usize];
 
             let mut size: f32 = rng.gen();
             size = 1.0 - size * 0.4;
 
-            tree.transform
-                .set_translation_2d_f(tree_offset, main_state.player.border().y)
+            let mut transform = core::Transform::new();
+            transform
+                .set_translation_2d_f(tree_offset, border.y)
                 .set_anchor_2d_f(0.0, 0.5)
-                .set_size_2d(&(main_state.tree_textures[tree.background_index].dimensions_f() * size))
-                .set_scale_2d_f(if rand::random() { -1.0 } else { 1.0 }, 1.0);
+                .set_size_2d(&(texture.dimensions_f() * size))
+                .set_scale_2d_f(if rng.gen() { -1.0 } else { 1.0 }, 1.0);
 
             let random_offset: f32 = rng.gen();
             tree_offset += min_offset + (max_offset - min_offset) * random_offset;
 
-            main_state.trees.push(tree);
+            let tree = main_state.world.create();
+            main_state.world
+                .add(tree, transform)
+                .add(tree, ecs::Sprite::new(texture, &tree_shader, objects::TREE_LAYER));
         }
 
         main_state.camera
             .set_orthographic(true)
-            .set_orthographic_size_both_f(1280.0, 720.0)
+            .set_orthographic_size_both(&logical_size)
             .transform().set_translation_f(0.0, 0.0, -1.0);
 
+        // Never show what's left of the first background
+        main_state.camera_controller
+            .set_damping(100.0)
+            .set_bounds(&core::Rect::new(
+                &nalgebra_glm::vec2(0.0, f32::NEG_INFINITY),
+                &nalgebra_glm::vec2(f32::INFINITY, f32::INFINITY)));
+
+        main_state.camera_shake
+            .set_max_translation_f(12.0, 8.0)
+            .set_max_rotation(0.02)
+            .set_seed(seed as u32);
+
         main_state
     }
+
+    // How many of the story texts have been shown so far
+    pub fn current_text(&self) -> usize {
+        self.current_text
+    }
 }
 
 impl MainState {
     fn set_defaults(&mut self) {
-        self.fade = 1.0;
-        self.fade_in();
-
-        let audio_device = &rodio::default_output_device().unwrap();
-        
-        let audio_file = File::open("assets/audio/music_mid.mp3").unwrap();
-        let audio_source = rodio::Decoder::new(BufReader::new(audio_file)).unwrap();
+        self.music_sink = self.audio_device.play_file("assets/audio/music_mid.mp3");
+        self.rain_sink = self.audio_device.play_file("assets/audio/rain.mp3");
+        self.rain_sink.set_volume(self.ambience_volume);
 
-        self.music_sink = rodio::Sink::new(audio_device);
-        self.music_sink.append(audio_source);
-
-        let audio_file = File::open("assets/audio/rain.mp3").unwrap();
-        let audio_source = rodio::Decoder::new(BufReader::new(audio_file)).unwrap();
+        self.camera_shake.reset();
+    }
 
-        self.rain_sink = rodio::Sink::new(audio_device);
-        self.rain_sink.append(audio_source);
+    fn player_border(&self) -> nalgebra_glm::Vec3 {
+        self.world.storage::<objects::Player>().get(self.player).unwrap().border()
     }
 
     fn play_end_music(&mut self) {
-        let audio_file = File::open("assets/audio/music_end.mp3").unwrap();
-        let audio_source = rodio::Decoder::new(BufReader::new(audio_file)).unwrap();
-
-        self.music_sink = rodio::Sink::new(&rodio::default_output_device().unwrap());
-        self.music_sink.append(audio_source);
+        self.music_sink = self.audio_device.play_file("assets/audio/music_end.mp3");
 
         self.audio_swapped = true;
     }
-
-    fn fade_in(&mut self) {
-        self.fade_mode = FadeMode::In;
-        self.fade_from = self.fade;
-        self.fade_timer = 0.0;
-    }
-
-    fn fade_out(&mut self) {
-        self.fade_mode = FadeMode::Out;
-        self.fade_from = self.fade;
-        self.fade_timer = 0.0;
-    }
-
-    fn update_fade(&mut self, dt: f32) {
-
-        if self.fade_mode != FadeMode::Done {
-            let fade_duration = 2.0;
-            self.fade_timer += dt * (1.0 / fade_duration);
-            self.fade_timer = self.fade_timer.min(1.0);
-        }
-
-        let ease = core::easing::out_cubic(self.fade_timer);
-        
-        match &self.fade_mode {
-            FadeMode::In => {
-                self.fade = self.fade_from * (1.0 - ease);
-            },
-            FadeMode::Out => {
-                self.fade = self.fade_from * (1.0 - ease) + ease;
-            },
-            FadeMode::Done => {}
-        }
-    }
 }
 
-impl core::GameState for MainState {
+impl core::GameState<StateKey> for MainState {
     fn on_enter(&mut self) {
         self.set_defaults();
     }
 
-    fn update(&mut self, dt: f32, window: &core::Window) -> Option<String> {
+    fn update(&mut self, dt: f32, window: &core::Window) -> Option<core::StateTransition<StateKey>> {
 
-        if self.first_frame == true {
+        if window.is_action_released(firefly::settings::RESTART) {
+            return Some(core::StateTransition::Switch(StateKey::Menu));
+        }
 
-            self.music_sink.play();
-            self.rain_sink.play();
+        // Keep showing the whole logical resolution, in case it changed
+        self.camera.set_orthographic_size_both(&window.logical_size_f());
 
-            self.first_frame = false;
-            return None;
-        }
+        self.previous_camera_translation = Some(self.camera.transform().translation());
+        ecs::systems::store_previous_transforms(&self.world);
 
-        // Debug
-        
-        let dt = if window.is_key_down(glium::glutin::VirtualKeyCode::RBracket) { dt * 10.0 } else { dt };
+        ecs::systems::apply_velocity(&self.world, dt);
+        firefly::systems::update_players(&self.world, dt, window);
 
-        if window.is_key_released(glium::glutin::VirtualKeyCode::R) {
-            return Some(String::from("MenuState"));
-        }
-        else if window.is_key_released(glium::glutin::VirtualKeyCode::F) {
-            self.fade_out();
-        }
-        else if window.is_key_released(glium::glutin::VirtualKeyCode::G) {
-            self.fade_in();
-        }
+        ecs::systems::propagate_transforms(&self.world);
+        firefly::systems::update_fireflies(&self.world, self.end_offset, dt);
 
-        // Fading
+        let (player_x, at_end, landed, air_time) = {
+            let mut transforms = self.world.storage_mut::<core::Transform>();
+            let players = self.world.storage::<objects::Player>();
+            let player = players.get(self.player).unwrap();
 
-        self.update_fade(dt);
-        self.player.update(dt, window);
-        self.firefly.update(self.player.transform(), self.end_offset, dt);
+            (transforms.get_mut(self.player).unwrap().world_translation_2d().x, player.is_at_end(), player.has_landed(), player.landing_air_time())
+        };
 
-        let player_x = self.player.transform().translation().x;
-        let at_end = self.player.is_at_end();
+        // Only a real fall shakes the camera, touching down where the player spawned doesn't
+        if landed && air_time > 0.2 {
+            self.camera_shake.add_trauma(0.3);
+            self.camera_shake.add_impulse_f(0.0, -4.0);
+        }
 
         if at_end {
             self.end_offset += dt * 14.0;
@@ -266,14 +240,14 @@ impl core::GameState for MainState {
         }
 
         // "Cross-fade" the audio
-        let border = self.player.border();
+        let border = self.player_border();
         let volume_ratio = if at_end && self.current_text > 6 { 1.0 } else { 1.0 - ((player_x - border.x) / (border.z - border.x)).powf(2.0) };
 
         if at_end && !self.audio_swapped && self.current_text > 6 {
             self.play_end_music();
         }
 
-        self.music_sink.set_volume(volume_ratio);
+        self.music_sink.set_volume(volume_ratio * self.music_volume);
 
         // Rain
 
@@ -281,7 +255,7 @@ impl core::GameState for MainState {
             self.raininess -= dt * (1.0 / 14.0);
             self.raininess = self.raininess.max(0.0);
 
-            self.rain_sink.set_volume(self.raininess);
+            self.rain_sink.set_volume(self.raininess * self.ambience_volume);
         }
 
         // Color
@@ -293,13 +267,14 @@ impl core::GameState for MainState {
 
         // Follow a point around with the camera
 
-        let camera_damping = 100.0;
-        let camera_offset = nalgebra_glm::vec2(0.0, 100.0 + self.end_offset);
+        self.camera_controller.set_offset_f(0.0, 100.0 + self.end_offset);
 
-        let mut t = self.camera.transform().translation_2d();
-        t = nalgebra_glm::lerp(&t, &(self.player.transform().translation_2d() + camera_offset), 1.0 - f32::powf(1.0 / camera_damping, dt));
-        t.x = t.x.max(0.0);
-        self.camera.transform().set_translation_2d(&t);
+        if let Some(player_transform) = self.world.storage_mut::<core::Transform>().get_mut(self.player) {
+            self.camera_controller.update(&mut self.camera, player_transform, dt);
+        }
+
+        self.camera_shake.update(dt);
+        self.camera_shake.apply(&mut self.camera);
 
         // Text
 
@@ -312,7 +287,13 @@ impl core::GameState for MainState {
         None
     }
 
-    fn draw(&mut self, command_buffer: &mut gfx::CommandBuffer, _dt: f32) {
+    fn draw(&mut self, command_buffer: &mut gfx::CommandBuffer, alpha: f32) {
+        // Drawn in between the last two ticks, and put back once everything is drawn
+        let camera_translation = self.camera.transform().translation();
+
+        if let Some(previous) = self.previous_camera_translation {
+            self.camera.transform().set_translation(&nalgebra_glm::lerp(&previous, &camera_translation, alpha));
+        }
 
         let mut fullscreen_transform = core::Transform::new();
         let mut text_transform = core::Transform::new();
@@ -322,6 +303,13 @@ impl core::GameState for MainState {
             .set_size_2d(&self.text_textures[0].dimensions_f())
             .set_translation_2d_f(0.0, 170.0 + 30.0 * text_ease);
 
+        let (width, height) = command_buffer.dimensions();
+        let target_dimensions = self.color_target.dimensions();
+
+        if width != 0 && height != 0 && (target_dimensions.x as u32 != width || target_dimensions.y as u32 != height) {
+            self.color_target = gfx::Texture2D::empty(command_buffer.device(), width as u16, height as u16);
+        }
+
         // Draw background
         command_buffer.set_blend_color(self.color_blend_in, 1.0, 1.0, self.raininess);
         let mut target = command_buffer.render_target(vec![&self.color_target]);
@@ -340,49 +328,10 @@ impl core::GameState for MainState {
         command_buffer.set_blend_color(1.0, 1.0, 1.0, 1.0);
         let mut target = command_buffer.render_target(vec![&self.color_target]);
 
-        for it in self.trees.iter_mut() {
-            command_buffer.draw_into_target(
-                &mut target,
-                &mut self.camera,
-                &self.quad,
-                &mut it.transform,
-                &mut self.tree_shader,
-                &vec![&self.tree_textures[it.background_index]]);
-        }
-
-        for it in self.backgrounds.iter_mut() {
-            command_buffer.draw_into_target(
-                &mut target,
-                &mut self.camera,
-                &self.quad,
-                &mut it.transform,
-                &mut self.shader,
-                &vec![&self.background_textures[it.background_index]]);
-        }
-
-        // Render player
-
-        let render_data = self.player.render_data();
-        command_buffer.draw_into_target(
-            &mut target,
-            &mut self.camera,
-            &self.quad,
-            render_data.transform,
-            &mut self.shader,
-            &render_data.textures);
-
-        // Render firefly
-        let render_data = self.firefly.render_data();
-        command_buffer.draw_into_target(
-            &mut target,
-            &mut self.camera,
-            &self.quad,
-            render_data.transform,
-            &mut self.shader,
-            &render_data.textures);
+        ecs::systems::draw_sprites(&self.world, command_buffer, &mut target, &mut self.camera, &mut self.sprite_batch, alpha);
 
         // Final pass
-        command_buffer.set_blend_color(1.0, 1.0, 1.0, 1.0 - self.fade);
+        command_buffer.set_blend_color(1.0, 1.0, 1.0, 1.0);
         command_buffer.fullscreen_pass(
             &mut self.camera,
             &mut self.fullscreen_shader, 
@@ -394,6 +343,8 @@ impl core::GameState for MainState {
             command_buffer.set_blend_color(1.0, 1.0, 1.0, c);
             command_buffer.draw(&mut self.camera, &self.quad, &mut text_transform, &mut self.widget_shader, &vec![&self.text_textures[self.current_text]]);
         }
+
+        self.camera.transform().set_translation(&camera_translation);
     }
 
     fn on_leave(&mut self) {
@@ -1,8 +1,8 @@
 use crate::snuff::core;
+use crate::snuff::core::Animation;
 use crate::snuff::gfx;
-
-use std::fs::File;
-use std::io::BufReader;
+use crate::firefly;
+use crate::firefly::StateKey;
 
 pub struct MenuState {
     camera: core::Camera,
@@ -13,54 +13,114 @@ pub struct MenuState {
     ground_texture: gfx::Texture2D,
     background_texture: gfx::Texture2D,
     player_texture: gfx::Texture2D,
+    title_alpha: core::Animated<f32>,
+    subtitle_slide: core::Animated<f32>,
+    slide_down: core::Animated<f32>,
+    player_hop: core::Animated<f32>,
+    text_progress: core::Animated<f32>,
+    intro: core::Sequence,
+    // The intro values at the start of the tick, to draw in between
+    previous_frame: IntroFrame,
+    audio_device: core::AudioDevice,
+    sink: rodio::Sink,
+    music_volume: f32,
+    texts: Vec<gfx::Texture2D>
+}
+
+#[derive(Clone, Copy)]
+struct IntroFrame {
     title_alpha: f32,
     subtitle_slide: f32,
     slide_down: f32,
     player_hop: f32,
-    fade_to_black: f32,
-    is_first_frame: bool,
-    player_transform: core::Transform,
-    sink: rodio::Sink,
-    texts: Vec<gfx::Texture2D>,
-    current_text: usize,
-    text_timer: f32
+    text_progress: f32
+}
+
+impl IntroFrame {
+    fn lerp(&self, to: &IntroFrame, alpha: f32) -> IntroFrame {
+        let lerp = |from: f32, to: f32| from + (to - from) * alpha;
+
+        IntroFrame {
+            title_alpha: lerp(self.title_alpha, to.title_alpha),
+            subtitle_slide: lerp(self.subtitle_slide, to.subtitle_slide),
+            slide_down: lerp(self.slide_down, to.slide_down),
+            player_hop: lerp(self.player_hop, to.player_hop),
+            text_progress: lerp(self.text_progress, to.text_progress)
+        }
+    }
 }
 
+const PLAYER_PIXELS_PER_SECOND: f32 = 200.0;
+const TO_HOP: f32 = 1440.0;
+
 impl MenuState {
-    pub fn new(window: &mut core::Window) -> MenuState {
-        let display = window.display();
+    pub fn new(window: &mut core::Window, audio_device: &core::AudioDevice, config: &core::Config) -> MenuState {
+        let logical_size = window.logical_size_f();
+        let device = window.device();
 
         let mut texts = Vec::new();
         let texts_count = 5;
 
         for i in 0..texts_count {
-            texts.push(gfx::Texture2D::from_image(display, &format!("assets/textures/menu/texts/{}.png", i)[..]));
+            texts.push(gfx::Texture2D::from_image(device, &format!("assets/textures/menu/texts/{}.png", i)[..]));
         }
 
+        let title_alpha = core::Animated::new(0.0);
+        let subtitle_slide = core::Animated::new(0.0);
+        let slide_down = core::Animated::new(0.0);
+        let player_hop = core::Animated::new(0.0);
+        let text_progress = core::Animated::new(0.0);
+
+        let title_duration = 5.0;
+        let subtitle_duration = 1.0 / 0.33;
+        let slide_down_duration = 8.0;
+        let each_text_duration = 5.0;
+
+        // The player starts hopping halfway down, and keeps hopping for a moment after crossing the screen
+        let hop_delay = slide_down_duration * 0.5;
+        let hop_duration = TO_HOP / PLAYER_PIXELS_PER_SECOND;
+        let off_screen_duration = 1.0;
+
+        let intro = core::Sequence::new()
+            .then(core::Tween::new(0.0, 1.0, title_duration).with_target(&title_alpha))
+            .then(core::Tween::new(0.0, 1.0, subtitle_duration).with_target(&subtitle_slide))
+            .then(core::Parallel::new()
+                .with(core::Tween::new(0.0, 1.0, slide_down_duration).with_target(&slide_down))
+                .with(core::Tween::new(0.0, TO_HOP + off_screen_duration * PLAYER_PIXELS_PER_SECOND, hop_duration + off_screen_duration)
+                    .with_delay(hop_delay)
+                    .with_target(&player_hop)))
+            .then(core::Tween::new(0.0, texts_count as f32, each_text_duration * texts_count as f32).with_target(&text_progress));
+
         let mut menu_state = MenuState {
             camera: core::Camera::new(),
-            quad: gfx::Mesh::create_quad(display, true),
-            menu_shader: gfx::ShaderProgram::from_source(display, "assets/shaders/widget.vs".to_string(), "assets/shaders/widget.fs".to_string()).unwrap(),
-            title_texture: gfx::Texture2D::from_image(display, "assets/textures/menu/title.png"),
-            subtitle_texture: gfx::Texture2D::from_image(display, "assets/textures/menu/subtitle.png"),
-            ground_texture: gfx::Texture2D::from_image(display, "assets/textures/menu/ground.png").with_nearest_filter(),
-            background_texture: gfx::Texture2D::from_image(display, "assets/textures/menu/background.png"),
-            player_texture: gfx::Texture2D::from_image(display, "assets/textures/characters/player.png"),
-            title_alpha: 0.0,
-            subtitle_slide: 0.0,
-            slide_down: 0.0,
-            player_hop: 0.0,
-            fade_to_black: 0.0,
-            is_first_frame: true,
-            player_transform: core::Transform::new(),
-            sink: rodio::Sink::new(&rodio::default_output_device().unwrap()),
-            texts,
-            current_text: 0,
-            text_timer: 0.0
+            quad: gfx::Mesh::create_quad(device, true),
+            menu_shader: gfx::ShaderProgram::from_source(device, "assets/shaders/widget.vs".to_string(), "assets/shaders/widget.fs".to_string()).unwrap(),
+            title_texture: gfx::Texture2D::from_image(device, "assets/textures/menu/title.png"),
+            subtitle_texture: gfx::Texture2D::from_image(device, "assets/textures/menu/subtitle.png"),
+            ground_texture: gfx::Texture2D::from_image(device, "assets/textures/menu/ground.png").with_nearest_filter(),
+            background_texture: gfx::Texture2D::from_image(device, "assets/textures/menu/background.png"),
+            player_texture: gfx::Texture2D::from_image(device, "assets/textures/characters/player.png"),
+            title_alpha,
+            subtitle_slide,
+            slide_down,
+            player_hop,
+            text_progress,
+            intro,
+            previous_frame: IntroFrame {
+                title_alpha: 0.0,
+                subtitle_slide: 0.0,
+                slide_down: 0.0,
+                player_hop: 0.0,
+                text_progress: 0.0
+            },
+            audio_device: audio_device.clone(),
+            sink: audio_device.create_sink(),
+            music_volume: config.volume(firefly::settings::MUSIC_BUS),
+            texts
         };
 
         menu_state.camera.set_orthographic(true);
-        menu_state.camera.set_orthographic_size(1280.0, 720.0 / 1280.0);
+        menu_state.camera.set_orthographic_size_both(&logical_size);
 
         menu_state
     }
@@ -68,124 +128,61 @@ impl MenuState {
 
 impl MenuState {
     fn set_defaults(&mut self) {
+        self.sink = self.audio_device.play_file("assets/audio/music_start.mp3");
+        self.sink.set_volume(self.music_volume);
 
-        let audio_file = File::open("assets/audio/music_start.mp3").unwrap();
-        let audio_source = rodio::Decoder::new(BufReader::new(audio_file)).unwrap();
-
-        self.sink = rodio::Sink::new(&rodio::default_output_device().unwrap());
-        self.sink.append(audio_source);
+        self.intro.reset();
+        self.previous_frame = self.intro_frame();
+    }
 
-        self.is_first_frame = true;
-        self.title_alpha = 0.0;
-        self.subtitle_slide = 0.0;
-        self.slide_down = 0.0;
-        self.player_hop = 0.0;
-        self.fade_to_black = 0.0;
-        self.current_text = 0;
-        self.text_timer = 0.0;
+    fn intro_frame(&self) -> IntroFrame {
+        IntroFrame {
+            title_alpha: self.title_alpha.get(),
+            subtitle_slide: self.subtitle_slide.get(),
+            slide_down: self.slide_down.get(),
+            player_hop: self.player_hop.get(),
+            text_progress: self.text_progress.get()
+        }
     }
 }
 
-impl core::GameState for MenuState {
+impl core::GameState<StateKey> for MenuState {
 
     fn on_enter(&mut self) {
         self.set_defaults();
     }
 
-    fn update(&mut self, dt: f32, window: &core::Window) -> Option<String> {
-
-        // Skip first frame because of high delta-times, start background music
-
-        if self.is_first_frame {
-            self.sink.play();
-            self.is_first_frame = false;
-            
-            return None
-        }
-
-        // Debug
-
-        let mut speed = dt;
-        if window.is_key_down(glium::glutin::VirtualKeyCode::RBracket) {
-            speed = speed * 10.0;
-        }
-        else if window.is_key_down(glium::glutin::VirtualKeyCode::LBracket) {
-            speed = speed * 0.1;
-        }
+    fn update(&mut self, dt: f32, window: &core::Window) -> Option<core::StateTransition<StateKey>> {
 
-        if window.is_key_released(glium::glutin::VirtualKeyCode::R) {
+        if window.is_action_released(firefly::settings::RESTART) {
             self.set_defaults();
             return None;
         }
-        else if window.is_key_released(glium::glutin::VirtualKeyCode::Space) {
-            return Some(String::from("MainState"));
+        else if window.is_action_released(firefly::settings::SKIP) {
+            return Some(core::StateTransition::Switch(StateKey::Main));
+        }
+        else if window.is_action_released(firefly::settings::QUIT) {
+            return Some(core::StateTransition::Quit);
         }
         
-        // Variables
-
-        let title_speed = 0.2;
-        let subtitle_speed = 0.33;
-        let slide_down_speed = 0.125;
-        let player_y_offset = -200.0;
-        let player_pixels_per_second = 200.0;
-        let to_hop = 1440.0;
-        let hop_height = 40.0;
-        let each_text_duration = 5.0;
+        // Keep showing the whole logical resolution, in case it changed
+        self.camera.set_orthographic_size_both(&window.logical_size_f());
 
         // Apply animations
-        if self.title_alpha < 1.0 {
-            self.title_alpha += speed * title_speed;
-            self.title_alpha = self.title_alpha.min(1.0);
-        }
-        else if self.subtitle_slide < 1.0 {
-            self.subtitle_slide += speed * subtitle_speed;
-            self.subtitle_slide = self.subtitle_slide.min(1.0);
-        }
-        else {
-            self.slide_down += speed * slide_down_speed;
-            self.slide_down = self.slide_down.min(1.0);
-
-            if self.slide_down > 0.5 {
-                self.player_hop += speed * player_pixels_per_second;
-            }
-
-            if self.player_hop > to_hop {
-                self.fade_to_black += speed;
-                self.fade_to_black = self.fade_to_black.min(1.0);
-            }
-        }
-
-        if self.fade_to_black >= 0.99 {
-            self.text_timer += speed * (1.0 / each_text_duration);
+        self.previous_frame = self.intro_frame();
+        self.intro.advance(dt);
 
-            while self.text_timer >= 1.0 {
-                self.text_timer -= 1.0;
-                self.current_text += 1;
-            }
-
-            if self.current_text >= self.texts.len() {
-                return Some(String::from("MainState"));
-            }
+        if self.intro.is_finished() {
+            return Some(core::StateTransition::SwitchWith(StateKey::Main, core::Transition::fade_to_color(0.0, 0.0, 0.0, 1.0)));
         }
-        
-
-        let pi = 3.14159;
-        let hop_factor = f32::abs(f32::sin(self.player_hop / player_pixels_per_second * pi * 1.75));
-        let squish_factor = hop_factor.powf(0.75);
-        let ease_down = core::easing::in_out_cubic(self.slide_down);
-
-        self.player_transform
-            .set_anchor_2d_f(0.0, 0.33)
-            .set_size_2d(&self.player_texture.dimensions_f())
-            .set_translation_2d_f(-to_hop * 0.5 + self.player_hop, hop_factor * hop_height)
-            .translate_2d_f(0.0, player_y_offset - 360.0 + ease_down * 360.0)
-            .set_scale_2d_f(1.0 + (1.0 - squish_factor) * 0.5, 0.25 + squish_factor * 0.9)
-            .set_orientation(0.1 - hop_factor * 0.3);
 
         None
     }
 
-    fn draw(&mut self, command_buffer: &mut gfx::CommandBuffer, _dt: f32) {
+    fn draw(&mut self, command_buffer: &mut gfx::CommandBuffer, alpha: f32) {
+
+        // Draw in between the last two ticks
+        let frame = self.previous_frame.lerp(&self.intro_frame(), alpha);
 
         // Variables
 
@@ -193,11 +190,19 @@ impl core::GameState for MenuState {
         let subtitle_slide = 15.0;
         let parallax_offset = 150.0;
         let texts_slide = 16.0;
+        let player_y_offset = -200.0;
+        let hop_height = 40.0;
+
+        let ease_title = core::easing::in_out_quad(frame.title_alpha);
+        let ease_slide = core::easing::in_out_cubic(frame.subtitle_slide);
+        let ease_down = core::easing::in_out_cubic(frame.slide_down);
+        let fade_out = (1.0 - frame.slide_down * 1.75).max(0.0);
 
-        let ease_title = core::easing::in_out_quad(self.title_alpha);
-        let ease_slide = core::easing::in_out_cubic(self.subtitle_slide);
-        let ease_down = core::easing::in_out_cubic(self.slide_down);
-        let fade_out = (1.0 - self.slide_down * 1.75).max(0.0);
+        let hop_factor = f32::abs(f32::sin(frame.player_hop / PLAYER_PIXELS_PER_SECOND * std::f32::consts::PI * 1.75));
+        let squish_factor = hop_factor.powf(0.75);
+
+        let current_text = frame.text_progress.floor() as usize;
+        let text_timer = frame.text_progress.fract();
 
         // Setup transforms
         let mut transform_background = core::Transform::new();
@@ -226,10 +231,17 @@ impl core::GameState for MenuState {
             .set_size_2d(&self.ground_texture.dimensions_f())
             .translate_2d_f(0.0, -720.0 + self.ground_texture.dimensions_f().y * 0.5 + 360.0 * ease_down);
 
-        let c = core::easing::out_cubic(1.0 - self.fade_to_black);
+        let mut transform_player = core::Transform::new();
+        transform_player
+            .set_anchor_2d_f(0.0, 0.33)
+            .set_size_2d(&self.player_texture.dimensions_f())
+            .set_translation_2d_f(-TO_HOP * 0.5 + frame.player_hop, hop_factor * hop_height)
+            .translate_2d_f(0.0, player_y_offset - 360.0 + ease_down * 360.0)
+            .set_scale_2d_f(1.0 + (1.0 - squish_factor) * 0.5, 0.25 + squish_factor * 0.9)
+            .set_orientation(0.1 - hop_factor * 0.3);
 
         // Render background
-        command_buffer.set_blend_color(c, c, c, 1.0);
+        command_buffer.set_blend_color(1.0, 1.0, 1.0, 1.0);
         command_buffer.draw(
             &mut self.camera,
             &self.quad,
@@ -238,7 +250,7 @@ impl core::GameState for MenuState {
             &vec![&self.background_texture]);
 
         // Render title
-        command_buffer.set_blend_color(c, c, c, ease_title * fade_out);
+        command_buffer.set_blend_color(1.0, 1.0, 1.0, ease_title * fade_out);
         command_buffer.draw(
             &mut self.camera,
             &self.quad,
@@ -247,7 +259,7 @@ impl core::GameState for MenuState {
             &vec![&self.title_texture]);
 
         // Render subtitle
-        command_buffer.set_blend_color(c, c, c, ease_slide * fade_out);
+        command_buffer.set_blend_color(1.0, 1.0, 1.0, ease_slide * fade_out);
         command_buffer.draw(
             &mut self.camera,
             &self.quad,
@@ -256,7 +268,7 @@ impl core::GameState for MenuState {
             &vec![&self.subtitle_texture]);
 
         // Render ground
-        command_buffer.set_blend_color(c, c, c, 1.0);
+        command_buffer.set_blend_color(1.0, 1.0, 1.0, 1.0);
         command_buffer.draw(
             &mut self.camera,
             &self.quad,
@@ -268,22 +280,22 @@ impl core::GameState for MenuState {
         command_buffer.draw(
             &mut self.camera,
             &self.quad,
-            &mut self.player_transform,
+            &mut transform_player,
             &mut self.menu_shader,
             &vec![&self.player_texture]);
 
         // Lastly, render texts
 
-        if self.current_text < self.texts.len() {
-            let current_text = &self.texts[self.current_text];
-            let text_ease = core::easing::out_cubic(self.text_timer);
+        if current_text < self.texts.len() {
+            let current_text = &self.texts[current_text];
+            let text_ease = core::easing::out_cubic(text_timer);
 
             let mut text_transform = core::Transform::new();
             text_transform
                 .set_size_2d(&current_text.dimensions_f())
                 .set_translation_2d_f(0.0, text_ease * texts_slide);
 
-            command_buffer.set_blend_color(1.0, 1.0, 1.0, (self.text_timer * 3.14159).sin());
+            command_buffer.set_blend_color(1.0, 1.0, 1.0, (text_timer * std::f32::consts::PI).sin());
             command_buffer.draw(
                 &mut self.camera,
                 &self.quad,
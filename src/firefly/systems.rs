@@ -0,0 +1,42 @@
+use crate::snuff::core;
+use crate::snuff::ecs;
+use crate::firefly::objects;
+
+pub fn update_players(world: &ecs::World, dt: f32, window: &core::Window) {
+    let mut players = world.storage_mut::<objects::Player>();
+    let mut transforms = world.storage_mut::<core::Transform>();
+    let mut velocities = world.storage_mut::<ecs::Velocity>();
+
+    for (entity, player) in players.iter_mut() {
+        let velocity = match velocities.get_mut(entity) {
+            Some(velocity) => &mut velocity.linear,
+            None => continue,
+        };
+
+        if let Some(transform) = transforms.get_mut(entity) {
+            player.update(transform, velocity, dt, window);
+        }
+
+        if let Some(body) = transforms.get_mut(player.body()) {
+            player.update_animations(body, velocity, dt);
+        }
+    }
+}
+
+// Run after propagate_transforms, fireflies chase their anchors wherever those were carried off to
+pub fn update_fireflies(world: &ecs::World, end_offset: f32, dt: f32) {
+    let mut fireflies = world.storage_mut::<objects::Firefly>();
+    let mut transforms = world.storage_mut::<core::Transform>();
+    let mut sprites = world.storage_mut::<ecs::Sprite>();
+
+    for (entity, firefly) in fireflies.iter_mut() {
+        let target = match transforms.get_mut(firefly.anchor()) {
+            Some(anchor) => firefly.update_anchor(anchor, end_offset, dt),
+            None => continue,
+        };
+
+        if let (Some(transform), Some(sprite)) = (transforms.get_mut(entity), sprites.get_mut(entity)) {
+            firefly.update(transform, sprite, &target, dt);
+        }
+    }
+}
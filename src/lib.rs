@@ -0,0 +1,17 @@
+#![allow(dead_code)]
+// Everything is built with a new() constructor, which Default would only duplicate
+#![allow(clippy::new_without_default)]
+
+#[macro_use]
+extern crate glium;
+extern crate image;
+extern crate time;
+extern crate notify;
+extern crate rodio;
+extern crate rand;
+extern crate serde;
+extern crate ron;
+extern crate clap;
+
+pub mod snuff;
+pub mod firefly;
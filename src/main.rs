@@ -1,30 +1,98 @@
-#![allow(dead_code)]
-#[macro_use]
+use firefly_reflection::{firefly, snuff};
 
-extern crate glium;
-extern crate image;
-extern crate time;
-extern crate notify;
-extern crate rodio;
-extern crate rand;
+fn main() {
+    let options = firefly::LaunchOptions::from_args();
+    options.apply_asset_root();
 
-mod snuff;
-mod firefly;
+    let mut config = snuff::core::Config::load(firefly::settings::APP_NAME);
+    firefly::settings::add_defaults(&mut config);
+    snuff::core::TimeController::add_default_bindings(&mut config);
+    snuff::core::GameLoop::<firefly::StateKey>::add_default_bindings(&mut config);
 
-fn main() {
-    let mut game_loop = snuff::core::GameLoop::new(1280, 720, "Firefly - Reflection", false);
+    // Headless runs are for tests and tools, which shouldn't touch the user's config
+    if options.headless_frames.is_none() {
+        if let Err(e) = config.save() {
+            println!("[main] Could not save the config: {}", e);
+        }
+    }
 
-    let window = game_loop.window();
+    // Command-line options only apply to this run, so they don't end up in the saved config
+    let mut display = config.display.clone();
+    options.apply_display(&mut display);
+
+    let mut game_loop = match options.headless_frames {
+        Some(_) => snuff::core::GameLoop::headless(display.width, display.height),
+        None => snuff::core::GameLoop::new("Firefly - Reflection", &display),
+    };
+
+    game_loop.window()
+        .set_logical_size(display.logical_width, display.logical_height)
+        .set_scaling_policy(display.scaling);
+
+    let initial_mode = game_loop.window().window_mode();
+    let initial_monitor = game_loop.window().monitor();
+
+    if options.mute {
+        game_loop.mute();
+    }
+
+    game_loop.window().set_input(snuff::core::Input::from_config(&config));
+
+    // A replay only matches the original run when it starts the same way
+    let replay = options.replay_recording();
+    let seed = replay.as_ref().map_or(options.seed, |recording| recording.seed());
+    let start_state = replay.as_ref()
+        .and_then(|recording| recording.start_state())
+        .unwrap_or(options.start_state);
+
+    println!("[main] Using random seed {}", seed);
+
+    game_loop.register_states(|key, window, audio_device| firefly::create_state(key, window, audio_device, &config, seed));
 
-    let menu_state = Box::new(firefly::MenuState::new(window));
-    let main_state = Box::new(firefly::MainState::new(window));
-    
     let game_state_manager = game_loop.game_state_manager();
 
-    game_state_manager.add_state("MenuState", menu_state);
-    game_state_manager.add_state("MainState", main_state);
+    game_state_manager.set_default_transition(
+        snuff::core::Transition::cross_dissolve(2.0).with_easing(snuff::core::easing::out_cubic));
+
+    game_state_manager.switch(start_state);
+
+    if let Some(path) = &options.record {
+        game_loop.start_recording(path, seed);
+    }
+
+    if let Some(recording) = replay {
+        game_loop.start_playback(recording);
+    }
+
+    if let Some(directory) = &options.capture {
+        game_loop.start_frame_capture(directory, options.capture_fps);
+    }
+
+    match options.headless_frames {
+        Some(frames) => game_loop.exec_headless(frames, &options.input_script()),
+        None => game_loop.exec(),
+    }
+
+    // Only what changed while playing is saved, so command-line options still stay out of the config
+    let window = game_loop.window();
+
+    if window.is_headless() {
+        return;
+    }
+
+    window.input().save(&mut config);
+
+    if window.window_mode() != initial_mode {
+        config.display.mode = window.window_mode();
+    }
+
+    if window.monitor() != initial_monitor {
+        config.display.monitor = window.monitor_name();
+    }
 
-    game_state_manager.switch("MenuState");
+    config.display.position = window.windowed_position();
 
-    game_loop.exec();
-}
+    if let Err(e) = config.save() {
+        println!("[main] Could not save the config: {}", e);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,64 @@
+use std::fs::File;
+use std::io::BufReader;
+
+#[derive(Clone)]
+pub struct AudioDevice {
+    device: Option<rodio::Device>,
+}
+
+impl AudioDevice {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> AudioDevice {
+        match rodio::default_output_device() {
+            Some(device) => AudioDevice {
+                device: Some(device),
+            },
+            None => {
+                println!("[AudioDevice] Could not find an output device, falling back to silent audio");
+                AudioDevice::silent()
+            }
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn silent() -> AudioDevice {
+        AudioDevice { device: None }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_silent(&self) -> bool {
+        self.device.is_none()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn create_sink(&self) -> rodio::Sink {
+        match &self.device {
+            Some(device) => rodio::Sink::new(device),
+            None => rodio::Sink::new_idle().0,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn play_file(&self, path: &str) -> rodio::Sink {
+        let sink = self.create_sink();
+
+        if self.is_silent() {
+            return sink;
+        }
+
+        let audio_file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                println!("[AudioDevice] Could not open audio file '{}': {}", path, e);
+                return sink;
+            }
+        };
+
+        match rodio::Decoder::new(BufReader::new(audio_file)) {
+            Ok(source) => sink.append(source),
+            Err(e) => println!("[AudioDevice] Could not decode audio file '{}': {}", path, e),
+        }
+
+        sink
+    }
+}
@@ -1,8 +1,10 @@
+use crate::snuff::core;
 use crate::snuff::core::Transform;
 
 pub struct Camera {
     transform: Transform,
     projection: nalgebra_glm::Mat4,
+    view_offset: nalgebra_glm::Mat4,
     dimensions: nalgebra_glm::Vec2,
     fov: f32,
     near_plane: f32,
@@ -19,6 +21,7 @@ impl Camera {
         Camera {
             transform: Transform::new(),
             projection: nalgebra_glm::identity(),
+            view_offset: nalgebra_glm::identity(),
             dimensions: nalgebra_glm::vec2(5.0, 5.0 * default_aspect),
             fov: 90.0,
             near_plane: 0.01,
@@ -80,7 +83,26 @@ impl Camera {
 
     //---------------------------------------------------------------------------------------------------
     pub fn view(&mut self) -> nalgebra_glm::Mat4 {
-        self.transform.world_to_local()
+        self.view_offset * self.transform.world_to_local()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Moves and rolls what the camera sees without touching its transform, used for effects like shake
+    pub fn set_view_offset(&mut self, translation: &nalgebra_glm::Vec2, rotation: f32) -> &mut Camera {
+        let z = nalgebra_glm::vec3(0.0, 0.0, 1.0);
+
+        // Stored inverted, since it gets applied in view space
+        self.view_offset = nalgebra_glm::rotate(&nalgebra_glm::identity(), -rotation, &z)
+            * nalgebra_glm::translate(&nalgebra_glm::identity(), &nalgebra_glm::vec3(-translation.x, -translation.y, 0.0));
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn clear_view_offset(&mut self) -> &mut Camera {
+        self.view_offset = nalgebra_glm::identity();
+
+        self
     }
 
     //---------------------------------------------------------------------------------------------------
@@ -147,6 +169,12 @@ impl Camera {
         self
     }
 
+    //---------------------------------------------------------------------------------------------------
+    // The width and height of what the camera sees, in world units when orthographic
+    pub fn dimensions(&self) -> nalgebra_glm::Vec2 {
+        self.dimensions
+    }
+
     //---------------------------------------------------------------------------------------------------
     pub fn fov(&self) -> f32 {
         self.fov
@@ -161,4 +189,189 @@ impl Camera {
     pub fn far_plane(&self) -> f32 {
         self.far_plane
     }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn view_projection(&mut self) -> nalgebra_glm::Mat4 {
+        self.projection() * self.view()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Clip space here is after the perspective divide, from -1 to 1 on every axis
+    pub fn world_to_clip(&mut self, world: &nalgebra_glm::Vec3) -> nalgebra_glm::Vec3 {
+        let clip = self.view_projection() * nalgebra_glm::vec4(world.x, world.y, world.z, 1.0);
+        nalgebra_glm::vec3(clip.x, clip.y, clip.z) / clip.w
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn clip_to_world(&mut self, clip: &nalgebra_glm::Vec3) -> nalgebra_glm::Vec3 {
+        let world = nalgebra_glm::inverse(&self.view_projection()) * nalgebra_glm::vec4(clip.x, clip.y, clip.z, 1.0);
+        nalgebra_glm::vec3(world.x, world.y, world.z) / world.w
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Screen positions are in pixels from the top left, like Window::mouse_position
+    pub fn screen_to_clip(screen: &nalgebra_glm::Vec2, viewport: &nalgebra_glm::Vec2) -> nalgebra_glm::Vec2 {
+        nalgebra_glm::vec2(screen.x / viewport.x * 2.0 - 1.0, 1.0 - screen.y / viewport.y * 2.0)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn clip_to_screen(clip: &nalgebra_glm::Vec2, viewport: &nalgebra_glm::Vec2) -> nalgebra_glm::Vec2 {
+        nalgebra_glm::vec2((clip.x + 1.0) * 0.5 * viewport.x, (1.0 - clip.y) * 0.5 * viewport.y)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn world_to_screen(&mut self, world: &nalgebra_glm::Vec3, viewport: &nalgebra_glm::Vec2) -> nalgebra_glm::Vec2 {
+        let clip = self.world_to_clip(world);
+        Camera::clip_to_screen(&nalgebra_glm::vec2(clip.x, clip.y), viewport)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Runs from the near plane through the pixel, parallel to the view direction when orthographic
+    pub fn screen_ray(&mut self, screen: &nalgebra_glm::Vec2, viewport: &nalgebra_glm::Vec2) -> core::Ray {
+        let clip = Camera::screen_to_clip(screen, viewport);
+        let near = self.clip_to_world(&nalgebra_glm::vec3(clip.x, clip.y, -1.0));
+        let far = self.clip_to_world(&nalgebra_glm::vec3(clip.x, clip.y, 1.0));
+
+        core::Ray::new(&near, &(far - near))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The point under the pixel on the z = 0 plane that sprites are drawn on
+    pub fn screen_to_world(&mut self, screen: &nalgebra_glm::Vec2, viewport: &nalgebra_glm::Vec2) -> nalgebra_glm::Vec3 {
+        let ray = self.screen_ray(screen, viewport);
+        ray.intersect_plane_z(0.0).unwrap_or(ray.origin)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // What the camera sees of the plane at the given z, None if it can't see the plane at all
+    pub fn visible_rect(&mut self, z: f32) -> Option<core::Rect> {
+        let corners = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)];
+        let mut points = Vec::new();
+
+        for (x, y) in corners.iter() {
+            let near = self.clip_to_world(&nalgebra_glm::vec3(*x, *y, -1.0));
+            let far = self.clip_to_world(&nalgebra_glm::vec3(*x, *y, 1.0));
+
+            let point = core::Ray::new(&near, &(far - near)).intersect_plane_z(z)?;
+            points.push(nalgebra_glm::vec2(point.x, point.y));
+        }
+
+        core::Rect::from_points(points)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_visible(&mut self, rect: &core::Rect, z: f32) -> bool {
+        match self.visible_rect(z) {
+            Some(visible) => visible.overlaps(rect),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: &nalgebra_glm::Vec3, expected: &nalgebra_glm::Vec3) {
+        assert!(nalgebra_glm::distance(actual, expected) < 1e-2, "expected {}, got {}", expected, actual);
+    }
+
+    fn orthographic_camera() -> Camera {
+        let mut camera = Camera::new();
+        camera.set_orthographic_size_both_f(1280.0, 720.0);
+        camera.transform().set_translation_2d_f(100.0, 50.0);
+        camera.transform().translate_f(0.0, 0.0, -10.0);
+
+        camera
+    }
+
+    fn perspective_camera() -> Camera {
+        let mut camera = Camera::new();
+        camera.set_perspective_size(&nalgebra_glm::vec2(1280.0, 720.0)).set_fov(1.2);
+        camera.transform().translate_f(3.0, -2.0, -10.0);
+
+        camera
+    }
+
+    #[test]
+    fn orthographic_screen_positions_round_trip() {
+        let mut camera = orthographic_camera();
+        let viewport = nalgebra_glm::vec2(1280.0, 720.0);
+
+        // The camera's position is in the middle of the screen, y goes up in the world and down on screen
+        assert_eq!(camera.world_to_screen(&nalgebra_glm::vec3(100.0, 50.0, 0.0), &viewport), nalgebra_glm::vec2(640.0, 360.0));
+        assert_eq!(camera.world_to_screen(&nalgebra_glm::vec3(110.0, 60.0, 0.0), &viewport), nalgebra_glm::vec2(650.0, 350.0));
+
+        let world = nalgebra_glm::vec3(-200.0, 300.0, 0.0);
+        let screen = camera.world_to_screen(&world, &viewport);
+        assert_near(&camera.screen_to_world(&screen, &viewport), &world);
+    }
+
+    #[test]
+    fn perspective_screen_positions_round_trip() {
+        let mut camera = perspective_camera();
+        let viewport = nalgebra_glm::vec2(1280.0, 720.0);
+
+        let center = camera.world_to_screen(&nalgebra_glm::vec3(3.0, -2.0, 0.0), &viewport);
+        assert!(nalgebra_glm::distance(&center, &nalgebra_glm::vec2(640.0, 360.0)) < 1e-3);
+
+        for world in [nalgebra_glm::vec3(5.0, 1.0, 0.0), nalgebra_glm::vec3(-4.0, -6.0, 0.0)].iter() {
+            let screen = camera.world_to_screen(world, &viewport);
+            assert_near(&camera.screen_to_world(&screen, &viewport), world);
+        }
+    }
+
+    #[test]
+    fn screen_rays_start_at_the_near_plane() {
+        let viewport = nalgebra_glm::vec2(1280.0, 720.0);
+
+        let ray = orthographic_camera().screen_ray(&nalgebra_glm::vec2(0.0, 0.0), &viewport);
+        assert_near(&ray.direction, &nalgebra_glm::vec3(0.0, 0.0, 1.0));
+        assert_near(&ray.origin, &nalgebra_glm::vec3(-540.0, 410.0, -9.99));
+
+        // Through the middle of the screen, a perspective ray runs straight down the view direction
+        let ray = perspective_camera().screen_ray(&nalgebra_glm::vec2(640.0, 360.0), &viewport);
+        assert_near(&ray.direction, &nalgebra_glm::vec3(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn orthographic_cameras_see_their_dimensions() {
+        let visible = orthographic_camera().visible_rect(0.0).unwrap();
+
+        assert_near(&nalgebra_glm::vec3(visible.min.x, visible.min.y, 0.0), &nalgebra_glm::vec3(-540.0, -310.0, 0.0));
+        assert_near(&nalgebra_glm::vec3(visible.max.x, visible.max.y, 0.0), &nalgebra_glm::vec3(740.0, 410.0, 0.0));
+    }
+
+    #[test]
+    fn perspective_cameras_see_more_further_away() {
+        let mut camera = perspective_camera();
+        let near = camera.visible_rect(0.0).unwrap();
+        let far = camera.visible_rect(10.0).unwrap();
+
+        assert!(far.max.x - far.min.x > near.max.x - near.min.x);
+        assert!(camera.visible_rect(-20.0).is_none());
+    }
+
+    #[test]
+    fn letterboxed_mouse_positions_map_to_the_world() {
+        // A 16:9 game in a taller window gets bars of 140 pixels at the top and bottom
+        let mut window = core::Window::headless(1280, 1000);
+        window.set_logical_size(1280, 720);
+
+        let mut camera = Camera::new();
+        camera.set_orthographic_size_both(&window.logical_size_f());
+        let logical = window.logical_size_f();
+
+        window.inject(core::InputEvent::MousePosition(640.0, 500.0));
+        let mouse = window.mouse_logical_position().unwrap();
+        assert_eq!(mouse, nalgebra_glm::vec2(640.0, 360.0));
+        assert_near(&camera.screen_to_world(&mouse, &logical), &nalgebra_glm::vec3(0.0, 0.0, 0.0));
+
+        window.inject(core::InputEvent::MousePosition(0.0, 140.0));
+        let mouse = window.mouse_logical_position().unwrap();
+        assert_near(&camera.screen_to_world(&mouse, &logical), &nalgebra_glm::vec3(-640.0, 360.0, 0.0));
+
+        window.inject(core::InputEvent::MousePosition(640.0, 100.0));
+        assert_eq!(window.mouse_logical_position(), None);
+    }
 }
@@ -0,0 +1,332 @@
+use crate::snuff::core;
+
+struct Handover {
+    from: nalgebra_glm::Vec2,
+    timer: f32,
+    duration: f32,
+    easing: core::easing::Curve,
+}
+
+// Moves a camera after a target, call update once per tick with the transform to follow
+pub struct CameraController {
+    damping: f32,
+    offset: nalgebra_glm::Vec2,
+    dead_zone: nalgebra_glm::Vec2,
+    look_ahead_time: f32,
+    max_look_ahead: nalgebra_glm::Vec2,
+    look_ahead_damping: f32,
+    bounds: core::Rect,
+    focus: Option<nalgebra_glm::Vec2>,
+    previous_target: Option<nalgebra_glm::Vec2>,
+    look_ahead: nalgebra_glm::Vec2,
+    handover: Option<Handover>,
+}
+
+//---------------------------------------------------------------------------------------------------
+// Frame rate independent exponential smoothing, a damping of 1 or less snaps straight to the goal
+fn damp(damping: f32, dt: f32) -> f32 {
+    if damping <= 1.0 {
+        1.0
+    } else {
+        1.0 - f32::powf(1.0 / damping, dt)
+    }
+}
+
+impl CameraController {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> CameraController {
+        CameraController {
+            damping: 100.0,
+            offset: nalgebra_glm::vec2(0.0, 0.0),
+            dead_zone: nalgebra_glm::vec2(0.0, 0.0),
+            look_ahead_time: 0.0,
+            max_look_ahead: nalgebra_glm::vec2(0.0, 0.0),
+            look_ahead_damping: 10.0,
+            bounds: core::Rect::new(
+                &nalgebra_glm::vec2(f32::NEG_INFINITY, f32::NEG_INFINITY),
+                &nalgebra_glm::vec2(f32::INFINITY, f32::INFINITY),
+            ),
+            focus: None,
+            previous_target: None,
+            look_ahead: nalgebra_glm::vec2(0.0, 0.0),
+            handover: None,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // How much of the distance is left after a second, so higher values follow more tightly
+    pub fn set_damping(&mut self, damping: f32) -> &mut CameraController {
+        self.damping = damping;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_offset(&mut self, offset: &nalgebra_glm::Vec2) -> &mut CameraController {
+        self.offset = *offset;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_offset_f(&mut self, x: f32, y: f32) -> &mut CameraController {
+        self.set_offset(&nalgebra_glm::vec2(x, y))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The target can move around this area, centered on what the camera follows, without moving the camera
+    pub fn set_dead_zone(&mut self, size: &nalgebra_glm::Vec2) -> &mut CameraController {
+        self.dead_zone = nalgebra_glm::abs(size);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Leads the target by where it will be in this many seconds, up to max_distance on each axis
+    pub fn set_look_ahead(&mut self, seconds: f32, max_distance: &nalgebra_glm::Vec2) -> &mut CameraController {
+        self.look_ahead_time = seconds.max(0.0);
+        self.max_look_ahead = nalgebra_glm::abs(max_distance);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_look_ahead_damping(&mut self, damping: f32) -> &mut CameraController {
+        self.look_ahead_damping = damping;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Limits where the center of the camera can go, use infinity for sides that are open
+    pub fn set_bounds(&mut self, bounds: &core::Rect) -> &mut CameraController {
+        self.bounds = *bounds;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn bounds(&self) -> core::Rect {
+        self.bounds
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Call right before passing a different target to update, the camera then eases over instead of jumping
+    pub fn begin_handover(&mut self, camera: &mut core::Camera, duration: f32, easing: core::easing::Curve) {
+        self.handover = Some(Handover {
+            from: camera.transform().translation_2d(),
+            timer: 0.0,
+            duration: duration.max(0.0),
+            easing,
+        });
+
+        // The new target has nothing to do with where the old one was going
+        self.focus = None;
+        self.previous_target = None;
+        self.look_ahead = nalgebra_glm::vec2(0.0, 0.0);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_handing_over(&self) -> bool {
+        self.handover.is_some()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Forgets what the target was doing, e.g. when it was teleported
+    pub fn reset(&mut self) {
+        self.focus = None;
+        self.previous_target = None;
+        self.look_ahead = nalgebra_glm::vec2(0.0, 0.0);
+        self.handover = None;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn clamp_to_bounds(&self, position: &nalgebra_glm::Vec2) -> nalgebra_glm::Vec2 {
+        nalgebra_glm::vec2(
+            position.x.max(self.bounds.min.x).min(self.bounds.max.x),
+            position.y.max(self.bounds.min.y).min(self.bounds.max.y),
+        )
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn update_focus(&mut self, target: &nalgebra_glm::Vec2) -> nalgebra_glm::Vec2 {
+        let half_zone = self.dead_zone * 0.5;
+        let mut focus = self.focus.unwrap_or(*target);
+
+        if target.x > focus.x + half_zone.x {
+            focus.x = target.x - half_zone.x;
+        } else if target.x < focus.x - half_zone.x {
+            focus.x = target.x + half_zone.x;
+        }
+
+        if target.y > focus.y + half_zone.y {
+            focus.y = target.y - half_zone.y;
+        } else if target.y < focus.y - half_zone.y {
+            focus.y = target.y + half_zone.y;
+        }
+
+        self.focus = Some(focus);
+
+        focus
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn update_look_ahead(&mut self, target: &nalgebra_glm::Vec2, dt: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+
+        let velocity = match self.previous_target {
+            Some(previous) => (target - previous) / dt,
+            None => nalgebra_glm::vec2(0.0, 0.0),
+        };
+
+        self.previous_target = Some(*target);
+
+        let wanted = nalgebra_glm::clamp_vec(
+            &(velocity * self.look_ahead_time),
+            &-self.max_look_ahead,
+            &self.max_look_ahead,
+        );
+
+        self.look_ahead = nalgebra_glm::lerp(&self.look_ahead, &wanted, damp(self.look_ahead_damping, dt));
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Where the camera would like to be right now
+    pub fn goal(&self, target: &nalgebra_glm::Vec2) -> nalgebra_glm::Vec2 {
+        let focus = self.focus.unwrap_or(*target);
+        self.clamp_to_bounds(&(focus + self.offset + self.look_ahead))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn update(&mut self, camera: &mut core::Camera, target: &mut core::Transform, dt: f32) {
+        let target = target.world_translation_2d();
+
+        self.update_look_ahead(&target, dt);
+        let focus = self.update_focus(&target);
+        let goal = focus + self.offset + self.look_ahead;
+
+        let mut t = camera.transform().translation_2d();
+        t = nalgebra_glm::lerp(&t, &goal, damp(self.damping, dt));
+        t = self.clamp_to_bounds(&t);
+
+        if let Some(handover) = self.handover.as_mut() {
+            handover.timer += dt;
+
+            let progress = if handover.duration > 0.0 { (handover.timer / handover.duration).min(1.0) } else { 1.0 };
+            t = nalgebra_glm::lerp(&handover.from, &t, handover.easing.sample(progress));
+
+            if progress >= 1.0 {
+                self.handover = None;
+            }
+        }
+
+        camera.transform().set_translation_2d(&t);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Jumps straight to the goal, e.g. when a level starts
+    pub fn snap(&mut self, camera: &mut core::Camera, target: &mut core::Transform) {
+        self.reset();
+
+        let target = target.world_translation_2d();
+        self.update_focus(&target);
+
+        let goal = self.goal(&target);
+        camera.transform().set_translation_2d(&goal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: &nalgebra_glm::Vec2, expected: &nalgebra_glm::Vec2) {
+        assert!(nalgebra_glm::distance(actual, expected) < 1e-2, "expected {}, got {}", expected, actual);
+    }
+
+    fn target_at(x: f32, y: f32) -> core::Transform {
+        let mut target = core::Transform::new();
+        target.set_translation_2d_f(x, y);
+
+        target
+    }
+
+    fn camera_at(controller: &mut CameraController, x: f32, y: f32) -> core::Camera {
+        let mut camera = core::Camera::new();
+        controller.snap(&mut camera, &mut target_at(x, y));
+
+        camera
+    }
+
+    #[test]
+    fn the_dead_zone_holds_the_camera_until_the_target_leaves_it() {
+        let mut controller = CameraController::new();
+        controller.set_damping(1.0).set_dead_zone(&nalgebra_glm::vec2(100.0, 40.0));
+        let mut camera = camera_at(&mut controller, 0.0, 0.0);
+
+        controller.update(&mut camera, &mut target_at(40.0, -15.0), 1.0 / 60.0);
+        assert_near(&camera.transform().translation_2d(), &nalgebra_glm::vec2(0.0, 0.0));
+
+        // Only pushed as far as needed to keep the target on the zone's edge
+        controller.update(&mut camera, &mut target_at(80.0, -30.0), 1.0 / 60.0);
+        assert_near(&camera.transform().translation_2d(), &nalgebra_glm::vec2(30.0, -10.0));
+
+        controller.update(&mut camera, &mut target_at(0.0, -30.0), 1.0 / 60.0);
+        assert_near(&camera.transform().translation_2d(), &nalgebra_glm::vec2(30.0, -10.0));
+    }
+
+    #[test]
+    fn damping_does_not_depend_on_the_tick_rate() {
+        let mut controller = CameraController::new();
+        let mut coarse = camera_at(&mut controller, 0.0, 0.0);
+        controller.update(&mut coarse, &mut target_at(100.0, 0.0), 1.0);
+
+        let mut controller = CameraController::new();
+        let mut fine = camera_at(&mut controller, 0.0, 0.0);
+        for _ in 0..60 {
+            controller.update(&mut fine, &mut target_at(100.0, 0.0), 1.0 / 60.0);
+        }
+
+        // The default damping of 100 leaves a hundredth of the distance after a second
+        assert_near(&coarse.transform().translation_2d(), &nalgebra_glm::vec2(99.0, 0.0));
+        assert_near(&fine.transform().translation_2d(), &nalgebra_glm::vec2(99.0, 0.0));
+    }
+
+    #[test]
+    fn the_camera_stays_inside_the_bounds() {
+        let mut controller = CameraController::new();
+        controller.set_bounds(&core::Rect::new(&nalgebra_glm::vec2(-100.0, -50.0), &nalgebra_glm::vec2(100.0, f32::INFINITY)));
+        let mut camera = camera_at(&mut controller, 500.0, -500.0);
+        assert_near(&camera.transform().translation_2d(), &nalgebra_glm::vec2(100.0, -50.0));
+
+        for _ in 0..120 {
+            controller.update(&mut camera, &mut target_at(-500.0, 1000.0), 1.0 / 60.0);
+
+            let t = camera.transform().translation_2d();
+            assert!(t.x >= -100.0 && t.x <= 100.0 && t.y >= -50.0);
+        }
+
+        // Two seconds of damping leave a ten thousandth of the way up from the bottom bound
+        assert_near(&camera.transform().translation_2d(), &nalgebra_glm::vec2(-100.0, 1000.0 - 1050.0 / 10000.0));
+    }
+
+    #[test]
+    fn handing_over_eases_from_the_old_target_to_the_new_one() {
+        let mut controller = CameraController::new();
+        controller.set_damping(1.0);
+        let mut camera = camera_at(&mut controller, 0.0, 0.0);
+
+        controller.begin_handover(&mut camera, 1.0, core::easing::Curve::Function(core::easing::linear));
+        assert!(controller.is_handing_over());
+
+        controller.update(&mut camera, &mut target_at(200.0, 100.0), 0.5);
+        assert_near(&camera.transform().translation_2d(), &nalgebra_glm::vec2(100.0, 50.0));
+        assert!(controller.is_handing_over());
+
+        controller.update(&mut camera, &mut target_at(200.0, 100.0), 0.5);
+        assert_near(&camera.transform().translation_2d(), &nalgebra_glm::vec2(200.0, 100.0));
+        assert!(!controller.is_handing_over());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::snuff::core;
+
+// Shakes a camera by its view offset, so whatever moves the camera's transform is left alone.
+// Trauma goes from 0 to 1 and the shake grows with its square, so small hits stay subtle
+pub struct CameraShake {
+    trauma: f32,
+    trauma_decay: f32,
+    max_translation: nalgebra_glm::Vec2,
+    max_rotation: f32,
+    frequency: f32,
+    impulse: nalgebra_glm::Vec2,
+    impulse_damping: f32,
+    time: f32,
+    seed: u32,
+}
+
+//---------------------------------------------------------------------------------------------------
+// Cheap integer hash, good enough for noise
+fn hash(seed: u32, i: i32) -> u32 {
+    let mut h = seed ^ (i as u32).wrapping_mul(0x9E37_79B9);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85EB_CA6B);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xC2B2_AE35);
+    h ^= h >> 16;
+
+    h
+}
+
+//---------------------------------------------------------------------------------------------------
+// One dimensional value noise between -1 and 1, smoothly blending a random value at every whole number
+fn noise(seed: u32, x: f32) -> f32 {
+    let i = x.floor();
+    let f = x - i;
+
+    let value = |i: i32| (hash(seed, i) as f32 / u32::MAX as f32) * 2.0 - 1.0;
+    let a = value(i as i32);
+    let b = value(i as i32 + 1);
+
+    let fade = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+
+    a + (b - a) * fade
+}
+
+impl CameraShake {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> CameraShake {
+        CameraShake {
+            trauma: 0.0,
+            trauma_decay: 1.0,
+            max_translation: nalgebra_glm::vec2(16.0, 16.0),
+            max_rotation: 0.05,
+            frequency: 15.0,
+            impulse: nalgebra_glm::vec2(0.0, 0.0),
+            impulse_damping: 1000.0,
+            time: 0.0,
+            seed: 0,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // How much trauma goes away every second
+    pub fn set_trauma_decay(&mut self, decay: f32) -> &mut CameraShake {
+        self.trauma_decay = decay.max(0.0);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_max_translation(&mut self, max_translation: &nalgebra_glm::Vec2) -> &mut CameraShake {
+        self.max_translation = nalgebra_glm::abs(max_translation);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_max_translation_f(&mut self, x: f32, y: f32) -> &mut CameraShake {
+        self.set_max_translation(&nalgebra_glm::vec2(x, y))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // In radians
+    pub fn set_max_rotation(&mut self, max_rotation: f32) -> &mut CameraShake {
+        self.max_rotation = max_rotation.abs();
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // How fast the noise is sampled, higher values shake more violently
+    pub fn set_frequency(&mut self, frequency: f32) -> &mut CameraShake {
+        self.frequency = frequency;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Same damping as the camera controller, how much of an impulse is left after a second
+    pub fn set_impulse_damping(&mut self, damping: f32) -> &mut CameraShake {
+        self.impulse_damping = damping;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_seed(&mut self, seed: u32) -> &mut CameraShake {
+        self.seed = seed;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Kicks the view in a direction, it then settles back on its own
+    pub fn add_impulse(&mut self, impulse: &nalgebra_glm::Vec2) {
+        self.impulse += impulse;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn add_impulse_f(&mut self, x: f32, y: f32) {
+        self.add_impulse(&nalgebra_glm::vec2(x, y))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_shaking(&self) -> bool {
+        self.trauma > 0.0 || nalgebra_glm::length(&self.impulse) > 0.01
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn reset(&mut self) {
+        self.trauma = 0.0;
+        self.impulse = nalgebra_glm::vec2(0.0, 0.0);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - self.trauma_decay * dt).max(0.0);
+
+        if self.impulse_damping <= 1.0 {
+            self.impulse = nalgebra_glm::vec2(0.0, 0.0);
+        } else {
+            self.impulse *= f32::powf(1.0 / self.impulse_damping, dt);
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The translation and rotation to apply on top of the camera this frame
+    pub fn offset(&self) -> (nalgebra_glm::Vec2, f32) {
+        let shake = self.trauma * self.trauma;
+        let t = self.time * self.frequency;
+
+        // Every axis reads its own noise, so they don't move in lockstep
+        let translation = nalgebra_glm::vec2(
+            self.max_translation.x * shake * noise(self.seed, t),
+            self.max_translation.y * shake * noise(self.seed.wrapping_add(1), t),
+        );
+        let rotation = self.max_rotation * shake * noise(self.seed.wrapping_add(2), t);
+
+        (translation + self.impulse, rotation)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn apply(&self, camera: &mut core::Camera) {
+        let (translation, rotation) = self.offset();
+        camera.set_view_offset(&translation, rotation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn trauma_decays_at_a_fixed_rate() {
+        let mut shake = CameraShake::new();
+        shake.set_trauma_decay(2.0);
+        shake.add_trauma(0.5);
+
+        shake.update(0.1);
+        assert_near(shake.trauma(), 0.3);
+
+        for _ in 0..60 {
+            shake.update(1.0 / 60.0);
+        }
+        assert_eq!(shake.trauma(), 0.0);
+        assert!(!shake.is_shaking());
+    }
+
+    #[test]
+    fn trauma_stays_between_zero_and_one() {
+        let mut shake = CameraShake::new();
+        shake.add_trauma(0.8);
+        shake.add_trauma(0.8);
+        assert_eq!(shake.trauma(), 1.0);
+
+        shake.add_trauma(-5.0);
+        assert_eq!(shake.trauma(), 0.0);
+    }
+
+    #[test]
+    fn impulses_fall_off_with_the_damping() {
+        let mut shake = CameraShake::new();
+        shake.set_impulse_damping(100.0);
+        shake.add_impulse_f(0.0, -10.0);
+
+        for _ in 0..30 {
+            shake.update(1.0 / 60.0);
+        }
+
+        // Without trauma the offset is just what is left of the impulse, a tenth after half a second
+        let (translation, rotation) = shake.offset();
+        assert_near(translation.x, 0.0);
+        assert_near(translation.y, -1.0);
+        assert_eq!(rotation, 0.0);
+
+        shake.set_impulse_damping(1.0);
+        shake.update(1.0 / 60.0);
+        assert_eq!(shake.offset().0, nalgebra_glm::vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn the_shake_stays_within_its_limits() {
+        let mut shake = CameraShake::new();
+        shake.set_trauma_decay(0.0).set_max_translation_f(10.0, 20.0).set_max_rotation(0.1);
+        shake.add_trauma(0.5);
+
+        // Trauma is squared, so half the trauma only shakes a quarter as much
+        for _ in 0..600 {
+            shake.update(1.0 / 60.0);
+
+            let (translation, rotation) = shake.offset();
+            assert!(translation.x.abs() <= 2.5 && translation.y.abs() <= 5.0 && rotation.abs() <= 0.025);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::cell::{Cell, RefCell};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Talks to the system clipboard through the tools every desktop ships with, rather than pulling in a
+// windowing specific library. Text copied in-game is always kept, so pasting works even without them
+pub struct Clipboard {
+    local: RefCell<Option<String>>,
+    warned: Cell<bool>,
+}
+
+//---------------------------------------------------------------------------------------------------
+// Programs that print the clipboard, tried in order
+fn paste_commands() -> Vec<Vec<&'static str>> {
+    if cfg!(target_os = "macos") {
+        vec![vec!["pbpaste"]]
+    } else if cfg!(target_os = "windows") {
+        vec![vec!["powershell", "-NoProfile", "-Command", "Get-Clipboard -Raw"]]
+    } else {
+        let mut commands = Vec::new();
+
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.push(vec!["wl-paste", "--no-newline"]);
+        }
+
+        commands.push(vec!["xclip", "-o", "-selection", "clipboard"]);
+        commands.push(vec!["xsel", "--output", "--clipboard"]);
+        commands
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+// Programs that read the new clipboard contents from stdin, tried in order
+fn copy_commands() -> Vec<Vec<&'static str>> {
+    if cfg!(target_os = "macos") {
+        vec![vec!["pbcopy"]]
+    } else if cfg!(target_os = "windows") {
+        vec![vec!["clip"]]
+    } else {
+        let mut commands = Vec::new();
+
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.push(vec!["wl-copy"]);
+        }
+
+        commands.push(vec!["xclip", "-i", "-selection", "clipboard"]);
+        commands.push(vec!["xsel", "--input", "--clipboard"]);
+        commands
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+fn run_paste(command: &[&str]) -> Option<String> {
+    let output = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8(output.stdout).ok()
+}
+
+//---------------------------------------------------------------------------------------------------
+fn run_copy(command: &[&str], text: &str) -> bool {
+    let child = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+
+    // Dropping stdin closes it, which is how the tool knows the text is complete
+    let written = match child.stdin.take() {
+        Some(mut stdin) => stdin.write_all(text.as_bytes()).is_ok(),
+        None => false,
+    };
+
+    match child.wait() {
+        Ok(status) => written && status.success(),
+        Err(_) => false,
+    }
+}
+
+impl Clipboard {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> Clipboard {
+        Clipboard {
+            local: RefCell::new(None),
+            warned: Cell::new(false),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn warn_once(&self) {
+        if !self.warned.replace(true) {
+            println!("[Clipboard] No clipboard tool found, only text copied in-game can be pasted");
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn text(&self) -> Option<String> {
+        for command in paste_commands() {
+            if let Some(text) = run_paste(&command) {
+                return Some(text);
+            }
+        }
+
+        self.warn_once();
+        self.local.borrow().clone()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_text(&self, text: &str) {
+        *self.local.borrow_mut() = Some(String::from(text));
+
+        if !copy_commands().iter().any(|command| run_copy(command, text)) {
+            self.warn_once();
+        }
+    }
+}
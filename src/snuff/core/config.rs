@@ -0,0 +1,385 @@
+use crate::snuff::core;
+
+use glium::glutin::VirtualKeyCode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+const CONFIG_VERSION: u32 = 3;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub width: u16,
+    pub height: u16,
+    // The resolution the game renders at, before it's scaled up to the window
+    pub logical_width: u16,
+    pub logical_height: u16,
+    pub scaling: core::ScalingPolicy,
+    pub mode: core::WindowMode,
+    // Picked by name, since the order of monitors can change between runs. None is the primary monitor
+    pub monitor: Option<String>,
+    // Where the window was when the game last closed, in logical pixels
+    pub position: Option<(i32, i32)>,
+    pub vsync: bool,
+    // Replaced by mode in version 2, only read to migrate older files
+    #[serde(rename = "fullscreen", skip_serializing)]
+    legacy_fullscreen: bool,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> DisplayConfig {
+        DisplayConfig {
+            width: 1280,
+            height: 720,
+            logical_width: 1280,
+            logical_height: 720,
+            scaling: core::ScalingPolicy::Fit,
+            mode: core::WindowMode::Windowed,
+            monitor: None,
+            position: None,
+            vsync: false,
+            legacy_fullscreen: false,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub master_volume: f32,
+    pub buses: BTreeMap<String, f32>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> AudioConfig {
+        AudioConfig {
+            master_volume: 1.0,
+            buses: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Files from before the config was versioned have no version field at all
+    #[serde(default)]
+    version: u32,
+    pub display: DisplayConfig,
+    pub audio: AudioConfig,
+    pub bindings: BTreeMap<String, Vec<core::Binding>>,
+    pub axes: BTreeMap<String, Vec<core::AxisBinding>>,
+    // Gameplay numbers the game lets players tweak by hand
+    pub tunables: BTreeMap<String, f32>,
+    // Replaced by bindings in version 3, only read to migrate older files
+    #[serde(rename = "key_bindings", skip_serializing)]
+    legacy_key_bindings: BTreeMap<String, Vec<VirtualKeyCode>>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            version: CONFIG_VERSION,
+            display: DisplayConfig::default(),
+            audio: AudioConfig::default(),
+            bindings: BTreeMap::new(),
+            axes: BTreeMap::new(),
+            tunables: BTreeMap::new(),
+            legacy_key_bindings: BTreeMap::new(),
+            path: None,
+        }
+    }
+}
+
+impl Config {
+    //---------------------------------------------------------------------------------------------------
+    pub fn default_path(app_name: &str) -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(app_name).join("config.ron"))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Falls back to the defaults if the file is missing or broken, so a bad config never stops the game
+    pub fn load(app_name: &str) -> Config {
+        let path = match Config::default_path(app_name) {
+            Some(path) => path,
+            None => {
+                println!("[Config] Could not find the user config directory, using defaults");
+                return Config::default();
+            }
+        };
+
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(contents) => match Config::parse(&contents) {
+                Ok(config) => {
+                    println!("[Config] Loaded '{}'", path.display());
+                    config
+                }
+                Err(e) => {
+                    // Keep the broken file around, saving the defaults would otherwise overwrite it
+                    let backup = path.with_extension("ron.broken");
+                    let _ = std::fs::copy(&path, &backup);
+
+                    println!(
+                        "[Config] Could not parse '{}', using defaults and keeping a copy at '{}': {}",
+                        path.display(),
+                        backup.display(),
+                        e
+                    );
+                    Config::default()
+                }
+            },
+            Err(_) => {
+                println!("[Config] No config at '{}', using defaults", path.display());
+                Config::default()
+            }
+        };
+
+        config.path = Some(path);
+
+        config
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Brings configs from older versions up to date while parsing
+    fn parse(contents: &str) -> std::io::Result<Config> {
+        let mut config: Config = ron::from_str(contents)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+
+        config.migrate();
+
+        Ok(config)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn migrate(&mut self) {
+        if self.version > CONFIG_VERSION {
+            println!(
+                "[Config] Config version {} is newer than this build ({}), unknown settings are ignored",
+                self.version, CONFIG_VERSION
+            );
+            return;
+        }
+
+        // Upgrades happen one version at a time, so a step only has to know about the version before it.
+        // Going from 0 (unversioned) to 1 needs no work, missing fields already have their defaults
+        while self.version < CONFIG_VERSION {
+            self.version += 1;
+
+            // Version 2 replaced the fullscreen flag with a window mode
+            if self.version == 2 && self.display.legacy_fullscreen {
+                self.display.mode = core::WindowMode::Fullscreen;
+            }
+
+            // Version 3 allowed actions to be bound to more than keys
+            if self.version == 3 {
+                for (action, keys) in &self.legacy_key_bindings {
+                    let bindings = keys.iter().map(|key| core::Binding::Key(*key)).collect();
+                    self.bindings.insert(action.clone(), bindings);
+                }
+            }
+
+            println!("[Config] Migrated config to version {}", self.version);
+        }
+
+        self.display.legacy_fullscreen = false;
+        self.legacy_key_bindings.clear();
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "the config has no path to save to",
+                ))
+            }
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+
+        std::fs::write(path, contents)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn volume(&self, bus: &str) -> f32 {
+        let bus_volume = self.audio.buses.get(bus).cloned().unwrap_or(1.0);
+
+        (self.audio.master_volume * bus_volume).clamp(0.0, 1.0)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_volume(&mut self, bus: &str, volume: f32) -> &mut Config {
+        self.audio.buses.insert(String::from(bus), volume.clamp(0.0, 1.0));
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Games register the buses, actions and tunables they use, without overwriting what the user picked
+    pub fn add_default_volume(&mut self, bus: &str, volume: f32) -> &mut Config {
+        if !self.audio.buses.contains_key(bus) {
+            self.set_volume(bus, volume);
+        }
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn add_default_bindings(&mut self, action: &str, bindings: &[core::Binding]) -> &mut Config {
+        if !self.bindings.contains_key(action) {
+            self.set_bindings(action, bindings);
+        }
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_bindings(&mut self, action: &str, bindings: &[core::Binding]) -> &mut Config {
+        self.bindings.insert(String::from(action), bindings.to_vec());
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn add_default_axis(&mut self, axis: &str, bindings: &[core::AxisBinding]) -> &mut Config {
+        if !self.axes.contains_key(axis) {
+            self.axes.insert(String::from(axis), bindings.to_vec());
+        }
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn tunable(&self, name: &str) -> Option<f32> {
+        self.tunables.get(name).cloned()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_tunable(&mut self, name: &str, value: f32) -> &mut Config {
+        self.tunables.insert(String::from(name), value);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn add_default_tunable(&mut self, name: &str, value: f32) -> &mut Config {
+        if !self.tunables.contains_key(name) {
+            self.set_tunable(name, value);
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unversioned_configs_keep_their_settings() {
+        let config = Config::parse("(display: (width: 800, height: 600))").unwrap();
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!((config.display.width, config.display.height), (800, 600));
+        assert_eq!(config.display.mode, core::WindowMode::Windowed);
+    }
+
+    #[test]
+    fn fullscreen_flag_becomes_a_window_mode() {
+        let config = Config::parse("(version: 1, display: (fullscreen: true))").unwrap();
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.display.mode, core::WindowMode::Fullscreen);
+        assert!(!config.display.legacy_fullscreen);
+    }
+
+    #[test]
+    fn key_bindings_become_bindings() {
+        let config = Config::parse("(version: 2, key_bindings: {\"hop\": [Space, Up]})").unwrap();
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(
+            config.bindings["hop"],
+            vec![core::Binding::Key(VirtualKeyCode::Space), core::Binding::Key(VirtualKeyCode::Up)]
+        );
+        assert!(config.legacy_key_bindings.is_empty());
+    }
+
+    #[test]
+    fn key_bindings_replace_the_registered_defaults() {
+        let mut config = Config::parse("(version: 2, key_bindings: {\"hop\": [W]})").unwrap();
+        config.add_default_bindings("hop", &[core::Binding::Key(VirtualKeyCode::Space)]);
+
+        assert_eq!(config.bindings["hop"], vec![core::Binding::Key(VirtualKeyCode::W)]);
+    }
+
+    #[test]
+    fn version_one_configs_migrate_all_the_way() {
+        let config = Config::parse("(version: 1, display: (fullscreen: true), key_bindings: {\"left\": [A]})").unwrap();
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.display.mode, core::WindowMode::Fullscreen);
+        assert_eq!(config.bindings["left"], vec![core::Binding::Key(VirtualKeyCode::A)]);
+        assert!(!config.display.legacy_fullscreen);
+        assert!(config.legacy_key_bindings.is_empty());
+    }
+
+    #[test]
+    fn current_configs_ignore_key_bindings() {
+        let config = Config::parse(&format!("(version: {}, key_bindings: {{\"hop\": [W]}})", CONFIG_VERSION)).unwrap();
+
+        assert!(!config.bindings.contains_key("hop"));
+        assert!(config.legacy_key_bindings.is_empty());
+    }
+
+    #[test]
+    fn newer_configs_are_left_alone() {
+        let config = Config::parse(&format!("(version: {}, display: (fullscreen: true))", CONFIG_VERSION + 1)).unwrap();
+
+        assert_eq!(config.version, CONFIG_VERSION + 1);
+        assert_eq!(config.display.mode, core::WindowMode::Windowed);
+    }
+
+    #[test]
+    fn broken_configs_are_an_error() {
+        assert!(Config::parse("(display: (width: \"wide\"))").is_err());
+    }
+
+    #[test]
+    fn volumes_are_clamped() {
+        let mut config = Config::default();
+        config.audio.master_volume = 2.0;
+        config.set_volume("music", 0.5).set_volume("sfx", -1.0);
+
+        assert_eq!(config.volume("music"), 1.0);
+        assert_eq!(config.volume("sfx"), 0.0);
+        assert_eq!(config.volume("unknown"), 1.0);
+    }
+
+    #[test]
+    fn tunables_keep_what_the_user_set() {
+        let mut config = Config::parse("(tunables: {\"hop_chain_delay\": 0.25})").unwrap();
+        config
+            .add_default_tunable("hop_chain_delay", 0.0)
+            .add_default_tunable("hop_buffer_duration", 0.15);
+
+        assert_eq!(config.tunable("hop_chain_delay"), Some(0.25));
+        assert_eq!(config.tunable("hop_buffer_duration"), Some(0.15));
+        assert_eq!(config.tunable("unknown"), None);
+    }
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum WindowMode {
+    Windowed,
+    // A window without decorations that covers the whole monitor
+    Borderless,
+    // Hands the monitor to the game. glutin 0.21 (winit 0.19) can neither list nor set video modes,
+    // so this always runs in the monitor's current video mode
+    Fullscreen,
+}
+
+#[derive(Clone, Debug)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub name: String,
+    // In physical pixels, on the desktop the monitors share
+    pub width: u32,
+    pub height: u32,
+    pub x: i32,
+    pub y: i32,
+    pub hidpi_factor: f64,
+}
+
+impl WindowMode {
+    //---------------------------------------------------------------------------------------------------
+    pub fn from_name(name: &str) -> Option<WindowMode> {
+        match name.to_lowercase().as_str() {
+            "windowed" => Some(WindowMode::Windowed),
+            "borderless" => Some(WindowMode::Borderless),
+            "fullscreen" => Some(WindowMode::Fullscreen),
+            _ => None,
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use serde::Deserialize;
+use std::f32::consts::PI;
+
 pub fn linear(t : f32) -> f32 {
     t
 }
@@ -25,4 +28,560 @@ pub fn out_cubic(t : f32) -> f32 {
 
 pub fn in_out_cubic(t : f32) -> f32 {
     if t < 0.5 { 4.0 * t * t * t } else { (t - 1.0) * (2.0 * t - 2.0) * (2.0 * t - 2.0) + 1.0 }
-}
\ No newline at end of file
+}
+
+pub fn in_quart(t : f32) -> f32 {
+    t * t * t * t
+}
+
+pub fn out_quart(t : f32) -> f32 {
+    1.0 - (1.0 - t).powi(4)
+}
+
+pub fn in_out_quart(t : f32) -> f32 {
+    if t < 0.5 { 8.0 * t * t * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(4) * 0.5 }
+}
+
+pub fn in_quint(t : f32) -> f32 {
+    t * t * t * t * t
+}
+
+pub fn out_quint(t : f32) -> f32 {
+    1.0 - (1.0 - t).powi(5)
+}
+
+pub fn in_out_quint(t : f32) -> f32 {
+    if t < 0.5 { 16.0 * t * t * t * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(5) * 0.5 }
+}
+
+pub fn in_sine(t : f32) -> f32 {
+    1.0 - (t * PI * 0.5).cos()
+}
+
+pub fn out_sine(t : f32) -> f32 {
+    (t * PI * 0.5).sin()
+}
+
+pub fn in_out_sine(t : f32) -> f32 {
+    -((t * PI).cos() - 1.0) * 0.5
+}
+
+pub fn in_expo(t : f32) -> f32 {
+    if t <= 0.0 { 0.0 } else { 2.0f32.powf(10.0 * t - 10.0) }
+}
+
+pub fn out_expo(t : f32) -> f32 {
+    if t >= 1.0 { 1.0 } else { 1.0 - 2.0f32.powf(-10.0 * t) }
+}
+
+pub fn in_out_expo(t : f32) -> f32 {
+    if t <= 0.0 {
+        0.0
+    } else if t >= 1.0 {
+        1.0
+    } else if t < 0.5 {
+        2.0f32.powf(20.0 * t - 10.0) * 0.5
+    } else {
+        (2.0 - 2.0f32.powf(-20.0 * t + 10.0)) * 0.5
+    }
+}
+
+pub fn in_circ(t : f32) -> f32 {
+    1.0 - (1.0 - t * t).max(0.0).sqrt()
+}
+
+pub fn out_circ(t : f32) -> f32 {
+    (1.0 - (t - 1.0) * (t - 1.0)).max(0.0).sqrt()
+}
+
+pub fn in_out_circ(t : f32) -> f32 {
+    if t < 0.5 {
+        (1.0 - (1.0 - 4.0 * t * t).max(0.0).sqrt()) * 0.5
+    } else {
+        ((1.0 - (-2.0 * t + 2.0).powi(2)).max(0.0).sqrt() + 1.0) * 0.5
+    }
+}
+
+// How far back overshoots, this is the usual 10% from Penner's equations
+const BACK_OVERSHOOT: f32 = 1.70158;
+
+pub fn in_back(t : f32) -> f32 {
+    (BACK_OVERSHOOT + 1.0) * t * t * t - BACK_OVERSHOOT * t * t
+}
+
+pub fn out_back(t : f32) -> f32 {
+    let t2 = t - 1.0;
+    1.0 + (BACK_OVERSHOOT + 1.0) * t2 * t2 * t2 + BACK_OVERSHOOT * t2 * t2
+}
+
+pub fn in_out_back(t : f32) -> f32 {
+    let c = BACK_OVERSHOOT * 1.525;
+
+    if t < 0.5 {
+        (2.0 * t).powi(2) * ((c + 1.0) * 2.0 * t - c) * 0.5
+    } else {
+        ((2.0 * t - 2.0).powi(2) * ((c + 1.0) * (t * 2.0 - 2.0) + c) + 2.0) * 0.5
+    }
+}
+
+pub fn in_elastic(t : f32) -> f32 {
+    if t <= 0.0 || t >= 1.0 {
+        return t.clamp(0.0, 1.0);
+    }
+
+    -(2.0f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * (2.0 * PI / 3.0)).sin()
+}
+
+pub fn out_elastic(t : f32) -> f32 {
+    if t <= 0.0 || t >= 1.0 {
+        return t.clamp(0.0, 1.0);
+    }
+
+    2.0f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+}
+
+pub fn in_out_elastic(t : f32) -> f32 {
+    if t <= 0.0 || t >= 1.0 {
+        return t.clamp(0.0, 1.0);
+    }
+
+    let s = ((20.0 * t - 11.125) * (2.0 * PI / 4.5)).sin();
+
+    if t < 0.5 {
+        -(2.0f32.powf(20.0 * t - 10.0) * s) * 0.5
+    } else {
+        2.0f32.powf(-20.0 * t + 10.0) * s * 0.5 + 1.0
+    }
+}
+
+pub fn out_bounce(t : f32) -> f32 {
+    let n = 7.5625;
+    let d = 2.75;
+
+    if t < 1.0 / d {
+        n * t * t
+    } else if t < 2.0 / d {
+        let t2 = t - 1.5 / d;
+        n * t2 * t2 + 0.75
+    } else if t < 2.5 / d {
+        let t2 = t - 2.25 / d;
+        n * t2 * t2 + 0.9375
+    } else {
+        let t2 = t - 2.625 / d;
+        n * t2 * t2 + 0.984375
+    }
+}
+
+pub fn in_bounce(t : f32) -> f32 {
+    1.0 - out_bounce(1.0 - t)
+}
+
+pub fn in_out_bounce(t : f32) -> f32 {
+    if t < 0.5 { (1.0 - out_bounce(1.0 - 2.0 * t)) * 0.5 } else { (1.0 + out_bounce(2.0 * t - 1.0)) * 0.5 }
+}
+
+pub type EasingFn = fn(f32) -> f32;
+
+const EASINGS: &[(&str, EasingFn)] = &[
+    ("linear", linear),
+    ("in_quad", in_quad),
+    ("out_quad", out_quad),
+    ("in_out_quad", in_out_quad),
+    ("in_cubic", in_cubic),
+    ("out_cubic", out_cubic),
+    ("in_out_cubic", in_out_cubic),
+    ("in_quart", in_quart),
+    ("out_quart", out_quart),
+    ("in_out_quart", in_out_quart),
+    ("in_quint", in_quint),
+    ("out_quint", out_quint),
+    ("in_out_quint", in_out_quint),
+    ("in_sine", in_sine),
+    ("out_sine", out_sine),
+    ("in_out_sine", in_out_sine),
+    ("in_expo", in_expo),
+    ("out_expo", out_expo),
+    ("in_out_expo", in_out_expo),
+    ("in_circ", in_circ),
+    ("out_circ", out_circ),
+    ("in_out_circ", in_out_circ),
+    ("in_back", in_back),
+    ("out_back", out_back),
+    ("in_out_back", in_out_back),
+    ("in_elastic", in_elastic),
+    ("out_elastic", out_elastic),
+    ("in_out_elastic", in_out_elastic),
+    ("in_bounce", in_bounce),
+    ("out_bounce", out_bounce),
+    ("in_out_bounce", in_out_bounce),
+];
+
+//---------------------------------------------------------------------------------------------------
+pub fn names() -> impl Iterator<Item = &'static str> {
+    EASINGS.iter().map(|(name, _)| *name)
+}
+
+//---------------------------------------------------------------------------------------------------
+pub fn by_name(name: &str) -> Option<EasingFn> {
+    EASINGS.iter().find(|(easing_name, _)| *easing_name == name).map(|(_, easing)| *easing)
+}
+
+// A CSS style cubic-bezier(x1, y1, x2, y2) curve, with the end points fixed at (0, 0) and (1, 1)
+#[derive(Clone, Copy, Debug)]
+pub struct CubicBezier {
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+}
+
+//---------------------------------------------------------------------------------------------------
+fn bezier(p1: f32, p2: f32, s: f32) -> f32 {
+    let inv = 1.0 - s;
+    3.0 * inv * inv * s * p1 + 3.0 * inv * s * s * p2 + s * s * s
+}
+
+//---------------------------------------------------------------------------------------------------
+fn bezier_slope(p1: f32, p2: f32, s: f32) -> f32 {
+    let inv = 1.0 - s;
+    3.0 * inv * inv * p1 + 6.0 * inv * s * (p2 - p1) + 3.0 * s * s * (1.0 - p2)
+}
+
+impl CubicBezier {
+    //---------------------------------------------------------------------------------------------------
+    // The x coordinates are clamped to [0, 1] like in CSS, so every t maps to exactly one point
+    pub fn new(x1: f32, y1: f32, x2: f32, y2: f32) -> CubicBezier {
+        CubicBezier {
+            x1: x1.clamp(0.0, 1.0),
+            y1,
+            x2: x2.clamp(0.0, 1.0),
+            y2,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn sample(&self, t: f32) -> f32 {
+        if t <= 0.0 || t >= 1.0 {
+            return t.clamp(0.0, 1.0);
+        }
+
+        // Newton's method converges in a couple of steps for most curves, bisection catches the flat ones
+        let mut s = t;
+        for _ in 0..8 {
+            let error = bezier(self.x1, self.x2, s) - t;
+            if error.abs() < 1e-6 {
+                return bezier(self.y1, self.y2, s);
+            }
+
+            let slope = bezier_slope(self.x1, self.x2, s);
+            if slope.abs() < 1e-6 {
+                break;
+            }
+
+            s -= error / slope;
+        }
+
+        let mut low = 0.0;
+        let mut high = 1.0;
+        s = t;
+
+        for _ in 0..32 {
+            let x = bezier(self.x1, self.x2, s);
+            if (x - t).abs() < 1e-6 {
+                break;
+            }
+
+            if x < t {
+                low = s;
+            } else {
+                high = s;
+            }
+
+            s = (low + high) * 0.5;
+        }
+
+        bezier(self.y1, self.y2, s)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: f32,
+    // Used on the way from this keyframe to the next one
+    pub easing: EasingFn,
+}
+
+// A piecewise curve through keyframes, sampling before the first or after the last one holds its value
+#[derive(Clone)]
+pub struct KeyframeCurve {
+    keyframes: Vec<Keyframe>,
+}
+
+impl KeyframeCurve {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> KeyframeCurve {
+        KeyframeCurve { keyframes: Vec::new() }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn with_key(self, time: f32, value: f32) -> KeyframeCurve {
+        self.with_eased_key(time, value, linear)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn with_eased_key(mut self, time: f32, value: f32, easing: EasingFn) -> KeyframeCurve {
+        let index = self.keyframes.iter().position(|key| key.time > time).unwrap_or(self.keyframes.len());
+        self.keyframes.insert(index, Keyframe { time, value, easing });
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn sample(&self, t: f32) -> f32 {
+        let first = match self.keyframes.first() {
+            Some(first) => first,
+            None => return t,
+        };
+
+        if t <= first.time {
+            return first.value;
+        }
+
+        for pair in self.keyframes.windows(2) {
+            let (from, to) = (&pair[0], &pair[1]);
+
+            if t < to.time {
+                let progress = (t - from.time) / (to.time - from.time);
+                return from.value + (to.value - from.value) * (from.easing)(progress);
+            }
+        }
+
+        self.keyframes.last().unwrap().value
+    }
+}
+
+// Anything a tween or transition can be eased with
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Curve {
+    Function(EasingFn),
+    CubicBezier(CubicBezier),
+    Keyframes(KeyframeCurve),
+}
+
+//---------------------------------------------------------------------------------------------------
+fn parse_numbers(values: &str) -> Result<Vec<f32>, String> {
+    values
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse::<f32>().map_err(|_| format!("'{}' is not a number", value)))
+        .collect()
+}
+
+//---------------------------------------------------------------------------------------------------
+fn parse_arguments<'a>(name: &'a str, function: &'a str) -> Option<&'a str> {
+    let name = name.trim();
+
+    if name.starts_with(function) && name.ends_with(')') {
+        let arguments = name[function.len()..].trim_start();
+
+        if arguments.starts_with('(') {
+            return Some(&arguments[1..arguments.len() - 1]);
+        }
+    }
+
+    None
+}
+
+impl Curve {
+    //---------------------------------------------------------------------------------------------------
+    // Accepts the easing function names, the CSS keywords, "cubic-bezier(x1, y1, x2, y2)" and
+    // "keyframes(time: value easing, ...)" where the easing of a key is optional and defaults to linear
+    pub fn parse(name: &str) -> Result<Curve, String> {
+        let trimmed = name.trim();
+
+        if let Some(easing) = by_name(trimmed) {
+            return Ok(Curve::Function(easing));
+        }
+
+        let css = match trimmed {
+            "ease" => Some(CubicBezier::new(0.25, 0.1, 0.25, 1.0)),
+            "ease-in" => Some(CubicBezier::new(0.42, 0.0, 1.0, 1.0)),
+            "ease-out" => Some(CubicBezier::new(0.0, 0.0, 0.58, 1.0)),
+            "ease-in-out" => Some(CubicBezier::new(0.42, 0.0, 0.58, 1.0)),
+            _ => None,
+        };
+
+        if let Some(curve) = css {
+            return Ok(Curve::CubicBezier(curve));
+        }
+
+        if let Some(arguments) = parse_arguments(trimmed, "cubic-bezier") {
+            let values = parse_numbers(arguments)?;
+
+            if values.len() != 4 {
+                return Err(format!("cubic-bezier takes 4 values, got {}", values.len()));
+            }
+
+            return Ok(Curve::CubicBezier(CubicBezier::new(values[0], values[1], values[2], values[3])));
+        }
+
+        if let Some(arguments) = parse_arguments(trimmed, "keyframes") {
+            let mut curve = KeyframeCurve::new();
+
+            for key in arguments.split(',').map(|key| key.trim()).filter(|key| !key.is_empty()) {
+                let mut parts = key.splitn(2, ':');
+                let time = parts.next().unwrap_or("").trim();
+                let rest = parts.next().ok_or_else(|| format!("keyframe '{}' should look like 'time: value'", key))?;
+
+                let mut rest = rest.split_whitespace();
+                let value = rest.next().unwrap_or("");
+                let easing = match rest.next() {
+                    Some(easing_name) => by_name(easing_name).ok_or_else(|| format!("unknown easing '{}'", easing_name))?,
+                    None => linear,
+                };
+
+                let time = time.parse::<f32>().map_err(|_| format!("'{}' is not a number", time))?;
+                let value = value.parse::<f32>().map_err(|_| format!("'{}' is not a number", value))?;
+
+                curve = curve.with_eased_key(time, value, easing);
+            }
+
+            return Ok(Curve::Keyframes(curve));
+        }
+
+        Err(format!("unknown easing '{}'", trimmed))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Falls back to linear, so a typo in a data file doesn't take the game down
+    pub fn named(name: &str) -> Curve {
+        match Curve::parse(name) {
+            Ok(curve) => curve,
+            Err(e) => {
+                println!("[Easing] {}, using linear", e);
+                Curve::Function(linear)
+            }
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn sample(&self, t: f32) -> f32 {
+        match self {
+            Curve::Function(easing) => easing(t),
+            Curve::CubicBezier(curve) => curve.sample(t),
+            Curve::Keyframes(curve) => curve.sample(t),
+        }
+    }
+}
+
+impl std::convert::TryFrom<String> for Curve {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Curve, String> {
+        Curve::parse(&name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn every_easing_starts_at_zero_and_ends_at_one() {
+        for (name, easing) in EASINGS {
+            assert!(easing(0.0).abs() < 1e-4, "{} starts at {}", name, easing(0.0));
+            assert!((easing(1.0) - 1.0).abs() < 1e-4, "{} ends at {}", name, easing(1.0));
+        }
+    }
+
+    #[test]
+    fn penner_formulas_match_known_values() {
+        assert_near(in_quad(0.5), 0.25);
+        assert_near(out_quad(0.5), 0.75);
+        assert_near(in_cubic(0.5), 0.125);
+        assert_near(out_cubic(0.5), 0.875);
+        assert_near(in_out_cubic(0.25), 0.0625);
+        assert_near(in_out_quad(0.5), 0.5);
+        assert_near(in_sine(0.5), 1.0 - (PI / 4.0).cos());
+        assert_near(out_expo(0.5), 1.0 - 2.0f32.powi(-5));
+        assert_near(out_bounce(0.5), 0.765625);
+        assert_near(in_bounce(0.5), 1.0 - out_bounce(0.5));
+    }
+
+    #[test]
+    fn in_out_easings_are_symmetric() {
+        let pairs: &[EasingFn] = &[in_out_quad, in_out_cubic, in_out_sine, in_out_circ, in_out_back, in_out_bounce];
+
+        for easing in pairs {
+            for &t in &[0.1, 0.25, 0.4] {
+                assert_near(easing(t) + easing(1.0 - t), 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn back_easings_overshoot() {
+        assert!(in_back(0.2) < 0.0);
+        assert!(out_back(0.8) > 1.0);
+    }
+
+    #[test]
+    fn parses_easing_names() {
+        match Curve::parse(" out_cubic ") {
+            Ok(Curve::Function(easing)) => assert_near(easing(0.5), out_cubic(0.5)),
+            _ => panic!("expected an easing function"),
+        }
+    }
+
+    #[test]
+    fn parses_css_curves() {
+        let ease = Curve::parse("ease-in-out").unwrap();
+        assert_near(ease.sample(0.5), 0.5);
+
+        let linear = Curve::parse("cubic-bezier(0, 0, 1, 1)").unwrap();
+        assert_near(linear.sample(0.3), 0.3);
+
+        // The x coordinates are clamped, the y coordinates are free to overshoot
+        let overshoot = Curve::parse("cubic-bezier(0.3 -0.5 0.7 1.5)").unwrap();
+        assert!(overshoot.sample(0.9) > 1.0);
+    }
+
+    #[test]
+    fn parses_keyframes() {
+        let curve = Curve::parse("keyframes(0: 0, 0.5: 1 in_quad, 1: 0.5)").unwrap();
+
+        assert_near(curve.sample(-1.0), 0.0);
+        assert_near(curve.sample(0.25), 0.5);
+        assert_near(curve.sample(0.75), 1.0 - 0.5 * 0.25);
+        assert_near(curve.sample(2.0), 0.5);
+    }
+
+    #[test]
+    fn keyframes_are_sorted_by_time() {
+        let curve = KeyframeCurve::new().with_key(1.0, 2.0).with_key(0.0, 1.0);
+        let times: Vec<f32> = curve.keyframes().iter().map(|key| key.time).collect();
+
+        assert_eq!(times, vec![0.0, 1.0]);
+        assert_near(curve.sample(0.5), 1.5);
+    }
+
+    #[test]
+    fn rejects_bad_curves() {
+        assert!(Curve::parse("in_wobble").is_err());
+        assert!(Curve::parse("cubic-bezier(0, 0, 1)").is_err());
+        assert!(Curve::parse("cubic-bezier(0, 0, 1, one)").is_err());
+        assert!(Curve::parse("keyframes(0 0)").is_err());
+        assert!(Curve::parse("keyframes(0: 1 in_wobble)").is_err());
+    }
+}
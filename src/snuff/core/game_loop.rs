@@ -1,9 +1,35 @@
 use crate::snuff::core;
+use crate::snuff::gfx;
 
-pub struct GameLoop {
+use glium::glutin::VirtualKeyCode;
+
+pub const SCREENSHOT_ACTION: &str = "debug_screenshot";
+pub const PROFILER_OVERLAY_ACTION: &str = "debug_profiler_overlay";
+pub const PROFILER_TRACE_ACTION: &str = "debug_profiler_trace";
+
+pub struct GameLoop<K: core::GameStateKey> {
     window: core::Window,
-    game_state_manager: core::GameStateManager,
+    audio_device: core::AudioDevice,
+    game_state_manager: core::GameStateManager<K>,
+    quit_requested: bool,
+    profiler_overlay: core::ProfilerOverlay,
+    scene_target: Option<gfx::Texture2D>,
+    present_shader: gfx::ShaderProgram,
+    present_camera: core::Camera,
+    time_controller: core::TimeController,
     frame_count: u32,
+    tick_count: u32,
+    fixed_dt: f32,
+    max_ticks_per_frame: u32,
+    time: f32,
+    recording: Option<core::InputRecording>,
+    recording_path: String,
+    playback: Option<core::InputRecording>,
+    playback_frame: usize,
+    screenshot_path: Option<String>,
+    capture_directory: Option<String>,
+    capture_dt: f32,
+    capture_frame: u32,
 }
 
 struct DeltaTime {
@@ -22,80 +48,389 @@ fn calculate_delta_time(old_time: u64) -> DeltaTime {
     }
 }
 
-impl GameLoop {
+impl<K: core::GameStateKey> GameLoop<K> {
     //---------------------------------------------------------------------------------------------------
-    fn set_working_directory() {
-        let args: Vec<String> = std::env::args().collect();
-        if args.len() > 1 {
-            let working_dir = args[1].clone();
-            assert!(
-                std::env::set_current_dir(&working_dir).is_ok(),
-                format!("[main] Invalid working directory '{}'", working_dir)
-            );
-            println!("[main] Set working directory to '{}'", working_dir);
-        }
+    pub fn add_default_bindings(config: &mut core::Config) {
+        let key = core::Binding::Key;
+
+        config
+            .add_default_bindings(SCREENSHOT_ACTION, &[key(VirtualKeyCode::F12)])
+            .add_default_bindings(PROFILER_OVERLAY_ACTION, &[key(VirtualKeyCode::F3)])
+            .add_default_bindings(PROFILER_TRACE_ACTION, &[key(VirtualKeyCode::F4)]);
     }
 
     //---------------------------------------------------------------------------------------------------
-    pub fn new(
-        window_width: u16,
-        window_height: u16,
-        window_title: &'static str,
-        vsync: bool,
-    ) -> GameLoop {
-        GameLoop::set_working_directory();
+    pub fn new(window_title: &'static str, display_config: &core::DisplayConfig) -> GameLoop<K> {
+        GameLoop::from_window(
+            core::Window::new(window_title, display_config),
+            core::AudioDevice::new(),
+        )
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn headless(window_width: u16, window_height: u16) -> GameLoop<K> {
+        GameLoop::from_window(
+            core::Window::headless(window_width, window_height),
+            core::AudioDevice::silent(),
+        )
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn from_window(mut window: core::Window, audio_device: core::AudioDevice) -> GameLoop<K> {
+        let profiler_overlay = core::ProfilerOverlay::new(window.device());
+        let present_shader = gfx::ShaderProgram::from_source(
+            window.device(),
+            "assets/shaders/fullscreen.vs".to_string(),
+            "assets/shaders/simple.fs".to_string(),
+        )
+        .unwrap();
 
         GameLoop {
-            window: core::Window::new(window_width, window_height, window_title, vsync),
+            window,
+            audio_device,
             game_state_manager: core::GameStateManager::new(),
+            quit_requested: false,
+            profiler_overlay,
+            scene_target: None,
+            present_shader,
+            present_camera: core::Camera::new(),
+            time_controller: core::TimeController::new(),
             frame_count: 0,
+            tick_count: 0,
+            fixed_dt: 1.0 / 60.0,
+            max_ticks_per_frame: 8,
+            time: 0.0,
+            recording: None,
+            recording_path: String::new(),
+            playback: None,
+            playback_frame: 0,
+            screenshot_path: None,
+            capture_directory: None,
+            capture_dt: 1.0 / 60.0,
+            capture_frame: 0,
         }
     }
 
     //---------------------------------------------------------------------------------------------------
-    fn tick(&mut self, time: f32, dt: f32) {
-        let game_state_manager = &mut self.game_state_manager;
-        let current_state = game_state_manager.get_current_state();
+    pub fn set_tick_rate(&mut self, ticks_per_second: f32) -> &mut GameLoop<K> {
+        assert!(
+            ticks_per_second > 0.0,
+            "[GameLoop] The tick rate must be greater than zero"
+        );
+
+        self.fixed_dt = 1.0 / ticks_per_second;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_max_ticks_per_frame(&mut self, max_ticks: u32) -> &mut GameLoop<K> {
+        self.max_ticks_per_frame = max_ticks.max(1);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // States keep a copy of the audio device, so this has to happen before registering them
+    pub fn mute(&mut self) -> &mut GameLoop<K> {
+        self.audio_device = core::AudioDevice::silent();
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn register_states<F>(&mut self, mut create_state: F)
+    where
+        F: FnMut(K, &mut core::Window, &core::AudioDevice) -> Box<dyn core::GameState<K>>,
+    {
+        let window = &mut self.window;
+        let audio_device = &self.audio_device;
+
+        self.game_state_manager
+            .register_states(|key| create_state(key, window, audio_device));
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn quit(&mut self) {
+        self.quit_requested = true;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The seed is only stored, so a replay can hand the same one to the game
+    pub fn start_recording(&mut self, path: &str, seed: u64) {
+        let start_state = self.game_state_manager.current_state();
+        self.recording = Some(core::InputRecording::new(seed, start_state));
+        self.recording_path = String::from(path);
+
+        println!("[GameLoop] Started recording input to '{}'", path);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            match recording.save(&self.recording_path) {
+                Ok(_) => println!(
+                    "[GameLoop] Saved {} ticks of input to '{}'",
+                    recording.len(),
+                    self.recording_path
+                ),
+                Err(e) => println!(
+                    "[GameLoop] Could not save input recording to '{}': {}",
+                    self.recording_path, e
+                ),
+            }
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn start_playback(&mut self, recording: core::InputRecording) {
+        println!("[GameLoop] Started playback of {} ticks of input", recording.len());
+
+        self.playback = Some(recording);
+        self.playback_frame = 0;
+        self.window.set_device_input_enabled(false);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn stop_playback(&mut self) {
+        if self.playback.take().is_some() {
+            println!("[GameLoop] Stopped input playback after {} ticks", self.playback_frame);
+        }
+
+        self.window.set_device_input_enabled(true);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_playing_back(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn take_screenshot(&mut self, path: &str) {
+        self.screenshot_path = Some(String::from(path));
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn start_frame_capture(&mut self, directory: &str, frames_per_second: f32) {
+        assert!(
+            frames_per_second > 0.0,
+            "[GameLoop] The capture frame rate must be greater than zero"
+        );
+
+        if let Err(e) = std::fs::create_dir_all(directory) {
+            println!("[GameLoop] Could not create capture directory '{}': {}", directory, e);
+            return;
+        }
 
-        let next_state = match current_state {
-            Some(state) => { state.update(dt, &self.window) },
-            _ => None
+        self.capture_directory = Some(String::from(directory));
+        self.capture_dt = 1.0 / frames_per_second;
+        self.capture_frame = 0;
+
+        println!("[GameLoop] Started capturing frames to '{}' at {} fps", directory, frames_per_second);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn stop_frame_capture(&mut self) {
+        if self.capture_directory.take().is_some() {
+            println!("[GameLoop] Stopped capturing frames after {} frames", self.capture_frame);
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_capturing(&self) -> bool {
+        self.capture_directory.is_some()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn apply_playback(&mut self) -> f32 {
+        let recording = match &self.playback {
+            Some(recording) => recording,
+            None => return self.fixed_dt,
         };
 
-        let mut target = self.window.begin_frame(time);
-        let current_state = game_state_manager.get_current_state();
+        match recording.frame(self.playback_frame) {
+            Some(frame) => {
+                for evt in frame.events.iter() {
+                    self.window.inject(*evt);
+                }
 
-        match current_state {
-            Some(state) => { state.draw(&mut target, dt); },
+                self.playback_frame += 1;
+                frame.dt
+            }
+            None => {
+                self.stop_playback();
+                self.fixed_dt
+            }
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn update(&mut self) {
+        let _scope = core::profiler::scope("GameLoop::update");
+
+        // Recordings dictate their own delta time, so a replay matches the original run
+        let dt = self.apply_playback();
+
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push_frame(dt, self.window.tick_events());
+        }
+
+        // Debug keys are part of the recorded input, so a replay pauses and speeds up in the same places
+        let scaled_dt = self.time_controller.tick(dt, &self.window);
+
+        let game_state_manager = &mut self.game_state_manager;
+        let next_transition = game_state_manager.update(scaled_dt, dt, &self.window);
+
+        if self.window.is_action_pressed(SCREENSHOT_ACTION) {
+            self.screenshot_path = Some(format!("screenshot_{}.png", self.frame_count));
+        }
+
+        if self.window.is_action_pressed(PROFILER_OVERLAY_ACTION) {
+            self.profiler_overlay.toggle();
+        }
+
+        if self.window.is_action_pressed(PROFILER_TRACE_ACTION) {
+            let path = format!("trace_{}.json", self.frame_count);
+
+            match core::profiler::save_chrome_trace(&path) {
+                Ok(_) => println!("[GameLoop] Saved profiler trace to '{}'", path),
+                Err(e) => println!("[GameLoop] Could not save profiler trace to '{}': {}", path, e),
+            }
+        }
+
+        // Pressed and released states only last for a single tick
+        self.window.reset_input_states();
+
+        match next_transition {
+            Some(core::StateTransition::Quit) => self.quit_requested = true,
+            Some(transition) => game_state_manager.apply(transition),
             None => {}
         };
 
+        self.time += scaled_dt;
+        self.tick_count += 1;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn draw(&mut self, alpha: f32) {
+        let draw_scope = core::profiler::scope("GameLoop::draw");
+
+        // States draw at the logical resolution, which is then scaled up into the window
+        self.update_scene_target();
+        let viewport = self.window.viewport();
+
+        let mut target = self.window.begin_frame(self.time + alpha * self.fixed_dt);
+        let scene_target = self.scene_target.as_ref().unwrap();
+
+        {
+            let mut scene = target.offscreen(scene_target);
+            self.game_state_manager.draw(&mut scene, alpha);
+        }
+
+        target.clear_output(0.0, 0.0, 0.0, 1.0);
+        target.set_viewport(Some(viewport));
+        target.opaque_blend();
+        target.fullscreen_pass(&mut self.present_camera, &mut self.present_shader, &vec![scene_target]);
+        target.alpha_blend();
+        target.set_viewport(None);
+
+        if let Some(path) = self.screenshot_path.take() {
+            if target.save_frame(&path) {
+                println!("[GameLoop] Saved screenshot to '{}'", path);
+            }
+        }
+
+        if let Some(directory) = &self.capture_directory {
+            target.save_frame(&format!("{}/{:06}.png", directory, self.capture_frame));
+            self.capture_frame += 1;
+        }
+
+        // Drawn after saving the frame, so screenshots and captures don't include it
+        self.profiler_overlay.draw(&mut target);
+
+        drop(draw_scope);
+
+        let _scope = core::profiler::scope("GameLoop::present");
         target.end();
 
-        match next_state {
-            Some(state_name) => game_state_manager.switch(&state_name[..]),
-            _ => {}
+        self.frame_count += 1;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Recreated whenever the logical resolution or scaling policy changes
+    fn update_scene_target(&mut self) {
+        let width = self.window.logical_width();
+        let height = self.window.logical_height();
+        let pixel_perfect = self.window.scaling_policy() == core::ScalingPolicy::Integer;
+
+        let matches = match &self.scene_target {
+            Some(texture) => {
+                let nearest = texture.filtering().1 == glium::uniforms::MagnifySamplerFilter::Nearest;
+                texture.dimensions() == nalgebra_glm::vec2(width, height) && nearest == pixel_perfect
+            }
+            None => false,
         };
 
-        self.frame_count += 1;
+        if !matches {
+            let texture = gfx::Texture2D::empty(self.window.device(), width, height);
+            self.scene_target = Some(if pixel_perfect { texture.with_nearest_filter() } else { texture });
+        }
     }
 
     //---------------------------------------------------------------------------------------------------
     pub fn exec(&mut self) {
         let mut old_time = time::precise_time_ns();
-        let mut dt = 0.0;
-        let mut time = 0.0;
+        let mut accumulator = 0.0;
 
-        while self.window.process_events() {
-            self.tick(time, dt);
+        while self.window.process_events() && !self.quit_requested {
+            core::profiler::begin_frame();
 
             let deltas = calculate_delta_time(old_time);
-
-            dt = deltas.new_dt;
             old_time = deltas.new_time;
 
-            time += dt;
+            // Never try to catch up on more than a handful of ticks, so a long stall
+            // (loading, dragging the window) doesn't make the simulation spiral
+            let max_frame_time = self.fixed_dt * self.max_ticks_per_frame as f32;
+
+            // While capturing, every frame is exactly one capture step apart,
+            // no matter how long it took to write the previous one to disk
+            accumulator += if self.is_capturing() {
+                self.capture_dt
+            } else {
+                deltas.new_dt.min(max_frame_time)
+            };
+
+            while accumulator >= self.fixed_dt {
+                self.update();
+                accumulator -= self.fixed_dt;
+            }
+
+            self.draw(accumulator / self.fixed_dt);
+
+            core::profiler::end_frame();
         }
+
+        self.stop_recording();
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn exec_headless(&mut self, ticks: u32, script: &core::InputScript) {
+        for _ in 0..ticks {
+            if !self.window.process_events() || self.quit_requested {
+                break;
+            }
+
+            core::profiler::begin_frame();
+
+            script.apply(self.tick_count, &mut self.window);
+
+            self.update();
+            self.draw(0.0);
+
+            core::profiler::end_frame();
+        }
+
+        self.stop_recording();
     }
 
     //---------------------------------------------------------------------------------------------------
@@ -104,12 +439,32 @@ impl GameLoop {
     }
 
     //---------------------------------------------------------------------------------------------------
-    pub fn game_state_manager(&mut self) -> &mut core::GameStateManager {
+    pub fn audio_device(&self) -> &core::AudioDevice {
+        &self.audio_device
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn game_state_manager(&mut self) -> &mut core::GameStateManager<K> {
         &mut self.game_state_manager
     }
 
+    //---------------------------------------------------------------------------------------------------
+    pub fn time_controller(&mut self) -> &mut core::TimeController {
+        &mut self.time_controller
+    }
+
     //---------------------------------------------------------------------------------------------------
     pub fn frame_count(&self) -> u32 {
         self.frame_count
     }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn tick_count(&self) -> u32 {
+        self.tick_count
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn fixed_dt(&self) -> f32 {
+        self.fixed_dt
+    }
 }
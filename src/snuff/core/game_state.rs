@@ -1,65 +1,671 @@
 use crate::snuff;
+use crate::snuff::core;
+use crate::snuff::gfx;
+use std::any::Any;
 use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
 
-pub trait GameState {
+// Implemented by the enum that names every state of a game, see game_state_keys!
+pub trait GameStateKey: Copy + Eq + Hash + Debug + 'static {
+    fn all() -> &'static [Self];
+
+    // Looks a key up by its variant name, ignoring case
+    fn from_name(name: &str) -> Option<Self> {
+        Self::all()
+            .iter()
+            .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+            .cloned()
+    }
+}
+
+// Declares a state key enum, and keeps its list of keys in sync with the variants
+#[macro_export]
+macro_rules! game_state_keys {
+    ($name:ident { $($variant:ident),* $(,)* }) => {
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        pub enum $name {
+            $($variant),*
+        }
+
+        impl $crate::snuff::core::GameStateKey for $name {
+            fn all() -> &'static [$name] {
+                &[$($name::$variant),*]
+            }
+        }
+    };
+}
+
+pub enum StateTransition<K: GameStateKey> {
+    // Leaves every state on the stack and enters the new one, using the default transition
+    Switch(K),
+    // Like Switch, for the few places that want a transition of their own
+    SwitchWith(K, core::Transition),
+    Push(K),
+    Pop,
+    // Swaps out the top of the stack, without pausing or resuming the states below
+    Replace(K),
+    Quit,
+}
+
+// Lets the manager hand out a state as its concrete type, see GameStateManager::state
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub trait GameState<K: GameStateKey>: AsAny {
     fn on_enter(&mut self);
     fn on_leave(&mut self);
-    fn update(&mut self, dt: f32, window: &snuff::core::Window) -> Option<String>;
-    fn draw(&mut self, command_buffer: &mut snuff::gfx::CommandBuffer, dt: f32);
+    fn update(&mut self, dt: f32, window: &snuff::core::Window) -> Option<StateTransition<K>>;
+    fn draw(&mut self, command_buffer: &mut snuff::gfx::CommandBuffer, alpha: f32);
+
+    // Called on the state below when another state is pushed on top of it, or popped off again
+    fn on_pause(&mut self) {}
+    fn on_resume(&mut self) {}
+
+    // Overlays can choose to let the states below them keep drawing or updating
+    fn draws_state_below(&self) -> bool {
+        false
+    }
+
+    fn updates_state_below(&self) -> bool {
+        false
+    }
+
+    // Opting out keeps a state running at real time, even when the game is paused or slowed down
+    fn uses_time_scale(&self) -> bool {
+        true
+    }
+}
+
+struct ActiveTransition<K: GameStateKey> {
+    transition: core::Transition,
+    outgoing: Vec<K>,
+    timer: f32,
+}
+
+pub struct GameStateManager<K: GameStateKey> {
+    states: HashMap<K, Box<dyn GameState<K>>>,
+    stack: Vec<K>,
+    default_transition: core::Transition,
+    active_transition: Option<ActiveTransition<K>>,
+    from_target: Option<gfx::Texture2D>,
+    to_target: Option<gfx::Texture2D>,
+    transition_camera: core::Camera,
+    transition_shaders: HashMap<String, gfx::ShaderProgram>,
 }
 
-pub struct GameStateManager {
-    states: HashMap<String, Box<GameState>>,
-    current_state: String,
+//---------------------------------------------------------------------------------------------------
+fn draw_stack<K: GameStateKey>(
+    states: &mut HashMap<K, Box<dyn GameState<K>>>,
+    stack: &[K],
+    command_buffer: &mut gfx::CommandBuffer,
+    alpha: f32,
+) {
+    let mut lowest = stack.len().max(1) - 1;
+
+    while lowest > 0 && states[&stack[lowest]].draws_state_below() {
+        lowest -= 1;
+    }
+
+    for key in stack[lowest..].iter() {
+        if let Some(s) = states.get_mut(key) {
+            let _scope = core::profiler::scope(format!("{:?}::draw", key));
+            s.draw(command_buffer, alpha);
+        }
+    }
 }
 
-impl GameStateManager {
+impl<K: GameStateKey> GameStateManager<K> {
     //---------------------------------------------------------------------------------------------------
-    pub fn new() -> GameStateManager {
+    pub fn new() -> GameStateManager<K> {
         GameStateManager {
             states: HashMap::new(),
-            current_state: String::new(),
+            stack: Vec::new(),
+            default_transition: core::Transition::cut(),
+            active_transition: None,
+            from_target: None,
+            to_target: None,
+            transition_camera: core::Camera::new(),
+            transition_shaders: HashMap::new(),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_default_transition(&mut self, transition: core::Transition) -> &mut GameStateManager<K> {
+        self.default_transition = transition;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Creates a state for every key; matching on the key makes forgetting one a compile error
+    pub fn register_states<F>(&mut self, mut create_state: F)
+    where
+        F: FnMut(K) -> Box<dyn GameState<K>>,
+    {
+        for key in K::all().iter() {
+            self.states.insert(*key, create_state(*key));
         }
     }
 
     //---------------------------------------------------------------------------------------------------
-    pub fn add_state<'a>(&mut self, name: &'a str, state: Box<GameState>) {
-        if self.states.contains_key(name) {
-            println!("[GameStateManager] Attempted to add a state with key '{}', but it already exists, skipping", name);
+    pub fn apply(&mut self, transition: StateTransition<K>) {
+        match transition {
+            StateTransition::Switch(key) => self.switch(key),
+            StateTransition::SwitchWith(key, transition) => self.switch_with(key, transition),
+            StateTransition::Push(key) => self.push(key),
+            StateTransition::Pop => self.pop(),
+            StateTransition::Replace(key) => self.replace(key),
+            StateTransition::Quit => {
+                println!("[GameStateManager] Quitting is up to the game loop, ignoring");
+            }
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn switch(&mut self, key: K) {
+        let transition = self.default_transition.clone();
+        self.switch_with(key, transition);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn switch_with(&mut self, key: K, transition: core::Transition) {
+        self.finish_transition();
+
+        // A state can't be blended with itself, so switching to a state that is already active is a cut
+        let is_cut = transition.is_cut()
+            || self.stack.is_empty()
+            || self.stack.contains(&key);
+
+        if is_cut {
+            while let Some(top) = self.stack.pop() {
+                if let Some(s) = self.states.get_mut(&top) {
+                    s.on_leave();
+                }
+            }
+        } else {
+            // The outgoing states are kept around, frozen, until the transition is over
+            self.active_transition = Some(ActiveTransition {
+                transition,
+                outgoing: self.stack.drain(..).collect(),
+                timer: 0.0,
+            });
+        }
+
+        self.states.get_mut(&key).unwrap().on_enter();
+        self.stack.push(key);
+
+        println!("[GameStateManager] Switched to state {:?}", key);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn finish_transition(&mut self) {
+        if let Some(active) = self.active_transition.take() {
+            for key in active.outgoing.iter().rev() {
+                if let Some(s) = self.states.get_mut(key) {
+                    s.on_leave();
+                }
+            }
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_transitioning(&self) -> bool {
+        self.active_transition.is_some()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn push(&mut self, key: K) {
+        if self.stack.contains(&key) {
+            println!("[GameStateManager] Attempted to push state {:?}, but it is already on the stack", key);
             return;
         }
 
-        self.states.insert(String::from(name), state);
+        self.finish_transition();
+
+        if let Some(s) = self.get_current_state() {
+            s.on_pause();
+        }
+
+        self.states.get_mut(&key).unwrap().on_enter();
+        self.stack.push(key);
+
+        println!("[GameStateManager] Pushed state {:?}", key);
     }
 
     //---------------------------------------------------------------------------------------------------
-    pub fn switch<'a>(&mut self, name: &'a str) {
+    pub fn pop(&mut self) {
+        if self.stack.len() < 2 {
+            println!("[GameStateManager] Attempted to pop a state, but there is nothing below it");
+            return;
+        }
+
+        self.finish_transition();
+
+        let top = self.stack.pop().unwrap();
+
+        if let Some(s) = self.states.get_mut(&top) {
+            s.on_leave();
+        }
+
         if let Some(s) = self.get_current_state() {
+            s.on_resume();
+        }
+
+        println!("[GameStateManager] Popped state {:?}", top);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn replace(&mut self, key: K) {
+        if self.stack.is_empty() {
+            self.switch(key);
+            return;
+        }
+
+        if self.stack[..self.stack.len() - 1].contains(&key) {
+            println!("[GameStateManager] Attempted to replace the top state with {:?}, but it is already on the stack", key);
+            return;
+        }
+
+        self.finish_transition();
+
+        let top = self.stack.pop().unwrap();
+
+        if let Some(s) = self.states.get_mut(&top) {
             s.on_leave();
         }
 
-        match self.states.get_mut(name) {
-            Some(s) => {
-                s.on_enter();
-                self.current_state = String::from(name);
-                println!("[GameStateManager] Switched to state '{}'", name);
+        self.states.get_mut(&key).unwrap().on_enter();
+        self.stack.push(key);
+
+        println!("[GameStateManager] Replaced state {:?} with {:?}", top, key);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn lowest_active_index<F>(&self, passes_through: F) -> usize
+    where
+        F: Fn(&dyn GameState<K>) -> bool,
+    {
+        if self.stack.is_empty() {
+            return 0;
+        }
+
+        let mut index = self.stack.len() - 1;
+
+        while index > 0 && passes_through(self.states[&self.stack[index]].as_ref()) {
+            index -= 1;
+        }
+
+        index
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn update(
+        &mut self,
+        scaled_dt: f32,
+        real_dt: f32,
+        window: &snuff::core::Window,
+    ) -> Option<StateTransition<K>> {
+        let finished = match self.active_transition.as_mut() {
+            Some(active) => {
+                active.timer += scaled_dt;
+                active.timer >= active.transition.duration()
             }
+            None => false,
+        };
+
+        if finished {
+            self.finish_transition();
+        }
 
-            None => {
-                println!("[GameStateManager] Attempted to switch to state '{}', but it doesn't exist, staying in current state", name);
+        let lowest = self.lowest_active_index(|s| s.updates_state_below());
+        let mut next_transition = None;
+
+        // Bottom to top, the top-most state gets the final say in where to go next
+        for key in self.stack[lowest..].iter() {
+            if let Some(s) = self.states.get_mut(key) {
+                let _scope = core::profiler::scope(format!("{:?}::update", key));
+                let dt = if s.uses_time_scale() { scaled_dt } else { real_dt };
+
+                if let Some(transition) = s.update(dt, window) {
+                    next_transition = Some(transition);
+                }
+            }
+        }
+
+        next_transition
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn draw(&mut self, command_buffer: &mut snuff::gfx::CommandBuffer, alpha: f32) {
+        let (width, height) = command_buffer.dimensions();
+
+        if self.active_transition.is_none() || width == 0 || height == 0 {
+            draw_stack(&mut self.states, &self.stack, command_buffer, alpha);
+            return;
+        }
+
+        let active = self.active_transition.as_ref().unwrap();
+        let shader_path = String::from(active.transition.shader_path());
+
+        if !self.transition_shaders.contains_key(&shader_path) {
+            match gfx::ShaderProgram::from_source(
+                command_buffer.device(),
+                "assets/shaders/fullscreen.vs".to_string(),
+                shader_path.clone(),
+            ) {
+                Ok(shader) => {
+                    self.transition_shaders.insert(shader_path.clone(), shader);
+                }
+                Err(_) => {
+                    println!("[GameStateManager] Could not create transition shader '{}', cutting instead", shader_path);
+                    self.finish_transition();
+                    draw_stack(&mut self.states, &self.stack, command_buffer, alpha);
+                    return;
+                }
             }
         }
+
+        let device = command_buffer.device();
+        let target_matches = |target: &Option<gfx::Texture2D>| match target {
+            Some(texture) => {
+                let dimensions = texture.dimensions();
+                dimensions.x as u32 == width && dimensions.y as u32 == height
+            }
+            None => false,
+        };
+
+        if !target_matches(&self.from_target) || !target_matches(&self.to_target) {
+            self.from_target = Some(gfx::Texture2D::empty(device, width as u16, height as u16));
+            self.to_target = Some(gfx::Texture2D::empty(device, width as u16, height as u16));
+        }
+
+        let from_target = self.from_target.as_ref().unwrap();
+        let to_target = self.to_target.as_ref().unwrap();
+
+        {
+            let mut offscreen = command_buffer.offscreen(from_target);
+            draw_stack(&mut self.states, &active.outgoing, &mut offscreen, alpha);
+        }
+
+        {
+            let mut offscreen = command_buffer.offscreen(to_target);
+            draw_stack(&mut self.states, &self.stack, &mut offscreen, alpha);
+        }
+
+        let color = active.transition.color();
+        let progress = active.transition.progress(active.timer);
+
+        command_buffer.opaque_blend();
+        command_buffer.set_blend_color(color.x, color.y, color.z, progress);
+        command_buffer.fullscreen_pass(
+            &mut self.transition_camera,
+            self.transition_shaders.get_mut(&shader_path).unwrap(),
+            &vec![from_target, to_target],
+        );
+        command_buffer.set_blend_color(1.0, 1.0, 1.0, 1.0);
+        command_buffer.alpha_blend();
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn current_state(&self) -> Option<K> {
+        self.stack.last().cloned()
     }
 
     //---------------------------------------------------------------------------------------------------
-    pub fn get_current_state(&mut self) -> Option<&mut GameState> {
-        if !self.current_state.is_empty() {
-            match self.states.get_mut(&self.current_state) {
+    // None if the state for key isn't an S
+    pub fn state<S: GameState<K> + 'static>(&self, key: K) -> Option<&S> {
+        self.states.get(&key)?.as_ref().as_any().downcast_ref::<S>()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn stack_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn get_current_state(&mut self) -> Option<&mut dyn GameState<K>> {
+        match self.stack.last() {
+            Some(key) => match self.states.get_mut(key) {
                 Some(s) => Some(s.as_mut()),
                 None => None,
+            },
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    crate::game_state_keys!(TestKey { Game, Pause, Overlay });
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    // Writes everything that happens to it into a log shared by all the states
+    struct StubState {
+        key: TestKey,
+        log: Log,
+        passes_through: bool,
+        // Asks to quit on this update, counting from one
+        quit_on_update: Option<u32>,
+        updates: u32,
+    }
+
+    impl StubState {
+        fn record(&self, event: &str) {
+            self.log.borrow_mut().push(format!("{:?} {}", self.key, event));
+        }
+    }
+
+    impl GameState<TestKey> for StubState {
+        fn on_enter(&mut self) {
+            self.record("enter");
+        }
+
+        fn on_leave(&mut self) {
+            self.record("leave");
+        }
+
+        fn update(&mut self, _dt: f32, _window: &snuff::core::Window) -> Option<StateTransition<TestKey>> {
+            self.record("update");
+            self.updates += 1;
+
+            match self.quit_on_update {
+                Some(update) if update == self.updates => Some(StateTransition::Quit),
+                _ => None,
             }
-        } else {
-            None
         }
+
+        fn draw(&mut self, _command_buffer: &mut snuff::gfx::CommandBuffer, _alpha: f32) {
+            self.record("draw");
+        }
+
+        fn on_pause(&mut self) {
+            self.record("pause");
+        }
+
+        fn on_resume(&mut self) {
+            self.record("resume");
+        }
+
+        fn draws_state_below(&self) -> bool {
+            self.passes_through
+        }
+
+        fn updates_state_below(&self) -> bool {
+            self.passes_through
+        }
+    }
+
+    // The overlay lets the states below it keep going, the pause menu doesn't
+    fn stub_state(key: TestKey, log: &Log) -> Box<dyn GameState<TestKey>> {
+        Box::new(StubState {
+            key,
+            log: log.clone(),
+            passes_through: key == TestKey::Overlay,
+            quit_on_update: None,
+            updates: 0,
+        })
+    }
+
+    fn manager(log: &Log) -> GameStateManager<TestKey> {
+        let mut manager = GameStateManager::new();
+        manager.register_states(|key| stub_state(key, log));
+        manager.switch(TestKey::Game);
+        log.borrow_mut().clear();
+
+        manager
+    }
+
+    fn take(log: &Log) -> Vec<String> {
+        log.borrow_mut().drain(..).collect()
+    }
+
+    #[test]
+    fn pushing_pauses_and_popping_resumes() {
+        let log = Log::default();
+        let mut manager = manager(&log);
+
+        manager.push(TestKey::Pause);
+        assert_eq!(take(&log), vec!["Game pause", "Pause enter"]);
+        assert_eq!(manager.stack_depth(), 2);
+
+        manager.pop();
+        assert_eq!(take(&log), vec!["Pause leave", "Game resume"]);
+        assert_eq!(manager.current_state(), Some(TestKey::Game));
+    }
+
+    #[test]
+    fn overlays_can_draw_and_update_the_state_below() {
+        let log = Log::default();
+        let mut manager = manager(&log);
+        let mut window = snuff::core::Window::headless(64, 64);
+
+        manager.push(TestKey::Overlay);
+        take(&log);
+
+        manager.update(1.0, 1.0, &window);
+        manager.draw(&mut window.begin_frame(0.0), 1.0);
+        assert_eq!(take(&log), vec!["Game update", "Overlay update", "Game draw", "Overlay draw"]);
+    }
+
+    #[test]
+    fn other_states_hide_the_state_below() {
+        let log = Log::default();
+        let mut manager = manager(&log);
+        let mut window = snuff::core::Window::headless(64, 64);
+
+        manager.push(TestKey::Overlay);
+        manager.push(TestKey::Pause);
+        take(&log);
+
+        manager.update(1.0, 1.0, &window);
+        manager.draw(&mut window.begin_frame(0.0), 1.0);
+        assert_eq!(take(&log), vec!["Pause update", "Pause draw"]);
+    }
+
+    #[test]
+    fn pushing_a_state_that_is_already_on_the_stack_is_refused() {
+        let log = Log::default();
+        let mut manager = manager(&log);
+
+        manager.push(TestKey::Pause);
+        take(&log);
+        manager.push(TestKey::Game);
+
+        assert!(take(&log).is_empty());
+        assert_eq!(manager.stack_depth(), 2);
+        assert_eq!(manager.current_state(), Some(TestKey::Pause));
+    }
+
+    #[test]
+    fn popping_the_last_state_is_refused() {
+        let log = Log::default();
+        let mut manager = manager(&log);
+
+        manager.pop();
+
+        assert!(take(&log).is_empty());
+        assert_eq!(manager.stack_depth(), 1);
+        assert_eq!(manager.current_state(), Some(TestKey::Game));
+    }
+
+    #[test]
+    fn replacing_leaves_the_states_below_alone() {
+        let log = Log::default();
+        let mut manager = manager(&log);
+
+        manager.push(TestKey::Pause);
+        take(&log);
+        manager.apply(StateTransition::Replace(TestKey::Overlay));
+
+        assert_eq!(take(&log), vec!["Pause leave", "Overlay enter"]);
+        assert_eq!(manager.stack_depth(), 2);
+        assert_eq!(manager.current_state(), Some(TestKey::Overlay));
+    }
+
+    #[test]
+    fn replacing_with_a_state_below_is_refused() {
+        let log = Log::default();
+        let mut manager = manager(&log);
+
+        manager.push(TestKey::Pause);
+        take(&log);
+        manager.apply(StateTransition::Replace(TestKey::Game));
+
+        assert!(take(&log).is_empty());
+        assert_eq!(manager.current_state(), Some(TestKey::Pause));
+    }
+
+    #[test]
+    fn quitting_stops_the_game_loop() {
+        let log = Log::default();
+
+        let mut game_loop = snuff::core::GameLoop::<TestKey>::headless(64, 64);
+        game_loop.register_states(|key, _window, _audio_device| {
+            Box::new(StubState {
+                key,
+                log: log.clone(),
+                passes_through: false,
+                quit_on_update: if key == TestKey::Game { Some(3) } else { None },
+                updates: 0,
+            })
+        });
+        game_loop.game_state_manager().switch(TestKey::Game);
+        game_loop.exec_headless(10, &snuff::core::InputScript::new());
+
+        assert_eq!(game_loop.tick_count(), 3);
+        assert_eq!(game_loop.game_state_manager().current_state(), Some(TestKey::Game));
+        assert!(!log.borrow().contains(&String::from("Game leave")));
+    }
+
+    #[test]
+    fn transitions_run_on_scaled_time() {
+        let log = Log::default();
+        let mut manager = manager(&log);
+        let window = snuff::core::Window::headless(64, 64);
+
+        manager.switch_with(TestKey::Pause, core::Transition::cross_dissolve(1.0).with_easing(core::easing::in_quad));
+        manager.update(0.5, 10.0, &window);
+
+        let active = manager.active_transition.as_ref().unwrap();
+        assert_eq!(active.timer, 0.5);
+        assert_eq!(active.transition.progress(active.timer), 0.25);
+
+        manager.update(0.5, 0.0, &window);
+        assert!(!manager.is_transitioning());
+        assert!(log.borrow().contains(&String::from("Game leave")));
     }
 }
@@ -0,0 +1,395 @@
+use crate::snuff::core;
+use crate::snuff::core::KeyState;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// How far a stick or trigger has to be pushed for it to count as a button, after the deadzone
+pub const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub struct GamepadId(pub u32);
+
+// Named after where the buttons are, so it doesn't matter whose labels are printed on them
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    Select,
+    Start,
+    Guide,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    Other(u8),
+}
+
+// Sticks go from -1 to 1 with up and right positive, triggers go from 0 to 1
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+    Other(u8),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Negative,
+    Positive,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+}
+
+pub struct Gamepad {
+    id: GamepadId,
+    name: String,
+    deadzone: f32,
+    buttons: HashMap<GamepadButton, KeyState>,
+    axes: HashMap<GamepadAxis, f32>,
+    old_axes: HashMap<GamepadAxis, f32>,
+}
+
+impl GamepadAxis {
+    //---------------------------------------------------------------------------------------------------
+    // The other half of the stick, the deadzone is applied to both at once
+    pub fn stick_partner(&self) -> Option<GamepadAxis> {
+        match self {
+            GamepadAxis::LeftX => Some(GamepadAxis::LeftY),
+            GamepadAxis::LeftY => Some(GamepadAxis::LeftX),
+            GamepadAxis::RightX => Some(GamepadAxis::RightY),
+            GamepadAxis::RightY => Some(GamepadAxis::RightX),
+            _ => None,
+        }
+    }
+}
+
+impl AxisDirection {
+    //---------------------------------------------------------------------------------------------------
+    // How far a value points this way, between 0 and 1
+    pub fn amount(&self, value: f32) -> f32 {
+        match self {
+            AxisDirection::Negative => (-value).max(0.0),
+            AxisDirection::Positive => value.max(0.0),
+        }
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+// Rescales what's left outside the deadzone back to 0..1, so the stick doesn't jump once it's past it
+fn apply_deadzone(magnitude: f32, deadzone: f32) -> f32 {
+    if magnitude <= deadzone || deadzone >= 1.0 {
+        return 0.0;
+    }
+
+    ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0)
+}
+
+impl Gamepad {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(id: GamepadId, name: &str, deadzone: f32) -> Gamepad {
+        Gamepad {
+            id,
+            name: String::from(name),
+            deadzone,
+            buttons: HashMap::new(),
+            axes: HashMap::new(),
+            old_axes: HashMap::new(),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn id(&self) -> GamepadId {
+        self.id
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_deadzone(&mut self, deadzone: f32) -> &mut Gamepad {
+        self.deadzone = deadzone.clamp(0.0, 1.0);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn deadzone(&self) -> f32 {
+        self.deadzone
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn set_button(&mut self, button: GamepadButton, pressed: bool) -> bool {
+        let old_state = self.buttons.get(&button).cloned();
+        let new_state = KeyState::after(old_state, pressed);
+        self.buttons.insert(button, new_state);
+
+        new_state == KeyState::Pressed && old_state != Some(KeyState::Pressed)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.axes.insert(axis, value.clamp(-1.0, 1.0));
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn reset_states(&mut self) {
+        for state in self.buttons.values_mut() {
+            *state = match *state {
+                KeyState::Pressed => KeyState::Down,
+                KeyState::Released => KeyState::Up,
+                old_state => old_state,
+            };
+        }
+
+        self.old_axes = self.axes.clone();
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Everything held lets go, so nothing stays stuck down after a disconnect
+    pub(crate) fn release_all(&mut self) {
+        for state in self.buttons.values_mut() {
+            if *state == KeyState::Down || *state == KeyState::Pressed {
+                *state = KeyState::Released;
+            }
+        }
+
+        for value in self.axes.values_mut() {
+            *value = 0.0;
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_button_pressed(&self, button: GamepadButton) -> bool {
+        self.buttons.get(&button) == Some(&KeyState::Pressed)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_button_released(&self, button: GamepadButton) -> bool {
+        self.buttons.get(&button) == Some(&KeyState::Released)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_button_down(&self, button: GamepadButton) -> bool {
+        match self.buttons.get(&button) {
+            Some(state) => *state == KeyState::Down || *state == KeyState::Pressed,
+            None => false,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn raw_axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes.get(&axis).cloned().unwrap_or(0.0)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn axis_from(&self, axes: &HashMap<GamepadAxis, f32>, axis: GamepadAxis) -> f32 {
+        let value = axes.get(&axis).cloned().unwrap_or(0.0);
+
+        // Sticks use a round deadzone, a square one would snap diagonals to the axes
+        let magnitude = match axis.stick_partner() {
+            Some(partner) => {
+                let other = axes.get(&partner).cloned().unwrap_or(0.0);
+                (value * value + other * other).sqrt()
+            }
+            None => value.abs(),
+        };
+
+        if magnitude <= 0.0 {
+            return 0.0;
+        }
+
+        value / magnitude * apply_deadzone(magnitude, self.deadzone)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.axis_from(&self.axes, axis)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn stick(&self, x: GamepadAxis, y: GamepadAxis) -> nalgebra_glm::Vec2 {
+        nalgebra_glm::vec2(self.axis(x), self.axis(y))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Axes pushed past the threshold behave like buttons, so they can be bound to actions
+    pub fn is_axis_down(&self, axis: GamepadAxis, direction: AxisDirection) -> bool {
+        direction.amount(self.axis(axis)) >= AXIS_PRESS_THRESHOLD
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn was_axis_down(&self, axis: GamepadAxis, direction: AxisDirection) -> bool {
+        direction.amount(self.axis_from(&self.old_axes, axis)) >= AXIS_PRESS_THRESHOLD
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_axis_pressed(&self, axis: GamepadAxis, direction: AxisDirection) -> bool {
+        self.is_axis_down(axis, direction) && !self.was_axis_down(axis, direction)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_axis_released(&self, axis: GamepadAxis, direction: AxisDirection) -> bool {
+        !self.is_axis_down(axis, direction) && self.was_axis_down(axis, direction)
+    }
+}
+
+// Stands in for a real gamepad. Its events go through the same path as hardware input,
+// either injected into the window right away or scheduled with an input script
+pub struct VirtualGamepad {
+    id: GamepadId,
+}
+
+impl VirtualGamepad {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(id: GamepadId) -> VirtualGamepad {
+        VirtualGamepad { id }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn id(&self) -> GamepadId {
+        self.id
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn connect(&self) -> core::InputEvent {
+        core::InputEvent::GamepadConnected(self.id, true)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn disconnect(&self) -> core::InputEvent {
+        core::InputEvent::GamepadConnected(self.id, false)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn press(&self, button: GamepadButton) -> core::InputEvent {
+        core::InputEvent::GamepadButton(self.id, button, true)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn release(&self, button: GamepadButton) -> core::InputEvent {
+        core::InputEvent::GamepadButton(self.id, button, false)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn move_axis(&self, axis: GamepadAxis, value: f32) -> core::InputEvent {
+        core::InputEvent::GamepadAxis(self.id, axis, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gamepad(deadzone: f32) -> Gamepad {
+        Gamepad::new(GamepadId(0), "Test", deadzone)
+    }
+
+    #[test]
+    fn deadzone_swallows_small_movements() {
+        let mut pad = gamepad(0.2);
+        pad.set_axis(GamepadAxis::LeftX, 0.2);
+        pad.set_axis(GamepadAxis::LeftTrigger, -0.1);
+
+        assert_eq!(pad.axis(GamepadAxis::LeftX), 0.0);
+        assert_eq!(pad.axis(GamepadAxis::LeftTrigger), 0.0);
+        assert_eq!(pad.raw_axis(GamepadAxis::LeftX), 0.2);
+    }
+
+    #[test]
+    fn deadzone_rescales_what_is_left() {
+        let mut pad = gamepad(0.2);
+        pad.set_axis(GamepadAxis::RightTrigger, 0.6);
+        pad.set_axis(GamepadAxis::LeftX, -1.0);
+
+        assert!((pad.axis(GamepadAxis::RightTrigger) - 0.5).abs() < 1e-6);
+        assert_eq!(pad.axis(GamepadAxis::LeftX), -1.0);
+    }
+
+    #[test]
+    fn sticks_use_a_round_deadzone() {
+        let mut pad = gamepad(0.2);
+        pad.set_axis(GamepadAxis::LeftX, 0.15);
+        pad.set_axis(GamepadAxis::LeftY, 0.15);
+
+        // Each half is inside the deadzone, together they're past it
+        assert!(pad.axis(GamepadAxis::LeftX) > 0.0);
+        assert!((pad.axis(GamepadAxis::LeftX) - pad.axis(GamepadAxis::LeftY)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn full_deadzone_turns_the_axes_off() {
+        let mut pad = gamepad(0.0);
+        pad.set_deadzone(2.0).set_axis(GamepadAxis::LeftX, 1.0);
+
+        assert_eq!(pad.deadzone(), 1.0);
+        assert_eq!(pad.axis(GamepadAxis::LeftX), 0.0);
+    }
+
+    #[test]
+    fn axes_count_as_down_from_the_threshold() {
+        let mut pad = gamepad(0.0);
+        pad.set_axis(GamepadAxis::LeftX, AXIS_PRESS_THRESHOLD - 0.01);
+        assert!(!pad.is_axis_down(GamepadAxis::LeftX, AxisDirection::Positive));
+
+        pad.set_axis(GamepadAxis::LeftX, AXIS_PRESS_THRESHOLD);
+        assert!(pad.is_axis_down(GamepadAxis::LeftX, AxisDirection::Positive));
+        assert!(!pad.is_axis_down(GamepadAxis::LeftX, AxisDirection::Negative));
+    }
+
+    #[test]
+    fn axis_presses_last_one_frame() {
+        let mut pad = gamepad(0.0);
+        pad.set_axis(GamepadAxis::LeftY, -0.8);
+        assert!(pad.is_axis_pressed(GamepadAxis::LeftY, AxisDirection::Negative));
+
+        pad.reset_states();
+        assert!(!pad.is_axis_pressed(GamepadAxis::LeftY, AxisDirection::Negative));
+        assert!(pad.is_axis_down(GamepadAxis::LeftY, AxisDirection::Negative));
+
+        pad.set_axis(GamepadAxis::LeftY, -0.3);
+        assert!(pad.is_axis_released(GamepadAxis::LeftY, AxisDirection::Negative));
+
+        pad.reset_states();
+        assert!(!pad.is_axis_released(GamepadAxis::LeftY, AxisDirection::Negative));
+    }
+
+    #[test]
+    fn flicking_across_is_a_release_and_a_press() {
+        let mut pad = gamepad(0.0);
+        pad.set_axis(GamepadAxis::RightX, 1.0);
+        pad.reset_states();
+        pad.set_axis(GamepadAxis::RightX, -1.0);
+
+        assert!(pad.is_axis_released(GamepadAxis::RightX, AxisDirection::Positive));
+        assert!(pad.is_axis_pressed(GamepadAxis::RightX, AxisDirection::Negative));
+    }
+
+    #[test]
+    fn disconnecting_releases_everything() {
+        let mut pad = gamepad(0.0);
+        pad.set_button(GamepadButton::South, true);
+        pad.set_axis(GamepadAxis::LeftX, 1.0);
+        pad.reset_states();
+        pad.release_all();
+
+        assert!(pad.is_button_released(GamepadButton::South));
+        assert!(pad.is_axis_released(GamepadAxis::LeftX, AxisDirection::Positive));
+    }
+}
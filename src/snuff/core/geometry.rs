@@ -0,0 +1,95 @@
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rect {
+    pub min: nalgebra_glm::Vec2,
+    pub max: nalgebra_glm::Vec2,
+}
+
+impl Rect {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(a: &nalgebra_glm::Vec2, b: &nalgebra_glm::Vec2) -> Rect {
+        Rect {
+            min: nalgebra_glm::min2(a, b),
+            max: nalgebra_glm::max2(a, b),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn from_center(center: &nalgebra_glm::Vec2, size: &nalgebra_glm::Vec2) -> Rect {
+        let half_size = nalgebra_glm::abs(size) * 0.5;
+        Rect::new(&(center - half_size), &(center + half_size))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The smallest rect around all points, None if there are no points
+    pub fn from_points<I: IntoIterator<Item = nalgebra_glm::Vec2>>(points: I) -> Option<Rect> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        Some(points.fold(Rect::new(&first, &first), |rect, point| Rect {
+            min: nalgebra_glm::min2(&rect.min, &point),
+            max: nalgebra_glm::max2(&rect.max, &point),
+        }))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn center(&self) -> nalgebra_glm::Vec2 {
+        (self.min + self.max) * 0.5
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn size(&self) -> nalgebra_glm::Vec2 {
+        self.max - self.min
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn contains(&self, point: &nalgebra_glm::Vec2) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x && point.y >= self.min.y && point.y <= self.max.y
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x && self.min.y <= other.max.y && self.max.y >= other.min.y
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Ray {
+    pub origin: nalgebra_glm::Vec3,
+    pub direction: nalgebra_glm::Vec3,
+}
+
+impl Ray {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(origin: &nalgebra_glm::Vec3, direction: &nalgebra_glm::Vec3) -> Ray {
+        Ray {
+            origin: *origin,
+            direction: nalgebra_glm::normalize(direction),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn point_at(&self, distance: f32) -> nalgebra_glm::Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Where the ray crosses the plane at the given z, sprites are drawn at z = 0
+    pub fn intersect_plane_z(&self, z: f32) -> Option<nalgebra_glm::Vec3> {
+        if self.direction.z.abs() < 1e-6 {
+            return None;
+        }
+
+        let distance = (z - self.origin.z) / self.direction.z;
+        if distance < 0.0 {
+            return None;
+        }
+
+        Some(self.point_at(distance))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn intersect_rect(&self, rect: &Rect, z: f32) -> Option<nalgebra_glm::Vec3> {
+        self.intersect_plane_z(z)
+            .filter(|point| rect.contains(&nalgebra_glm::vec2(point.x, point.y)))
+    }
+}
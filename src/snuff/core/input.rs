@@ -0,0 +1,374 @@
+use crate::snuff::core;
+
+use glium::glutin::{MouseButton, VirtualKeyCode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    // Any connected gamepad
+    GamepadButton(core::GamepadButton),
+    GamepadAxis(core::GamepadAxis, core::AxisDirection),
+}
+
+// Pushes an axis towards -1 while negative is down, and towards 1 while positive is down
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct AxisBinding {
+    pub negative: Binding,
+    pub positive: Binding,
+}
+
+struct Rebind {
+    action: String,
+    replace: bool,
+}
+
+// Named actions and axes, so game code doesn't have to know which keys the player picked
+pub struct Input {
+    actions: BTreeMap<String, Vec<Binding>>,
+    axes: BTreeMap<String, Vec<AxisBinding>>,
+    rebind: Option<Rebind>,
+    rebound: Option<(String, Binding)>,
+    // Whatever finished a rebind shouldn't also trigger its new action, until it's let go
+    held_from_rebind: Vec<Binding>,
+}
+
+impl Binding {
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_down(&self, window: &core::Window) -> bool {
+        match self {
+            Binding::Key(key) => window.is_key_down(*key),
+            Binding::Mouse(button) => window.is_mouse_button_down(*button),
+            Binding::GamepadButton(button) => window.is_gamepad_button_down(*button),
+            Binding::GamepadAxis(axis, direction) => window.is_gamepad_axis_down(*axis, *direction),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_pressed(&self, window: &core::Window) -> bool {
+        match self {
+            Binding::Key(key) => window.is_key_pressed(*key),
+            Binding::Mouse(button) => window.is_mouse_button_pressed(*button),
+            Binding::GamepadButton(button) => window.is_gamepad_button_pressed(*button),
+            Binding::GamepadAxis(axis, direction) => window.is_gamepad_axis_pressed(*axis, *direction),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_released(&self, window: &core::Window) -> bool {
+        match self {
+            Binding::Key(key) => window.is_key_released(*key),
+            Binding::Mouse(button) => window.is_mouse_button_released(*button),
+            Binding::GamepadButton(button) => window.is_gamepad_button_released(*button),
+            Binding::GamepadAxis(axis, direction) => window.is_gamepad_axis_released(*axis, *direction),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Between 0 and 1, only gamepad axes are ever in between
+    pub fn value(&self, window: &core::Window) -> f32 {
+        match self {
+            Binding::GamepadAxis(axis, direction) => direction.amount(window.gamepad_axis(*axis)),
+            _ => {
+                if self.is_down(window) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+impl AxisBinding {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(negative: Binding, positive: Binding) -> AxisBinding {
+        AxisBinding { negative, positive }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn keys(negative: VirtualKeyCode, positive: VirtualKeyCode) -> AxisBinding {
+        AxisBinding::new(Binding::Key(negative), Binding::Key(positive))
+    }
+}
+
+impl Input {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> Input {
+        Input {
+            actions: BTreeMap::new(),
+            axes: BTreeMap::new(),
+            rebind: None,
+            rebound: None,
+            held_from_rebind: Vec::new(),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn from_config(config: &core::Config) -> Input {
+        let mut input = Input::new();
+        input.actions = config.bindings.clone();
+        input.axes = config.axes.clone();
+
+        input
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Writes the current bindings back, saving the config is up to the caller
+    pub fn save(&self, config: &mut core::Config) {
+        config.bindings = self.actions.clone();
+        config.axes = self.axes.clone();
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn bind(&mut self, action: &str, binding: Binding) -> &mut Input {
+        let bindings = self.actions.entry(String::from(action)).or_default();
+
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn unbind(&mut self, action: &str, binding: Binding) -> &mut Input {
+        if let Some(bindings) = self.actions.get_mut(action) {
+            bindings.retain(|b| *b != binding);
+        }
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_bindings(&mut self, action: &str, bindings: &[Binding]) -> &mut Input {
+        self.actions.insert(String::from(action), bindings.to_vec());
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        match self.actions.get(action) {
+            Some(bindings) => bindings,
+            None => &[],
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn action_names(&self) -> Vec<&str> {
+        self.actions.keys().map(|name| &name[..]).collect()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) -> &mut Input {
+        let bindings = self.axes.entry(String::from(axis)).or_default();
+
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_axis_bindings(&mut self, axis: &str, bindings: &[AxisBinding]) -> &mut Input {
+        self.axes.insert(String::from(axis), bindings.to_vec());
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn axis_bindings(&self, axis: &str) -> &[AxisBinding] {
+        match self.axes.get(axis) {
+            Some(bindings) => bindings,
+            None => &[],
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn is_held_from_rebind(&self, binding: &Binding) -> bool {
+        self.held_from_rebind.contains(binding)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn any<F: Fn(&Binding) -> bool>(&self, action: &str, check: F) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| !self.is_held_from_rebind(binding) && check(binding))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Unknown actions are never down, so a game can query actions the user hasn't bound
+    pub fn is_down(&self, window: &core::Window, action: &str) -> bool {
+        self.any(action, |binding| binding.is_down(window))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_pressed(&self, window: &core::Window, action: &str) -> bool {
+        self.any(action, |binding| binding.is_pressed(window))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_released(&self, window: &core::Window, action: &str) -> bool {
+        self.any(action, |binding| binding.is_released(window))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Between -1 and 1, every binding adds to it
+    pub fn axis(&self, window: &core::Window, axis: &str) -> f32 {
+        let value: f32 = self
+            .axis_bindings(axis)
+            .iter()
+            .map(|binding| {
+                let value = |b: &Binding| if self.is_held_from_rebind(b) { 0.0 } else { b.value(window) };
+
+                value(&binding.positive) - value(&binding.negative)
+            })
+            .sum();
+
+        value.clamp(-1.0, 1.0)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The next key, mouse button or gamepad input pressed gets bound to the action, escape cancels.
+    // Replacing throws out the action's old bindings, otherwise the new one is added to them
+    pub fn start_rebind(&mut self, action: &str, replace: bool) {
+        self.rebind = Some(Rebind {
+            action: String::from(action),
+            replace,
+        });
+        self.rebound = None;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn cancel_rebind(&mut self) {
+        self.rebind = None;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn rebinding(&self) -> Option<&str> {
+        self.rebind.as_ref().map(|rebind| &rebind.action[..])
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The action and binding the last rebind ended up with, only returned once
+    pub fn take_rebound(&mut self) -> Option<(String, Binding)> {
+        self.rebound.take()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Called by the window for every new press, returns whether a rebind used it
+    pub(crate) fn capture(&mut self, binding: Binding) -> bool {
+        let rebind = match self.rebind.take() {
+            Some(rebind) => rebind,
+            None => return false,
+        };
+
+        self.held_from_rebind.push(binding);
+
+        if binding == Binding::Key(VirtualKeyCode::Escape) {
+            println!("[Input] Cancelled rebinding '{}'", rebind.action);
+            return true;
+        }
+
+        if rebind.replace {
+            self.set_bindings(&rebind.action, &[binding]);
+        } else {
+            self.bind(&rebind.action, binding);
+        }
+
+        println!("[Input] Bound {:?} to '{}'", binding, rebind.action);
+        self.rebound = Some((rebind.action, binding));
+
+        true
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn release_held(&mut self, window: &core::Window) {
+        self.held_from_rebind.retain(|binding| binding.is_down(window));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window_with_hop() -> core::Window {
+        let mut input = Input::new();
+        input.bind("hop", Binding::Key(VirtualKeyCode::Space));
+
+        let mut window = core::Window::headless(64, 64);
+        window.set_input(input);
+
+        window
+    }
+
+    fn press(window: &mut core::Window, key: VirtualKeyCode, pressed: bool) {
+        window.inject(core::InputEvent::Key(key, pressed));
+    }
+
+    #[test]
+    fn captured_keys_replace_the_old_bindings() {
+        let mut window = window_with_hop();
+        window.input_mut().start_rebind("hop", true);
+        assert_eq!(window.input().rebinding(), Some("hop"));
+
+        press(&mut window, VirtualKeyCode::W, true);
+
+        let mut input = window.input_mut();
+        assert_eq!(input.bindings("hop"), &[Binding::Key(VirtualKeyCode::W)]);
+        assert_eq!(input.take_rebound(), Some((String::from("hop"), Binding::Key(VirtualKeyCode::W))));
+        assert_eq!(input.take_rebound(), None);
+        assert_eq!(input.rebinding(), None);
+    }
+
+    #[test]
+    fn captured_keys_can_be_added_instead() {
+        let mut window = window_with_hop();
+        window.input_mut().start_rebind("hop", false);
+
+        press(&mut window, VirtualKeyCode::W, true);
+
+        assert_eq!(
+            window.input().bindings("hop"),
+            &[Binding::Key(VirtualKeyCode::Space), Binding::Key(VirtualKeyCode::W)]
+        );
+    }
+
+    #[test]
+    fn escape_cancels_a_rebind() {
+        let mut window = window_with_hop();
+        window.input_mut().start_rebind("hop", true);
+
+        press(&mut window, VirtualKeyCode::Escape, true);
+
+        let mut input = window.input_mut();
+        assert_eq!(input.bindings("hop"), &[Binding::Key(VirtualKeyCode::Space)]);
+        assert_eq!(input.take_rebound(), None);
+        assert_eq!(input.rebinding(), None);
+    }
+
+    #[test]
+    fn the_captured_key_only_works_once_it_has_been_let_go() {
+        let mut window = window_with_hop();
+        window.input_mut().start_rebind("hop", true);
+
+        press(&mut window, VirtualKeyCode::W, true);
+        assert!(!window.is_action_pressed("hop"));
+
+        window.reset_input_states();
+        assert!(window.is_key_down(VirtualKeyCode::W));
+        assert!(!window.is_action_down("hop"));
+
+        press(&mut window, VirtualKeyCode::W, false);
+        assert!(!window.is_action_released("hop"));
+
+        window.reset_input_states();
+        press(&mut window, VirtualKeyCode::W, true);
+        assert!(window.is_action_pressed("hop"));
+        assert!(window.is_action_down("hop"));
+    }
+}
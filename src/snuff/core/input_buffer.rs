@@ -0,0 +1,185 @@
+use crate::snuff::core;
+
+struct BufferedAction {
+    action: String,
+    remaining: f32,
+}
+
+// Remembers actions for a short while after they're pressed, so one that can't happen yet
+// (e.g. a jump just before landing) still happens once it can
+pub struct InputBuffer {
+    duration: f32,
+    // Oldest first
+    buffered: Vec<BufferedAction>,
+}
+
+impl InputBuffer {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(duration: f32) -> InputBuffer {
+        InputBuffer {
+            duration: duration.max(0.0),
+            buffered: Vec::new(),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Only affects actions buffered from now on
+    pub fn set_duration(&mut self, duration: f32) -> &mut InputBuffer {
+        self.duration = duration.max(0.0);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Pressing an action again restarts its window
+    pub fn buffer(&mut self, action: &str) {
+        self.buffered.retain(|buffered| buffered.action != action);
+        self.buffered.push(BufferedAction {
+            action: String::from(action),
+            remaining: self.duration,
+        });
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Buffers every action that was pressed this tick
+    pub fn buffer_pressed(&mut self, window: &core::Window, actions: &[&str]) {
+        for action in actions {
+            if window.is_action_pressed(action) {
+                self.buffer(action);
+            }
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Ages everything by dt, an action is still usable on the tick its window runs out
+    pub fn update(&mut self, dt: f32) {
+        for buffered in self.buffered.iter_mut() {
+            buffered.remaining -= dt;
+        }
+
+        self.buffered.retain(|buffered| buffered.remaining >= 0.0);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_buffered(&self, action: &str) -> bool {
+        self.buffered.iter().any(|buffered| buffered.action == action)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Returns whether the action was buffered, and forgets it so it only happens once
+    pub fn consume(&mut self, action: &str) -> bool {
+        let count = self.buffered.len();
+        self.buffered.retain(|buffered| buffered.action != action);
+
+        self.buffered.len() != count
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The most recently pressed of the actions, when they're mutually exclusive
+    pub fn consume_latest<'a>(&mut self, actions: &[&'a str]) -> Option<&'a str> {
+        let latest = self
+            .buffered
+            .iter()
+            .rev()
+            .find_map(|buffered| actions.iter().find(|action| **action == buffered.action))
+            .cloned()?;
+
+        self.consume(latest);
+        Some(latest)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn clear(&mut self) {
+        self.buffered.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_last_for_the_duration() {
+        let mut buffer = InputBuffer::new(0.15);
+        buffer.buffer("hop");
+
+        buffer.update(0.1);
+        assert!(buffer.is_buffered("hop"));
+
+        buffer.update(0.05);
+        assert!(buffer.is_buffered("hop"));
+
+        buffer.update(0.01);
+        assert!(!buffer.is_buffered("hop"));
+    }
+
+    #[test]
+    fn zero_duration_lasts_the_tick_it_was_pressed() {
+        let mut buffer = InputBuffer::new(-1.0);
+        buffer.buffer("hop");
+
+        assert_eq!(buffer.duration(), 0.0);
+        assert!(buffer.is_buffered("hop"));
+
+        buffer.update(1.0 / 60.0);
+        assert!(!buffer.is_buffered("hop"));
+    }
+
+    #[test]
+    fn pressing_again_restarts_the_window() {
+        let mut buffer = InputBuffer::new(0.1);
+        buffer.buffer("hop");
+        buffer.update(0.08);
+        buffer.buffer("hop");
+        buffer.update(0.08);
+
+        assert!(buffer.is_buffered("hop"));
+    }
+
+    #[test]
+    fn new_durations_only_apply_to_new_presses() {
+        let mut buffer = InputBuffer::new(0.1);
+        buffer.buffer("left");
+        buffer.set_duration(0.5).buffer("right");
+        buffer.update(0.2);
+
+        assert!(!buffer.is_buffered("left"));
+        assert!(buffer.is_buffered("right"));
+    }
+
+    #[test]
+    fn consuming_only_happens_once() {
+        let mut buffer = InputBuffer::new(0.1);
+        buffer.buffer("hop");
+
+        assert!(buffer.consume("hop"));
+        assert!(!buffer.consume("hop"));
+    }
+
+    #[test]
+    fn latest_press_wins() {
+        let mut buffer = InputBuffer::new(0.1);
+        buffer.buffer("left");
+        buffer.buffer("right");
+        buffer.buffer("skip");
+
+        assert_eq!(buffer.consume_latest(&["left", "right"]), Some("right"));
+        assert_eq!(buffer.consume_latest(&["left", "right"]), Some("left"));
+        assert_eq!(buffer.consume_latest(&["left", "right"]), None);
+        assert!(buffer.is_buffered("skip"));
+    }
+
+    #[test]
+    fn expired_actions_cannot_be_consumed() {
+        let mut buffer = InputBuffer::new(0.1);
+        buffer.buffer("left");
+        buffer.update(0.2);
+
+        assert_eq!(buffer.consume_latest(&["left"]), None);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::snuff::core;
+
+use serde::{Deserialize, Serialize};
+
+const RECORDING_VERSION: u32 = 2;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InputFrame {
+    pub dt: f32,
+    pub events: Vec<core::InputEvent>,
+}
+
+// Input alone only replays the same run when the game starts out the same,
+// so the random seed and the state it started in are stored along with it
+#[derive(Serialize, Deserialize)]
+pub struct InputRecording {
+    version: u32,
+    seed: u64,
+    start_state: Option<String>,
+    frames: Vec<InputFrame>,
+}
+
+impl InputRecording {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new<K: core::GameStateKey>(seed: u64, start_state: Option<K>) -> InputRecording {
+        InputRecording {
+            version: RECORDING_VERSION,
+            seed,
+            start_state: start_state.map(|key| format!("{:?}", key)),
+            frames: Vec::new(),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn from_file(path: &str) -> std::io::Result<InputRecording> {
+        let contents = std::fs::read_to_string(path)?;
+
+        let recording: InputRecording = ron::from_str(&contents).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
+        })?;
+
+        if recording.version != RECORDING_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "unsupported recording version {}, expected {}",
+                    recording.version, RECORDING_VERSION
+                ),
+            ));
+        }
+
+        Ok(recording)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new().depth_limit(2))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+
+        std::fs::write(path, contents)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // None when the recording started before any state was entered, or the state no longer exists
+    pub fn start_state<K: core::GameStateKey>(&self) -> Option<K> {
+        K::from_name(self.start_state.as_ref()?)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn push_frame(&mut self, dt: f32, events: &[core::InputEvent]) {
+        self.frames.push(InputFrame {
+            dt,
+            events: events.to_vec(),
+        });
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn frame(&self, index: usize) -> Option<&InputFrame> {
+        self.frames.get(index)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
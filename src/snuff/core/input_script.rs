@@ -0,0 +1,150 @@
+use crate::snuff::core;
+
+use glium::glutin;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum InputEvent {
+    Key(glutin::VirtualKeyCode, bool),
+    MouseButton(glutin::MouseButton, bool),
+    MousePosition(f32, f32),
+    MouseScroll(f32),
+    Character(char),
+    GamepadConnected(core::GamepadId, bool),
+    GamepadButton(core::GamepadId, core::GamepadButton, bool),
+    GamepadAxis(core::GamepadId, core::GamepadAxis, f32),
+}
+
+// Stored as RON, a map from tick to the events that happen on it, e.g. {0: [Key(Right, true)]}
+#[derive(Serialize, Deserialize)]
+pub struct InputScript {
+    events: BTreeMap<u32, Vec<InputEvent>>,
+}
+
+impl InputScript {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> InputScript {
+        InputScript {
+            events: BTreeMap::new(),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn from_file(path: &str) -> std::io::Result<InputScript> {
+        let contents = std::fs::read_to_string(path)?;
+
+        ron::from_str(&contents)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new().depth_limit(3))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+
+        std::fs::write(path, contents)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn push(&mut self, tick: u32, event: InputEvent) -> &mut InputScript {
+        self.events.entry(tick).or_default().push(event);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn press_key(&mut self, tick: u32, key: glutin::VirtualKeyCode) -> &mut InputScript {
+        self.push(tick, InputEvent::Key(key, true))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn release_key(&mut self, tick: u32, key: glutin::VirtualKeyCode) -> &mut InputScript {
+        self.push(tick, InputEvent::Key(key, false))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn hold_key(
+        &mut self,
+        tick: u32,
+        duration: u32,
+        key: glutin::VirtualKeyCode,
+    ) -> &mut InputScript {
+        self.press_key(tick, key)
+            .release_key(tick + duration.max(1), key)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn tap_key(&mut self, tick: u32, key: glutin::VirtualKeyCode) -> &mut InputScript {
+        self.hold_key(tick, 1, key)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn type_text(&mut self, tick: u32, text: &str) -> &mut InputScript {
+        for character in text.chars() {
+            self.push(tick, InputEvent::Character(character));
+        }
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn connect_gamepad(&mut self, tick: u32, id: core::GamepadId) -> &mut InputScript {
+        self.push(tick, core::VirtualGamepad::new(id).connect())
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn disconnect_gamepad(&mut self, tick: u32, id: core::GamepadId) -> &mut InputScript {
+        self.push(tick, core::VirtualGamepad::new(id).disconnect())
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn hold_gamepad_button(
+        &mut self,
+        tick: u32,
+        duration: u32,
+        id: core::GamepadId,
+        button: core::GamepadButton,
+    ) -> &mut InputScript {
+        let gamepad = core::VirtualGamepad::new(id);
+
+        self.push(tick, gamepad.press(button))
+            .push(tick + duration.max(1), gamepad.release(button))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Holds an axis at value, then lets it spring back to the middle
+    pub fn hold_gamepad_axis(
+        &mut self,
+        tick: u32,
+        duration: u32,
+        id: core::GamepadId,
+        axis: core::GamepadAxis,
+        value: f32,
+    ) -> &mut InputScript {
+        let gamepad = core::VirtualGamepad::new(id);
+
+        self.push(tick, gamepad.move_axis(axis, value))
+            .push(tick + duration.max(1), gamepad.move_axis(axis, 0.0))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn last_tick(&self) -> u32 {
+        match self.events.keys().next_back() {
+            Some(tick) => *tick,
+            None => 0,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn apply(&self, tick: u32, window: &mut core::Window) {
+        let events = match self.events.get(&tick) {
+            Some(events) => events,
+            None => return,
+        };
+
+        for evt in events.iter() {
+            window.inject(*evt);
+        }
+    }
+}
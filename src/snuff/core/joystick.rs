@@ -0,0 +1,191 @@
+use crate::snuff::core;
+use crate::snuff::core::{GamepadAxis, GamepadButton, GamepadId};
+
+use std::time::{Duration, Instant};
+
+// Devices can be plugged in at any time, looking for them every frame would be wasteful
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_DEVICES: u32 = 8;
+
+// Reads gamepads through the Linux joystick API (/dev/input/js*), which needs nothing but the standard library.
+// Elsewhere no devices are ever found, games can still use virtual gamepads
+pub struct Joysticks {
+    devices: Vec<Device>,
+    last_scan: Option<Instant>,
+}
+
+#[cfg(target_os = "linux")]
+struct Device {
+    id: GamepadId,
+    file: std::fs::File,
+}
+
+#[cfg(not(target_os = "linux"))]
+struct Device {
+    id: GamepadId,
+}
+
+//---------------------------------------------------------------------------------------------------
+// The button and axis numbers used by the common Xbox-style driver mapping
+fn button_from_raw(number: u8) -> GamepadButton {
+    match number {
+        0 => GamepadButton::South,
+        1 => GamepadButton::East,
+        2 => GamepadButton::West,
+        3 => GamepadButton::North,
+        4 => GamepadButton::LeftShoulder,
+        5 => GamepadButton::RightShoulder,
+        6 => GamepadButton::Select,
+        7 => GamepadButton::Start,
+        8 => GamepadButton::Guide,
+        9 => GamepadButton::LeftStick,
+        10 => GamepadButton::RightStick,
+        _ => GamepadButton::Other(number),
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+fn axis_events(id: GamepadId, number: u8, raw: i16, events: &mut Vec<core::InputEvent>) {
+    let value = f32::from(raw) / 32767.0;
+
+    let (axis, value) = match number {
+        0 => (GamepadAxis::LeftX, value),
+        1 => (GamepadAxis::LeftY, -value),
+        // Triggers rest at -1
+        2 => (GamepadAxis::LeftTrigger, (value + 1.0) * 0.5),
+        3 => (GamepadAxis::RightX, value),
+        4 => (GamepadAxis::RightY, -value),
+        5 => (GamepadAxis::RightTrigger, (value + 1.0) * 0.5),
+        // The d-pad shows up as a pair of axes
+        6 | 7 => {
+            let (negative, positive) = if number == 6 {
+                (GamepadButton::DPadLeft, GamepadButton::DPadRight)
+            } else {
+                (GamepadButton::DPadUp, GamepadButton::DPadDown)
+            };
+
+            events.push(core::InputEvent::GamepadButton(id, negative, raw < 0));
+            events.push(core::InputEvent::GamepadButton(id, positive, raw > 0));
+            return;
+        }
+        _ => (GamepadAxis::Other(number), value),
+    };
+
+    events.push(core::InputEvent::GamepadAxis(id, axis, value));
+}
+
+impl Joysticks {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> Joysticks {
+        Joysticks {
+            devices: Vec::new(),
+            last_scan: None,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Adds connects, disconnects, button and axis changes since the last poll to events
+    pub fn poll(&mut self, events: &mut Vec<core::InputEvent>) {
+        let scan_due = match self.last_scan {
+            Some(time) => time.elapsed() >= SCAN_INTERVAL,
+            None => true,
+        };
+
+        if scan_due {
+            self.last_scan = Some(Instant::now());
+            self.scan(events);
+        }
+
+        self.read(events);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    #[cfg(target_os = "linux")]
+    fn scan(&mut self, events: &mut Vec<core::InputEvent>) {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        // O_NONBLOCK, so reading an idle device doesn't stall the frame
+        const NON_BLOCKING: i32 = 0o4000;
+
+        for index in 0..MAX_DEVICES {
+            let id = GamepadId(index);
+
+            if self.devices.iter().any(|device| device.id == id) {
+                continue;
+            }
+
+            let path = format!("/dev/input/js{}", index);
+
+            let file = match std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(NON_BLOCKING)
+                .open(&path)
+            {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+
+            println!("[Joysticks] Connected '{}'", path);
+            events.push(core::InputEvent::GamepadConnected(id, true));
+            self.devices.push(Device { id, file });
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    #[cfg(not(target_os = "linux"))]
+    fn scan(&mut self, _events: &mut Vec<core::InputEvent>) {}
+
+    //---------------------------------------------------------------------------------------------------
+    #[cfg(target_os = "linux")]
+    fn read(&mut self, events: &mut Vec<core::InputEvent>) {
+        use std::io::Read;
+
+        const BUTTON_EVENT: u8 = 0x01;
+        const AXIS_EVENT: u8 = 0x02;
+        // Set on the made up events a device sends when it's opened, describing its current state
+        const INIT_EVENT: u8 = 0x80;
+
+        let mut disconnected = Vec::new();
+
+        for device in self.devices.iter_mut() {
+            // struct js_event { u32 time; i16 value; u8 type; u8 number; }
+            let mut buffer = [0u8; 8];
+
+            loop {
+                match device.file.read(&mut buffer) {
+                    Ok(8) => {
+                        let value = i16::from_ne_bytes([buffer[4], buffer[5]]);
+                        let kind = buffer[6] & !INIT_EVENT;
+                        let number = buffer[7];
+
+                        if kind == BUTTON_EVENT {
+                            events.push(core::InputEvent::GamepadButton(
+                                device.id,
+                                button_from_raw(number),
+                                value != 0,
+                            ));
+                        } else if kind == AXIS_EVENT {
+                            axis_events(device.id, number, value, events);
+                        }
+                    }
+                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                    // Anything else means the device is gone
+                    _ => {
+                        disconnected.push(device.id);
+                        break;
+                    }
+                }
+            }
+        }
+
+        for id in disconnected {
+            println!("[Joysticks] Disconnected '/dev/input/js{}'", id.0);
+            events.push(core::InputEvent::GamepadConnected(id, false));
+            self.devices.retain(|device| device.id != id);
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    #[cfg(not(target_os = "linux"))]
+    fn read(&mut self, _events: &mut Vec<core::InputEvent>) {}
+}
@@ -1,11 +1,36 @@
+mod audio;
 mod camera;
+mod camera_controller;
+mod camera_shake;
+mod clipboard;
+mod config;
+mod display;
 mod game_loop;
+mod gamepad;
 mod game_state;
+mod geometry;
+mod input;
+mod input_buffer;
+mod input_recording;
+mod input_script;
+pub(crate) mod joystick;
+mod profiler_overlay;
+mod scaling;
+mod time_controller;
 mod transform;
+mod transition;
+mod tween;
 mod window;
 pub mod easing;
+pub mod profiler;
 
 pub use {
-    camera::Camera, game_loop::GameLoop, game_state::GameState, game_state::GameStateManager,
-    transform::Transform, window::Window
-};
+    audio::AudioDevice, camera::Camera, camera_controller::CameraController, camera_shake::CameraShake, clipboard::Clipboard, config::Config, config::DisplayConfig, display::MonitorInfo, display::WindowMode, game_loop::GameLoop, game_state::GameState, gamepad::AxisDirection, gamepad::Gamepad, gamepad::GamepadAxis,
+    gamepad::GamepadButton, gamepad::GamepadEvent, gamepad::GamepadId, gamepad::VirtualGamepad,
+    game_state::GameStateKey, game_state::GameStateManager, game_state::StateTransition, geometry::Ray,
+    geometry::Rect, input::AxisBinding, input::Binding, input::Input, input_buffer::InputBuffer, input_recording::InputRecording,
+    input_script::InputEvent, input_script::InputScript, profiler_overlay::ProfilerOverlay,
+    scaling::ScalingPolicy, scaling::Viewport,
+    time_controller::TimeController, transform::Transform, transition::Transition, tween::Animated, tween::Animation,
+    tween::Parallel, tween::Sequence, tween::Tween, window::KeyState, window::Window
+};
\ No newline at end of file
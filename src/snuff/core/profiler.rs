@@ -0,0 +1,299 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::Write;
+
+// Enough frames for the stats to be stable, without the trace growing too big to open
+const HISTORY_FRAMES: usize = 120;
+
+struct Event {
+    name: Cow<'static, str>,
+    start_ns: u64,
+    duration_ns: u64,
+}
+
+struct Frame {
+    start_ns: u64,
+    duration_ns: u64,
+    draw_calls: u32,
+    sprites: u32,
+    sprite_batches: u32,
+    largest_sprite_batch: u32,
+    events: Vec<Event>,
+}
+
+struct Profiler {
+    start_ns: u64,
+    frame_start_ns: Option<u64>,
+    draw_calls: u32,
+    sprites: u32,
+    sprite_batches: u32,
+    largest_sprite_batch: u32,
+    events: Vec<Event>,
+    history: VecDeque<Frame>,
+}
+
+thread_local! {
+    static PROFILER: RefCell<Profiler> = RefCell::new(Profiler {
+        start_ns: time::precise_time_ns(),
+        frame_start_ns: None,
+        draw_calls: 0,
+        sprites: 0,
+        sprite_batches: 0,
+        largest_sprite_batch: 0,
+        events: Vec::new(),
+        history: VecDeque::new(),
+    });
+}
+
+#[derive(Clone, Copy)]
+pub struct TimingStats {
+    pub min_ms: f32,
+    pub avg_ms: f32,
+    pub max_ms: f32,
+}
+
+pub struct ScopeStats {
+    pub name: String,
+    pub timing: TimingStats,
+    pub calls_per_frame: f32,
+}
+
+pub struct FrameStats {
+    pub frame_count: usize,
+    pub timing: TimingStats,
+    pub min_draw_calls: u32,
+    pub avg_draw_calls: f32,
+    pub max_draw_calls: u32,
+    pub avg_sprites: f32,
+    pub max_sprites: u32,
+    pub avg_sprite_batches: f32,
+    pub max_sprite_batches: u32,
+    // The most sprites a single sprite batch draw call drew
+    pub largest_sprite_batch: u32,
+    pub scopes: Vec<ScopeStats>,
+}
+
+// Measures the time until it's dropped, create one with profiler::scope
+pub struct Scope {
+    name: Option<Cow<'static, str>>,
+    start_ns: u64,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let end_ns = time::precise_time_ns();
+        let name = self.name.take().unwrap();
+        let start_ns = self.start_ns;
+
+        PROFILER.with(|profiler| {
+            profiler.borrow_mut().events.push(Event {
+                name,
+                start_ns,
+                duration_ns: end_ns - start_ns,
+            });
+        });
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+fn to_ms(ns: u64) -> f32 {
+    (ns as f64 * 1e-6) as f32
+}
+
+//---------------------------------------------------------------------------------------------------
+fn timing_stats(durations_ns: &[u64]) -> TimingStats {
+    if durations_ns.is_empty() {
+        return TimingStats {
+            min_ms: 0.0,
+            avg_ms: 0.0,
+            max_ms: 0.0,
+        };
+    }
+
+    let total: u64 = durations_ns.iter().sum();
+
+    TimingStats {
+        min_ms: to_ms(*durations_ns.iter().min().unwrap()),
+        avg_ms: to_ms(total / durations_ns.len() as u64),
+        max_ms: to_ms(*durations_ns.iter().max().unwrap()),
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+pub fn scope<N: Into<Cow<'static, str>>>(name: N) -> Scope {
+    Scope {
+        name: Some(name.into()),
+        start_ns: time::precise_time_ns(),
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+pub fn count_draw_call() {
+    PROFILER.with(|profiler| profiler.borrow_mut().draw_calls += 1);
+}
+
+//---------------------------------------------------------------------------------------------------
+// Sprite batches report what they drew and in how many draw calls, so it's visible when batching breaks up
+pub fn count_sprite_batch(sprites: usize, draw_calls: usize, largest_batch: usize) {
+    PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+
+        profiler.sprites += sprites as u32;
+        profiler.sprite_batches += draw_calls as u32;
+        profiler.largest_sprite_batch = profiler.largest_sprite_batch.max(largest_batch as u32);
+    });
+}
+
+//---------------------------------------------------------------------------------------------------
+pub fn begin_frame() {
+    PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+
+        profiler.frame_start_ns = Some(time::precise_time_ns());
+        profiler.draw_calls = 0;
+        profiler.sprites = 0;
+        profiler.sprite_batches = 0;
+        profiler.largest_sprite_batch = 0;
+        profiler.events.clear();
+    });
+}
+
+//---------------------------------------------------------------------------------------------------
+pub fn end_frame() {
+    PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+
+        let start_ns = match profiler.frame_start_ns.take() {
+            Some(start_ns) => start_ns,
+            None => return,
+        };
+
+        let frame = Frame {
+            start_ns,
+            duration_ns: time::precise_time_ns() - start_ns,
+            draw_calls: profiler.draw_calls,
+            sprites: profiler.sprites,
+            sprite_batches: profiler.sprite_batches,
+            largest_sprite_batch: profiler.largest_sprite_batch,
+            events: profiler.events.drain(..).collect(),
+        };
+
+        if profiler.history.len() >= HISTORY_FRAMES {
+            profiler.history.pop_front();
+        }
+
+        profiler.history.push_back(frame);
+    });
+}
+
+//---------------------------------------------------------------------------------------------------
+// Scopes are summed per frame, so a scope that runs for every tick shows the cost of the whole frame
+pub fn stats() -> FrameStats {
+    PROFILER.with(|profiler| {
+        let profiler = profiler.borrow();
+        let history = &profiler.history;
+
+        let frame_durations: Vec<u64> = history.iter().map(|frame| frame.duration_ns).collect();
+        let draw_calls: Vec<u32> = history.iter().map(|frame| frame.draw_calls).collect();
+        let sprites: Vec<u32> = history.iter().map(|frame| frame.sprites).collect();
+        let sprite_batches: Vec<u32> = history.iter().map(|frame| frame.sprite_batches).collect();
+
+        // Keep the order in which scopes first show up, that roughly follows the order of a frame
+        let mut names: Vec<&str> = Vec::new();
+        for frame in history.iter() {
+            for event in frame.events.iter() {
+                if !names.contains(&&event.name[..]) {
+                    names.push(&event.name[..]);
+                }
+            }
+        }
+
+        let scopes = names
+            .iter()
+            .map(|name| {
+                let mut durations = Vec::new();
+                let mut calls = 0;
+
+                for frame in history.iter() {
+                    let mut total_ns = 0;
+                    let mut found = false;
+
+                    for event in frame.events.iter().filter(|event| event.name == *name) {
+                        total_ns += event.duration_ns;
+                        calls += 1;
+                        found = true;
+                    }
+
+                    if found {
+                        durations.push(total_ns);
+                    }
+                }
+
+                ScopeStats {
+                    name: String::from(*name),
+                    timing: timing_stats(&durations),
+                    calls_per_frame: calls as f32 / durations.len().max(1) as f32,
+                }
+            })
+            .collect();
+
+        FrameStats {
+            frame_count: history.len(),
+            timing: timing_stats(&frame_durations),
+            min_draw_calls: draw_calls.iter().cloned().min().unwrap_or(0),
+            avg_draw_calls: draw_calls.iter().sum::<u32>() as f32 / draw_calls.len().max(1) as f32,
+            max_draw_calls: draw_calls.iter().cloned().max().unwrap_or(0),
+            avg_sprites: sprites.iter().sum::<u32>() as f32 / sprites.len().max(1) as f32,
+            max_sprites: sprites.iter().cloned().max().unwrap_or(0),
+            avg_sprite_batches: sprite_batches.iter().sum::<u32>() as f32 / sprite_batches.len().max(1) as f32,
+            max_sprite_batches: sprite_batches.iter().cloned().max().unwrap_or(0),
+            largest_sprite_batch: history.iter().map(|frame| frame.largest_sprite_batch).max().unwrap_or(0),
+            scopes,
+        }
+    })
+}
+
+//---------------------------------------------------------------------------------------------------
+fn escape_json(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+//---------------------------------------------------------------------------------------------------
+// Writes the recent frames in the Chrome trace event format, open it in chrome://tracing or Perfetto
+pub fn save_chrome_trace(path: &str) -> std::io::Result<()> {
+    PROFILER.with(|profiler| {
+        let profiler = profiler.borrow();
+        let to_us = |ns: u64| (ns - profiler.start_ns.min(ns)) as f64 * 1e-3;
+
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+        write!(file, "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[")?;
+
+        let mut first = true;
+        for frame in profiler.history.iter() {
+            write!(
+                file,
+                "{}{{\"name\":\"Frame\",\"cat\":\"frame\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":1,\"args\":{{\"draw_calls\":{}}}}}",
+                if first { "" } else { "," },
+                to_us(frame.start_ns),
+                frame.duration_ns as f64 * 1e-3,
+                frame.draw_calls
+            )?;
+            first = false;
+
+            for event in frame.events.iter() {
+                write!(
+                    file,
+                    ",{{\"name\":\"{}\",\"cat\":\"scope\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":1}}",
+                    escape_json(&event.name),
+                    to_us(event.start_ns),
+                    event.duration_ns as f64 * 1e-3
+                )?;
+            }
+        }
+
+        write!(file, "]}}")?;
+        file.flush()
+    })
+}
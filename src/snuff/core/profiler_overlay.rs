@@ -0,0 +1,244 @@
+use crate::snuff::core;
+use crate::snuff::core::profiler;
+use crate::snuff::gfx;
+
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+const PIXEL_SCALE: f32 = 2.0;
+const MARGIN: f32 = 8.0;
+const REFRESH_FRAMES: u32 = 30;
+
+const VERTEX_SHADER: &str = "
+#version 330
+
+in vec2 position;
+in vec2 uv;
+
+out vec2 UV;
+
+uniform mat4 model;
+uniform mat4 projection;
+
+void main()
+{
+    gl_Position = projection * model * vec4(position, 1.0, 1.0);
+    UV = uv;
+}";
+
+const FRAGMENT_SHADER: &str = "
+#version 330
+
+in vec2 UV;
+
+out vec4 color;
+
+uniform sampler2D sampler0;
+
+void main()
+{
+    color = texture(sampler0, UV);
+}";
+
+//---------------------------------------------------------------------------------------------------
+// A tiny 3x5 pixel font, one row per byte, so the overlay doesn't depend on any assets
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '[' => [0b011, 0b010, 0b010, 0b010, 0b011],
+        ']' => [0b110, 0b010, 0b010, 0b010, 0b110],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+fn rasterize(lines: &[String]) -> (Vec<u8>, u16, u16) {
+    let columns = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    let width = columns * GLYPH_WIDTH + 2;
+    let height = lines.len() * GLYPH_HEIGHT + 2;
+
+    // Translucent black background, so the text stays readable on top of anything
+    let mut data = Vec::with_capacity(width * height * 4);
+    for _ in 0..width * height {
+        data.extend_from_slice(&[0, 0, 0, 180]);
+    }
+
+    for (row, line) in lines.iter().enumerate() {
+        for (column, c) in line.to_uppercase().chars().enumerate() {
+            let rows = glyph(c);
+
+            for (y, bits) in rows.iter().enumerate() {
+                for x in 0..3 {
+                    if bits & (0b100 >> x) == 0 {
+                        continue;
+                    }
+
+                    let px = 1 + column * GLYPH_WIDTH + x;
+                    let py = 1 + row * GLYPH_HEIGHT + y;
+                    let index = (py * width + px) * 4;
+
+                    data[index..index + 4].copy_from_slice(&[255, 255, 255, 255]);
+                }
+            }
+        }
+    }
+
+    (data, width as u16, height as u16)
+}
+
+pub struct ProfilerOverlay {
+    visible: bool,
+    frames_until_refresh: u32,
+    texture: Option<gfx::Texture2D>,
+    quad: gfx::Mesh,
+    shader: gfx::ShaderProgram,
+    camera: core::Camera,
+}
+
+impl ProfilerOverlay {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(device: &gfx::Device) -> ProfilerOverlay {
+        ProfilerOverlay {
+            visible: false,
+            frames_until_refresh: 0,
+            texture: None,
+            quad: gfx::Mesh::create_quad(device, true),
+            shader: gfx::ShaderProgram::from_string(device, VERTEX_SHADER, FRAGMENT_SHADER).unwrap(),
+            camera: core::Camera::new(),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.frames_until_refresh = 0;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn lines() -> Vec<String> {
+        let stats = profiler::stats();
+
+        let mut lines = vec![
+            format!(
+                "frame   avg {:6.2}  min {:6.2}  max {:6.2} ms  ({} frames)",
+                stats.timing.avg_ms, stats.timing.min_ms, stats.timing.max_ms, stats.frame_count
+            ),
+            format!(
+                "draws   avg {:6.1}  min {:6}  max {:6}",
+                stats.avg_draw_calls, stats.min_draw_calls, stats.max_draw_calls
+            ),
+            format!("sprites avg {:6.1}  max {:6}", stats.avg_sprites, stats.max_sprites),
+            format!(
+                "batches avg {:6.1}  max {:6}  largest {}",
+                stats.avg_sprite_batches, stats.max_sprite_batches, stats.largest_sprite_batch
+            ),
+            String::new(),
+        ];
+
+        for scope in stats.scopes.iter() {
+            lines.push(format!(
+                "{:28} {:6.2} {:6.2} {:6.2} ms  x{:.0}",
+                scope.name, scope.timing.avg_ms, scope.timing.min_ms, scope.timing.max_ms, scope.calls_per_frame
+            ));
+        }
+
+        lines
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn draw(&mut self, command_buffer: &mut gfx::CommandBuffer) {
+        if !self.visible {
+            return;
+        }
+
+        let (width, height) = command_buffer.dimensions();
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        // Rebuilding the texture every frame would make the numbers unreadable, and cost more than it measures
+        if self.frames_until_refresh == 0 || self.texture.is_none() {
+            let (data, texture_width, texture_height) = rasterize(&ProfilerOverlay::lines());
+
+            self.texture = Some(
+                gfx::Texture2D::from_data(command_buffer.device(), &data, texture_width, texture_height)
+                    .with_nearest_filter(),
+            );
+            self.frames_until_refresh = REFRESH_FRAMES;
+        }
+
+        self.frames_until_refresh -= 1;
+
+        let texture = self.texture.as_ref().unwrap();
+        let size = texture.dimensions_f() * PIXEL_SCALE;
+
+        self.camera.set_orthographic_size_both_f(width as f32, height as f32);
+
+        let mut transform = core::Transform::new();
+        transform
+            .set_size_2d(&size)
+            .set_translation_2d_f(
+                (size.x - width as f32) * 0.5 + MARGIN,
+                (height as f32 - size.y) * 0.5 - MARGIN,
+            );
+
+        command_buffer.alpha_blend();
+        command_buffer.draw(
+            &mut self.camera,
+            &self.quad,
+            &mut transform,
+            &mut self.shader,
+            &vec![texture],
+        );
+    }
+}
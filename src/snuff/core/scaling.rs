@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+
+// How the logical resolution is scaled up to the window, whatever is left over becomes black bars
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ScalingPolicy {
+    // Whole multiples only, so every pixel stays square and crisp
+    Integer,
+    // As large as possible while keeping the aspect ratio
+    Fit,
+    // Fills the whole window, even if that distorts the image
+    Stretch,
+}
+
+// In window pixels, from the bottom left like OpenGL expects
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    //---------------------------------------------------------------------------------------------------
+    // Maps a position in window pixels from the top left to logical pixels, None if it's in the bars
+    pub fn window_to_logical(
+        &self,
+        position: &nalgebra_glm::Vec2,
+        window_height: u32,
+        logical: &nalgebra_glm::Vec2,
+    ) -> Option<nalgebra_glm::Vec2> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+
+        let top = window_height as f32 - (self.y + self.height) as f32;
+        let x = (position.x - self.x as f32) / self.width as f32;
+        let y = (position.y - top) / self.height as f32;
+
+        if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+            return None;
+        }
+
+        Some(nalgebra_glm::vec2(x * logical.x, y * logical.y))
+    }
+}
+
+impl ScalingPolicy {
+    //---------------------------------------------------------------------------------------------------
+    pub fn from_name(name: &str) -> Option<ScalingPolicy> {
+        match name.to_lowercase().as_str() {
+            "integer" => Some(ScalingPolicy::Integer),
+            "fit" => Some(ScalingPolicy::Fit),
+            "stretch" => Some(ScalingPolicy::Stretch),
+            _ => None,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Where the logical resolution ends up in a window of the given size, centered
+    pub fn viewport(&self, logical: (u32, u32), window: (u32, u32)) -> Viewport {
+        let (logical_width, logical_height) = (logical.0.max(1) as f32, logical.1.max(1) as f32);
+        let (window_width, window_height) = (window.0 as f32, window.1 as f32);
+
+        let fit = (window_width / logical_width).min(window_height / logical_height);
+
+        let (width, height) = match self {
+            ScalingPolicy::Stretch => (window_width, window_height),
+            ScalingPolicy::Fit => (logical_width * fit, logical_height * fit),
+            ScalingPolicy::Integer => {
+                // A window smaller than the logical resolution can't fit a whole multiple, so show everything instead
+                let scale = if fit >= 1.0 { fit.floor() } else { fit };
+                (logical_width * scale, logical_height * scale)
+            }
+        };
+
+        let width = (width.round() as u32).min(window.0);
+        let height = (height.round() as u32).min(window.1);
+
+        Viewport {
+            x: (window.0 - width) / 2,
+            y: (window.1 - height) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn viewport(x: u32, y: u32, width: u32, height: u32) -> Viewport {
+        Viewport { x, y, width, height }
+    }
+
+    #[test]
+    fn fit_keeps_the_aspect_ratio_with_bars() {
+        assert_eq!(ScalingPolicy::Fit.viewport((1280, 720), (1920, 1080)), viewport(0, 0, 1920, 1080));
+        assert_eq!(ScalingPolicy::Fit.viewport((1280, 720), (1920, 1200)), viewport(0, 60, 1920, 1080));
+        assert_eq!(ScalingPolicy::Fit.viewport((1280, 720), (1000, 1000)), viewport(0, 218, 1000, 563));
+    }
+
+    #[test]
+    fn integer_only_scales_by_whole_multiples() {
+        assert_eq!(ScalingPolicy::Integer.viewport((320, 180), (1920, 1080)), viewport(0, 0, 1920, 1080));
+        assert_eq!(ScalingPolicy::Integer.viewport((320, 180), (1900, 1080)), viewport(150, 90, 1600, 900));
+    }
+
+    #[test]
+    fn integer_shrinks_to_fit_small_windows() {
+        assert_eq!(ScalingPolicy::Integer.viewport((1280, 720), (640, 480)), viewport(0, 60, 640, 360));
+    }
+
+    #[test]
+    fn stretch_fills_the_window() {
+        assert_eq!(ScalingPolicy::Stretch.viewport((1280, 720), (1000, 1000)), viewport(0, 0, 1000, 1000));
+    }
+
+    #[test]
+    fn empty_sizes_dont_panic() {
+        assert_eq!(ScalingPolicy::Fit.viewport((0, 0), (0, 0)), viewport(0, 0, 0, 0));
+        assert_eq!(viewport(0, 0, 0, 0).window_to_logical(&nalgebra_glm::vec2(0.0, 0.0), 0, &nalgebra_glm::vec2(1280.0, 720.0)), None);
+    }
+
+    #[test]
+    fn window_positions_map_to_logical_ones() {
+        // Bars at the top and bottom of a 1920x1200 window
+        let viewport = ScalingPolicy::Fit.viewport((1280, 720), (1920, 1200));
+        let logical = nalgebra_glm::vec2(1280.0, 720.0);
+
+        assert_eq!(viewport.window_to_logical(&nalgebra_glm::vec2(0.0, 60.0), 1200, &logical), Some(nalgebra_glm::vec2(0.0, 0.0)));
+        assert_eq!(viewport.window_to_logical(&nalgebra_glm::vec2(960.0, 600.0), 1200, &logical), Some(nalgebra_glm::vec2(640.0, 360.0)));
+        assert_eq!(viewport.window_to_logical(&nalgebra_glm::vec2(1920.0, 1140.0), 1200, &logical), Some(logical));
+    }
+
+    #[test]
+    fn positions_in_the_bars_map_to_nothing() {
+        let viewport = ScalingPolicy::Fit.viewport((1280, 720), (1920, 1200));
+        let logical = nalgebra_glm::vec2(1280.0, 720.0);
+
+        assert_eq!(viewport.window_to_logical(&nalgebra_glm::vec2(960.0, 30.0), 1200, &logical), None);
+        assert_eq!(viewport.window_to_logical(&nalgebra_glm::vec2(960.0, 1170.0), 1200, &logical), None);
+    }
+
+    #[test]
+    fn parses_policy_names() {
+        assert_eq!(ScalingPolicy::from_name("Integer"), Some(ScalingPolicy::Integer));
+        assert_eq!(ScalingPolicy::from_name("fit"), Some(ScalingPolicy::Fit));
+        assert_eq!(ScalingPolicy::from_name("zoom"), None);
+    }
+}
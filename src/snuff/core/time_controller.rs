@@ -0,0 +1,272 @@
+use crate::snuff::core;
+
+use glium::glutin::VirtualKeyCode;
+
+pub const PAUSE_ACTION: &str = "debug_pause";
+pub const STEP_ACTION: &str = "debug_step";
+pub const FAST_FORWARD_ACTION: &str = "debug_fast_forward";
+pub const SLOW_MOTION_ACTION: &str = "debug_slow_motion";
+pub const SPEED_UP_ACTION: &str = "debug_speed_up";
+pub const SLOW_DOWN_ACTION: &str = "debug_slow_down";
+pub const RESET_SPEED_ACTION: &str = "debug_reset_speed";
+
+const FAST_FORWARD_SCALE: f32 = 10.0;
+const SLOW_MOTION_SCALE: f32 = 0.1;
+const MIN_SCALE: f32 = 1.0 / 64.0;
+const MAX_SCALE: f32 = 64.0;
+
+pub struct TimeController {
+    scale: f32,
+    paused: bool,
+    pending_steps: u32,
+}
+
+impl TimeController {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> TimeController {
+        TimeController {
+            scale: 1.0,
+            paused: false,
+            pending_steps: 0,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn add_default_bindings(config: &mut core::Config) {
+        let key = core::Binding::Key;
+
+        config
+            .add_default_bindings(PAUSE_ACTION, &[key(VirtualKeyCode::P)])
+            .add_default_bindings(STEP_ACTION, &[key(VirtualKeyCode::Period)])
+            .add_default_bindings(FAST_FORWARD_ACTION, &[key(VirtualKeyCode::RBracket)])
+            .add_default_bindings(SLOW_MOTION_ACTION, &[key(VirtualKeyCode::LBracket)])
+            .add_default_bindings(SPEED_UP_ACTION, &[key(VirtualKeyCode::Equals)])
+            .add_default_bindings(SLOW_DOWN_ACTION, &[key(VirtualKeyCode::Minus)])
+            .add_default_bindings(RESET_SPEED_ACTION, &[key(VirtualKeyCode::Key0)]);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_scale(&mut self, scale: f32) -> &mut TimeController {
+        self.scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_paused(&mut self, paused: bool) -> &mut TimeController {
+        self.paused = paused;
+        self.pending_steps = 0;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Only does something while paused, every step lets exactly one tick through
+    pub fn step(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn handle_actions(&mut self, window: &core::Window) {
+        if window.is_action_pressed(PAUSE_ACTION) {
+            let paused = !self.paused;
+            self.set_paused(paused);
+            println!("[TimeController] {}", if paused { "Paused" } else { "Resumed" });
+        }
+
+        if window.is_action_pressed(STEP_ACTION) {
+            self.step();
+        }
+
+        let old_scale = self.scale;
+
+        if window.is_action_pressed(SPEED_UP_ACTION) {
+            self.set_scale(old_scale * 2.0);
+        }
+
+        if window.is_action_pressed(SLOW_DOWN_ACTION) {
+            self.set_scale(old_scale * 0.5);
+        }
+
+        if window.is_action_pressed(RESET_SPEED_ACTION) {
+            self.set_scale(1.0);
+        }
+
+        if self.scale != old_scale {
+            println!("[TimeController] Time scale set to {}", self.scale);
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Returns the delta time the simulation should use for this tick
+    pub fn tick(&mut self, dt: f32, window: &core::Window) -> f32 {
+        self.handle_actions(window);
+
+        if self.paused {
+            if self.pending_steps == 0 {
+                return 0.0;
+            }
+
+            self.pending_steps -= 1;
+            return dt;
+        }
+
+        let mut scale = self.scale;
+
+        // Holding these only lasts as long as they're down, on top of the persistent scale
+        if window.is_action_down(FAST_FORWARD_ACTION) {
+            scale *= FAST_FORWARD_SCALE;
+        } else if window.is_action_down(SLOW_MOTION_ACTION) {
+            scale *= SLOW_MOTION_SCALE;
+        }
+
+        dt * scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn debug_window() -> core::Window {
+        let mut input = core::Input::new();
+        input
+            .bind(STEP_ACTION, core::Binding::Key(VirtualKeyCode::Period))
+            .bind(SPEED_UP_ACTION, core::Binding::Key(VirtualKeyCode::Equals))
+            .bind(SLOW_DOWN_ACTION, core::Binding::Key(VirtualKeyCode::Minus));
+
+        let mut window = core::Window::headless(64, 64);
+        window.set_input(input);
+
+        window
+    }
+
+    // Runs a tick with the key tapped during it, like the game loop would
+    fn tick_tapping(controller: &mut TimeController, window: &mut core::Window, key: VirtualKeyCode) -> f32 {
+        window.inject(core::InputEvent::Key(key, true));
+        let dt = controller.tick(DT, window);
+        window.reset_input_states();
+        window.inject(core::InputEvent::Key(key, false));
+        window.reset_input_states();
+
+        dt
+    }
+
+    #[test]
+    fn the_scale_is_clamped() {
+        let mut controller = TimeController::new();
+        assert_eq!(controller.set_scale(1000.0).scale(), MAX_SCALE);
+        assert_eq!(controller.set_scale(0.0).scale(), MIN_SCALE);
+        assert_eq!(controller.set_scale(-2.0).scale(), MIN_SCALE);
+
+        let mut window = debug_window();
+        controller.set_scale(1.0);
+        for _ in 0..10 {
+            tick_tapping(&mut controller, &mut window, VirtualKeyCode::Equals);
+        }
+        assert_eq!(controller.scale(), 64.0);
+
+        for _ in 0..20 {
+            tick_tapping(&mut controller, &mut window, VirtualKeyCode::Minus);
+        }
+        assert_eq!(controller.scale(), 1.0 / 64.0);
+    }
+
+    #[test]
+    fn stepping_while_paused_lets_exactly_one_tick_through() {
+        let mut controller = TimeController::new();
+        let mut window = debug_window();
+        controller.set_scale(2.0).set_paused(true);
+
+        assert_eq!(controller.tick(DT, &window), 0.0);
+        assert_eq!(tick_tapping(&mut controller, &mut window, VirtualKeyCode::Period), DT);
+        assert_eq!(controller.tick(DT, &window), 0.0);
+
+        // Steps queue up, and don't carry over into the next pause
+        controller.step();
+        controller.step();
+        assert_eq!(controller.tick(DT, &window), DT);
+        controller.set_paused(false).set_paused(true);
+        assert_eq!(controller.tick(DT, &window), 0.0);
+    }
+
+    #[test]
+    fn stepping_without_pausing_does_nothing() {
+        let mut controller = TimeController::new();
+        let window = debug_window();
+
+        controller.step();
+        assert_eq!(controller.tick(DT, &window), DT);
+
+        controller.set_paused(true);
+        assert_eq!(controller.tick(DT, &window), 0.0);
+    }
+
+    crate::game_state_keys!(TestKey { Game, Menu });
+
+    // Writes the delta time of every update down, the menu opts out of the time scale
+    struct TimedState {
+        key: TestKey,
+        dts: Rc<RefCell<Vec<(TestKey, f32)>>>,
+    }
+
+    impl core::GameState<TestKey> for TimedState {
+        fn on_enter(&mut self) {}
+        fn on_leave(&mut self) {}
+
+        fn update(&mut self, dt: f32, _window: &core::Window) -> Option<core::StateTransition<TestKey>> {
+            self.dts.borrow_mut().push((self.key, dt));
+            None
+        }
+
+        fn draw(&mut self, _command_buffer: &mut crate::snuff::gfx::CommandBuffer, _alpha: f32) {}
+
+        fn updates_state_below(&self) -> bool {
+            true
+        }
+
+        fn uses_time_scale(&self) -> bool {
+            self.key != TestKey::Menu
+        }
+    }
+
+    #[test]
+    fn states_can_keep_running_at_real_time() {
+        let dts = Rc::new(RefCell::new(Vec::new()));
+        let mut manager = core::GameStateManager::new();
+        manager.register_states(|key| Box::new(TimedState { key, dts: dts.clone() }));
+        manager.switch(TestKey::Game);
+        manager.push(TestKey::Menu);
+
+        let mut controller = TimeController::new();
+        let window = debug_window();
+
+        controller.set_scale(0.5);
+        manager.update(controller.tick(DT, &window), DT, &window);
+
+        controller.set_paused(true);
+        manager.update(controller.tick(DT, &window), DT, &window);
+
+        assert_eq!(
+            *dts.borrow(),
+            vec![(TestKey::Game, DT * 0.5), (TestKey::Menu, DT), (TestKey::Game, 0.0), (TestKey::Menu, DT)]
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::snuff::core;
+
 use nalgebra_glm;
 
 pub struct Transform {
@@ -6,6 +8,8 @@ pub struct Transform {
     scale: nalgebra_glm::Vec3,
     size: nalgebra_glm::Vec3,
     rotation: nalgebra_glm::Quat,
+    parent_to_world: nalgebra_glm::Mat4,
+    child_to_world: nalgebra_glm::Mat4,
     local_to_world: nalgebra_glm::Mat4,
     world_to_local: nalgebra_glm::Mat4,
     is_dirty: bool,
@@ -20,6 +24,8 @@ impl Transform {
             scale: nalgebra_glm::vec3(1.0, 1.0, 1.0),
             size: nalgebra_glm::vec3(1.0, 1.0, 1.0),
             rotation: nalgebra_glm::quat_identity(),
+            parent_to_world: nalgebra_glm::identity(),
+            child_to_world: nalgebra_glm::identity(),
             local_to_world: nalgebra_glm::identity(),
             world_to_local: nalgebra_glm::identity(),
             is_dirty: true,
@@ -201,27 +207,125 @@ impl Transform {
         self.rotation_euler().z
     }
 
+    //---------------------------------------------------------------------------------------------------
+    // Only ever set by whoever owns the hierarchy, this is the parent's child_to_world
+    pub fn set_parent_to_world(&mut self, m: &nalgebra_glm::Mat4) -> &mut Transform {
+        if self.parent_to_world != *m {
+            self.parent_to_world = *m;
+            self.mark_dirty();
+        }
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn parent_to_world(&self) -> nalgebra_glm::Mat4 {
+        self.parent_to_world
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn update_matrices(&mut self) {
+        let mut m = self.parent_to_world;
+        m = nalgebra_glm::translate(&m, &self.translation);
+
+        // Children inherit translation, scale and rotation, but not the size or anchor of what we draw
+        let mut child = nalgebra_glm::scale(&m, &self.scale);
+        child *= nalgebra_glm::quat_to_mat4(&self.rotation);
+
+        let upscaled = nalgebra_glm::vec3(self.scale.x * self.size.x, self.scale.y * self.size.y, self.scale.z * self.size.z);
+
+        m = nalgebra_glm::scale(&m, &upscaled);
+        m *= nalgebra_glm::quat_to_mat4(&self.rotation);
+        m = nalgebra_glm::translate(&m, &self.anchor);
+
+        self.child_to_world = child;
+        self.world_to_local = nalgebra_glm::inverse(&m);
+        self.local_to_world = m;
+
+        self.clean();
+    }
+
     //---------------------------------------------------------------------------------------------------
     pub fn local_to_world(&mut self) -> nalgebra_glm::Mat4 {
-        if !self.is_dirty {
-            self.local_to_world
-        } else {
-            let mut m: nalgebra_glm::Mat4 = nalgebra_glm::identity();
+        if self.is_dirty {
+            self.update_matrices();
+        }
 
-            let upscaled = nalgebra_glm::vec3(self.scale.x * self.size.x, self.scale.y * self.size.y, self.scale.z * self.size.z);
+        self.local_to_world
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn child_to_world(&mut self) -> nalgebra_glm::Mat4 {
+        if self.is_dirty {
+            self.update_matrices();
+        }
+
+        self.child_to_world
+    }
 
-            m = nalgebra_glm::translate(&m, &self.translation);
-            m = nalgebra_glm::scale(&m, &upscaled);
-            m *= nalgebra_glm::quat_to_mat4(&self.rotation);
-            m = nalgebra_glm::translate(&m, &self.anchor);
+    //---------------------------------------------------------------------------------------------------
+    pub fn world_translation(&mut self) -> nalgebra_glm::Vec3 {
+        let m = self.child_to_world();
+        nalgebra_glm::vec3(m[(0, 3)], m[(1, 3)], m[(2, 3)])
+    }
 
-            self.world_to_local = nalgebra_glm::inverse(&m);
-            self.local_to_world = m;
+    //---------------------------------------------------------------------------------------------------
+    pub fn world_translation_2d(&mut self) -> nalgebra_glm::Vec2 {
+        let t = self.world_translation();
+        nalgebra_glm::vec2(t.x, t.y)
+    }
 
-            self.clean();
+    //---------------------------------------------------------------------------------------------------
+    // The bounds of the half size quad that sprites are drawn with, e.g. to pick them with the mouse
+    pub fn world_rect_2d(&mut self) -> core::Rect {
+        let m = self.local_to_world();
+        let corners = [(-0.5, -0.5), (0.5, -0.5), (-0.5, 0.5), (0.5, 0.5)];
 
-            self.local_to_world
+        core::Rect::from_points(corners.iter().map(|(x, y)| {
+            let corner = m * nalgebra_glm::vec4(*x, *y, 0.0, 1.0);
+            nalgebra_glm::vec2(corner.x, corner.y)
+        }))
+        .unwrap()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Sets translation, scale and rotation so that child_to_world ends up as m, relative to the current parent.
+    // Shear can't be represented, so a rotated child of a non-uniformly scaled parent only gets close
+    pub fn set_child_to_world(&mut self, m: &nalgebra_glm::Mat4) -> &mut Transform {
+        let local = nalgebra_glm::inverse(&self.parent_to_world) * m;
+
+        // The upper 3x3 is scale * rotation, so each row is a rotation row scaled by one axis
+        let mut scale = nalgebra_glm::vec3(
+            nalgebra_glm::length(&nalgebra_glm::vec3(local[(0, 0)], local[(0, 1)], local[(0, 2)])),
+            nalgebra_glm::length(&nalgebra_glm::vec3(local[(1, 0)], local[(1, 1)], local[(1, 2)])),
+            nalgebra_glm::length(&nalgebra_glm::vec3(local[(2, 0)], local[(2, 1)], local[(2, 2)])),
+        );
+
+        // A mirrored transform, like a flipped sprite, keeps the flip on x
+        if nalgebra_glm::determinant(&nalgebra_glm::mat4_to_mat3(&local)) < 0.0 {
+            scale.x = -scale.x;
         }
+
+        let mut rotation: nalgebra_glm::Mat4 = nalgebra_glm::identity();
+        for row in 0..3 {
+            let axis_scale = if scale[row] != 0.0 { scale[row] } else { 1.0 };
+
+            for column in 0..3 {
+                rotation[(row, column)] = local[(row, column)] / axis_scale;
+            }
+        }
+
+        self.translation = nalgebra_glm::vec3(local[(0, 3)], local[(1, 3)], local[(2, 3)]);
+        self.scale = scale;
+        self.rotation = nalgebra_glm::to_quat(&rotation);
+        self.mark_dirty();
+
+        self
     }
 
     //---------------------------------------------------------------------------------------------------
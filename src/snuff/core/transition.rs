@@ -0,0 +1,128 @@
+use crate::snuff::core;
+
+#[derive(Clone)]
+pub struct Transition {
+    shader_path: String,
+    color: nalgebra_glm::Vec3,
+    duration: f32,
+    easing: core::easing::Curve,
+}
+
+impl Transition {
+    //---------------------------------------------------------------------------------------------------
+    pub fn cut() -> Transition {
+        Transition::custom("", 0.0)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn fade_to_color(r: f32, g: f32, b: f32, duration: f32) -> Transition {
+        let mut transition = Transition::custom("assets/shaders/transition_fade.fs", duration);
+        transition.color = nalgebra_glm::vec3(r, g, b);
+
+        transition
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn cross_dissolve(duration: f32) -> Transition {
+        Transition::custom("assets/shaders/transition_dissolve.fs", duration)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn wipe(duration: f32) -> Transition {
+        Transition::custom("assets/shaders/transition_wipe.fs", duration)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The fragment shader gets the outgoing states in sampler0, the incoming states in sampler1,
+    // the transition color in blend.rgb and the eased progress in blend.a
+    pub fn custom(shader_path: &str, duration: f32) -> Transition {
+        Transition {
+            shader_path: String::from(shader_path),
+            color: nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            duration: duration.max(0.0),
+            easing: core::easing::Curve::Function(core::easing::linear),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn with_easing(self, easing: core::easing::EasingFn) -> Transition {
+        self.with_curve(core::easing::Curve::Function(easing))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn with_curve(mut self, curve: core::easing::Curve) -> Transition {
+        self.easing = curve;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_cut(&self) -> bool {
+        self.duration <= 0.0 || self.shader_path.is_empty()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn shader_path(&self) -> &str {
+        &self.shader_path[..]
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn color(&self) -> &nalgebra_glm::Vec3 {
+        &self.color
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn progress(&self, timer: f32) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+
+        self.easing.sample((timer / self.duration).clamp(0.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_is_clamped() {
+        let transition = Transition::wipe(2.0);
+
+        assert_eq!(transition.progress(-1.0), 0.0);
+        assert_eq!(transition.progress(1.0), 0.5);
+        assert_eq!(transition.progress(3.0), 1.0);
+    }
+
+    #[test]
+    fn cuts_finish_right_away() {
+        for transition in [Transition::cut(), Transition::fade_to_color(0.0, 0.0, 0.0, 0.0), Transition::custom("", 1.0)].iter() {
+            assert!(transition.is_cut());
+        }
+
+        assert_eq!(Transition::cut().progress(0.0), 1.0);
+        assert_eq!(Transition::cross_dissolve(-1.0).progress(0.0), 1.0);
+    }
+
+    #[test]
+    fn easing_is_applied_to_the_progress() {
+        let transition = Transition::cross_dissolve(2.0).with_easing(core::easing::in_quad);
+
+        assert_eq!(transition.progress(1.0), 0.25);
+        assert_eq!(transition.progress(2.0), 1.0);
+    }
+
+    #[test]
+    fn curves_are_applied_to_the_progress() {
+        let keyframes = core::easing::Curve::parse("keyframes(0: 0, 0.5: 1, 1: 1)").unwrap();
+        let transition = Transition::wipe(1.0).with_curve(keyframes);
+
+        assert_eq!(transition.progress(0.25), 0.5);
+        assert_eq!(transition.progress(0.75), 1.0);
+    }
+}
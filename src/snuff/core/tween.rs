@@ -0,0 +1,361 @@
+use crate::snuff::core;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+pub trait Lerp: Copy {
+    fn lerp(from: &Self, to: &Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(from: &f32, to: &f32, t: f32) -> f32 {
+        from + (to - from) * t
+    }
+}
+
+impl Lerp for nalgebra_glm::Vec2 {
+    fn lerp(from: &nalgebra_glm::Vec2, to: &nalgebra_glm::Vec2, t: f32) -> nalgebra_glm::Vec2 {
+        nalgebra_glm::lerp(from, to, t)
+    }
+}
+
+impl Lerp for nalgebra_glm::Vec3 {
+    fn lerp(from: &nalgebra_glm::Vec3, to: &nalgebra_glm::Vec3, t: f32) -> nalgebra_glm::Vec3 {
+        nalgebra_glm::lerp(from, to, t)
+    }
+}
+
+// Colors are animated as RGBA vectors
+impl Lerp for nalgebra_glm::Vec4 {
+    fn lerp(from: &nalgebra_glm::Vec4, to: &nalgebra_glm::Vec4, t: f32) -> nalgebra_glm::Vec4 {
+        nalgebra_glm::lerp(from, to, t)
+    }
+}
+
+// A value shared between a tween and whoever reads it, so tweens can live inside sequences
+#[derive(Clone)]
+pub struct Animated<T: Copy> {
+    value: Rc<Cell<T>>,
+}
+
+impl<T: Copy> Animated<T> {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(value: T) -> Animated<T> {
+        Animated {
+            value: Rc::new(Cell::new(value)),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn get(&self) -> T {
+        self.value.get()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set(&self, value: T) {
+        self.value.set(value)
+    }
+}
+
+pub trait Animation {
+    // Returns the part of dt that was left over after finishing, so a sequence can pass it on
+    fn advance(&mut self, dt: f32) -> f32;
+    fn reset(&mut self);
+    fn is_finished(&self) -> bool;
+}
+
+pub struct Tween<T: Lerp> {
+    from: T,
+    to: T,
+    value: T,
+    target: Option<Animated<T>>,
+    duration: f32,
+    delay: f32,
+    easing: core::easing::Curve,
+    repeat_count: u32,
+    repeat_forever: bool,
+    yoyo: bool,
+    elapsed: f32,
+    delay_left: f32,
+    cycle: u32,
+    finished: bool,
+    on_complete: Option<Box<dyn FnMut()>>,
+}
+
+impl<T: Lerp> Tween<T> {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(from: T, to: T, duration: f32) -> Tween<T> {
+        Tween {
+            from,
+            to,
+            value: from,
+            target: None,
+            duration: duration.max(0.0),
+            delay: 0.0,
+            easing: core::easing::Curve::Function(core::easing::linear),
+            repeat_count: 0,
+            repeat_forever: false,
+            yoyo: false,
+            elapsed: 0.0,
+            delay_left: 0.0,
+            cycle: 0,
+            finished: false,
+            on_complete: None,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn with_target(mut self, target: &Animated<T>) -> Tween<T> {
+        target.set(self.value);
+        self.target = Some(target.clone());
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn with_easing(self, easing: core::easing::EasingFn) -> Tween<T> {
+        self.with_curve(core::easing::Curve::Function(easing))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn with_curve(mut self, curve: core::easing::Curve) -> Tween<T> {
+        self.easing = curve;
+        self.apply();
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn with_delay(mut self, delay: f32) -> Tween<T> {
+        self.delay = delay.max(0.0);
+        self.delay_left = self.delay;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Plays the tween this many extra times after the first
+    pub fn with_repeat(mut self, count: u32) -> Tween<T> {
+        self.repeat_count = count;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn with_repeat_forever(mut self) -> Tween<T> {
+        self.repeat_forever = true;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Every other repetition plays backwards
+    pub fn with_yoyo(mut self, yoyo: bool) -> Tween<T> {
+        self.yoyo = yoyo;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn on_complete<F: FnMut() + 'static>(mut self, callback: F) -> Tween<T> {
+        self.on_complete = Some(Box::new(callback));
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn apply(&mut self) {
+        let mut t = if self.duration > 0.0 {
+            (self.elapsed / self.duration).min(1.0)
+        } else {
+            1.0
+        };
+
+        if self.yoyo && self.cycle % 2 == 1 {
+            t = 1.0 - t;
+        }
+
+        self.value = T::lerp(&self.from, &self.to, self.easing.sample(t));
+
+        if let Some(target) = &self.target {
+            target.set(self.value);
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn complete(&mut self) {
+        self.finished = true;
+
+        if let Some(callback) = self.on_complete.as_mut() {
+            callback();
+        }
+    }
+}
+
+impl<T: Lerp> Animation for Tween<T> {
+    //---------------------------------------------------------------------------------------------------
+    fn advance(&mut self, dt: f32) -> f32 {
+        if self.finished {
+            return dt;
+        }
+
+        let used_by_delay = self.delay_left.min(dt);
+        self.delay_left -= used_by_delay;
+
+        if self.delay_left > 0.0 {
+            return 0.0;
+        }
+
+        self.elapsed += dt - used_by_delay;
+
+        while self.elapsed >= self.duration {
+            if !self.repeat_forever && self.cycle >= self.repeat_count {
+                let leftover = self.elapsed - self.duration;
+
+                self.elapsed = self.duration;
+                self.apply();
+                self.complete();
+
+                return leftover;
+            }
+
+            // A zero length tween that repeats forever would never leave this loop
+            if self.duration <= 0.0 {
+                break;
+            }
+
+            self.elapsed -= self.duration;
+            self.cycle += 1;
+        }
+
+        self.apply();
+
+        0.0
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.delay_left = self.delay;
+        self.cycle = 0;
+        self.finished = false;
+        self.apply();
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+// Plays its animations one after the other
+pub struct Sequence {
+    animations: Vec<Box<dyn Animation>>,
+    current: usize,
+}
+
+impl Sequence {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> Sequence {
+        Sequence {
+            animations: Vec::new(),
+            current: 0,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn then<A: Animation + 'static>(mut self, animation: A) -> Sequence {
+        self.animations.push(Box::new(animation));
+
+        self
+    }
+}
+
+impl Animation for Sequence {
+    //---------------------------------------------------------------------------------------------------
+    fn advance(&mut self, dt: f32) -> f32 {
+        let mut dt = dt;
+
+        while self.current < self.animations.len() {
+            dt = self.animations[self.current].advance(dt);
+
+            if !self.animations[self.current].is_finished() {
+                return 0.0;
+            }
+
+            self.current += 1;
+        }
+
+        dt
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn reset(&mut self) {
+        // Reset back to front, so animations that share a value end up showing the first one's start
+        for animation in self.animations.iter_mut().rev() {
+            animation.reset();
+        }
+
+        self.current = 0;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn is_finished(&self) -> bool {
+        self.current >= self.animations.len()
+    }
+}
+
+// Plays its animations at the same time, and finishes with the longest one
+pub struct Parallel {
+    animations: Vec<Box<dyn Animation>>,
+}
+
+impl Parallel {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> Parallel {
+        Parallel {
+            animations: Vec::new(),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn with<A: Animation + 'static>(mut self, animation: A) -> Parallel {
+        self.animations.push(Box::new(animation));
+
+        self
+    }
+}
+
+impl Animation for Parallel {
+    //---------------------------------------------------------------------------------------------------
+    fn advance(&mut self, dt: f32) -> f32 {
+        let mut leftover = dt;
+
+        for animation in self.animations.iter_mut() {
+            leftover = leftover.min(animation.advance(dt));
+        }
+
+        if self.is_finished() {
+            leftover
+        } else {
+            0.0
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn reset(&mut self) {
+        for animation in self.animations.iter_mut() {
+            animation.reset();
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn is_finished(&self) -> bool {
+        self.animations.iter().all(|animation| animation.is_finished())
+    }
+}
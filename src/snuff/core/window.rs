@@ -3,6 +3,8 @@ use crate::snuff::gfx::CommandBuffer;
 
 use glium::glutin;
 
+use std::cell::{Cell, Ref, RefCell, RefMut};
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum KeyState {
     Pressed,
@@ -11,6 +13,20 @@ pub enum KeyState {
     Up,
 }
 
+impl KeyState {
+    //---------------------------------------------------------------------------------------------------
+    // A press that hasn't been seen by a tick yet stays pressed
+    pub fn after(previous: Option<KeyState>, pressed: bool) -> KeyState {
+        match (previous, pressed) {
+            (Some(KeyState::Down), true) => KeyState::Down,
+            (_, true) => KeyState::Pressed,
+            (Some(KeyState::Down), false) | (Some(KeyState::Pressed), false) | (None, false) => KeyState::Released,
+            // Letting go of something that's already up changes nothing
+            (Some(state), false) => state,
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum MouseState {
     Pressed,
@@ -28,57 +44,154 @@ pub struct MouseEvent {
     scroll: f32
 }
 
+// Asked for by a game state, applied the next time events are processed
+#[derive(Clone, Copy)]
+struct DisplayRequest {
+    mode: snuff::core::WindowMode,
+    monitor: Option<usize>,
+}
+
 pub struct Window {
     client_width: u16,
     client_height: u16,
-    events_loop: glium::glutin::EventsLoop,
-    display: glium::Display,
+    logical_width: u16,
+    logical_height: u16,
+    scaling_policy: snuff::core::ScalingPolicy,
+    resized: bool,
+    window_mode: snuff::core::WindowMode,
+    monitor: Option<usize>,
+    windowed_size: glutin::dpi::LogicalSize,
+    windowed_position: Option<(i32, i32)>,
+    display_request: Cell<Option<DisplayRequest>>,
+    events_loop: Option<glium::glutin::EventsLoop>,
+    device: snuff::gfx::Device,
     default_texture: snuff::gfx::Texture2D,
     fullscreen_quad: snuff::gfx::Mesh,
     key_states: std::collections::HashMap<glutin::VirtualKeyCode, KeyState>,
     mouse_states: std::collections::HashMap<glutin::MouseButton, MouseState>,
     old_mouse_pos: nalgebra_glm::Vec2,
     current_mouse_pos: nalgebra_glm::Vec2,
-    mouse_scroll: f32
+    mouse_scroll: f32,
+    device_input_enabled: bool,
+    tick_events: Vec<snuff::core::InputEvent>,
+    repeated_keys: Vec<glutin::VirtualKeyCode>,
+    text_input: String,
+    text_input_enabled: Cell<bool>,
+    clipboard: snuff::core::Clipboard,
+    gamepads: std::collections::BTreeMap<snuff::core::GamepadId, snuff::core::Gamepad>,
+    gamepad_events: Vec<snuff::core::GamepadEvent>,
+    gamepad_deadzone: f32,
+    joysticks: snuff::core::joystick::Joysticks,
+    // Behind a RefCell so states can start a rebind while only holding on to the window
+    input: RefCell<snuff::core::Input>
 }
 
 impl Window {
     //---------------------------------------------------------------------------------------------------
-    pub fn new(width: u16, height: u16, title: &'static str, vsync: bool) -> Window {
+    pub fn new(title: &'static str, display_config: &snuff::core::DisplayConfig) -> Window {
         let events_loop = glutin::EventsLoop::new();
+        let (width, height) = (display_config.width, display_config.height);
+
+        // Stays hidden until it's in the right mode, so it doesn't flash up as a window first
         let wb = glutin::WindowBuilder::new()
             .with_dimensions((u32::from(width), u32::from(height)).into())
+            .with_visibility(false)
             .with_title(title);
 
         let cb = glutin::ContextBuilder::new()
             .with_srgb(true)
             .with_pixel_format(24, 8)
-            .with_vsync(vsync);
+            .with_vsync(display_config.vsync);
             
         let display = glium::Display::new(wb, cb, &events_loop).unwrap();
 
+        let monitor = match &display_config.monitor {
+            Some(name) => {
+                let monitor = Window::find_monitor(&events_loop, name);
+                if monitor.is_none() {
+                    println!("[Window] Monitor '{}' is not connected, using the primary monitor", name);
+                }
+                monitor
+            }
+            None => None,
+        };
+
+        let mut window = Window::from_device(width, height, Some(events_loop), snuff::gfx::Device::new(display));
+
+        window.windowed_position = display_config.position;
+        window.monitor = monitor;
+        window.apply_display(display_config.mode, monitor);
+
+        if let Some(display) = window.device.display() {
+            display.gl_window().window().show();
+        }
+
+        // The window is created in logical units, which can be more than one pixel each on high dpi screens
+        window.update_client_size();
+        window.resized = false;
+
+        window
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn headless(width: u16, height: u16) -> Window {
+        Window::from_device(width, height, None, snuff::gfx::Device::null())
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn from_device(
+        width: u16,
+        height: u16,
+        events_loop: Option<glutin::EventsLoop>,
+        device: snuff::gfx::Device,
+    ) -> Window {
         let default_texture =
-            snuff::gfx::Texture2D::from_data(&display, &vec![255, 255, 255, 255], 1, 1);
-        let fullscreen_quad = snuff::gfx::Mesh::create_quad(&display, false);
+            snuff::gfx::Texture2D::from_data(&device, &vec![255, 255, 255, 255], 1, 1);
+        let fullscreen_quad = snuff::gfx::Mesh::create_quad(&device, false);
 
         Window {
             client_width: width,
             client_height: height,
+            logical_width: width,
+            logical_height: height,
+            scaling_policy: snuff::core::ScalingPolicy::Fit,
+            resized: false,
+            window_mode: snuff::core::WindowMode::Windowed,
+            monitor: None,
+            windowed_size: glutin::dpi::LogicalSize::new(f64::from(width), f64::from(height)),
+            windowed_position: None,
+            display_request: Cell::new(None),
             events_loop,
-            display,
+            device,
             default_texture,
             fullscreen_quad,
             key_states: std::collections::HashMap::new(),
             mouse_states: std::collections::HashMap::new(),
             old_mouse_pos: nalgebra_glm::vec2(0.0, 0.0),
             current_mouse_pos: nalgebra_glm::vec2(0.0, 0.0),
-            mouse_scroll: 0.0
+            mouse_scroll: 0.0,
+            device_input_enabled: true,
+            tick_events: Vec::new(),
+            repeated_keys: Vec::new(),
+            text_input: String::new(),
+            text_input_enabled: Cell::new(false),
+            clipboard: snuff::core::Clipboard::new(),
+            gamepads: std::collections::BTreeMap::new(),
+            gamepad_events: Vec::new(),
+            gamepad_deadzone: 0.2,
+            joysticks: snuff::core::joystick::Joysticks::new(),
+            input: RefCell::new(snuff::core::Input::new())
         }
     }
 
     //---------------------------------------------------------------------------------------------------
-    pub fn display(&mut self) -> &mut glium::Display {
-        &mut self.display
+    pub fn device(&mut self) -> &mut snuff::gfx::Device {
+        &mut self.device
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_headless(&self) -> bool {
+        self.events_loop.is_none()
     }
 
     //---------------------------------------------------------------------------------------------------
@@ -91,88 +204,290 @@ impl Window {
         self.client_height
     }
 
+    //---------------------------------------------------------------------------------------------------
+    // The resolution the game renders at, scaled up to the window with the scaling policy
+    pub fn set_logical_size(&mut self, width: u16, height: u16) -> &mut Window {
+        self.logical_width = width.max(1);
+        self.logical_height = height.max(1);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn logical_width(&self) -> u16 {
+        self.logical_width
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn logical_height(&self) -> u16 {
+        self.logical_height
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn logical_size_f(&self) -> nalgebra_glm::Vec2 {
+        nalgebra_glm::vec2(self.logical_width as f32, self.logical_height as f32)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_scaling_policy(&mut self, scaling_policy: snuff::core::ScalingPolicy) -> &mut Window {
+        self.scaling_policy = scaling_policy;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn scaling_policy(&self) -> snuff::core::ScalingPolicy {
+        self.scaling_policy
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The part of the window the logical resolution is presented in
+    pub fn viewport(&self) -> snuff::core::Viewport {
+        self.scaling_policy.viewport(
+            (u32::from(self.logical_width), u32::from(self.logical_height)),
+            (u32::from(self.client_width), u32::from(self.client_height)),
+        )
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Looks the monitor up by name, or by its index if nothing has that name
+    fn find_monitor(events_loop: &glutin::EventsLoop, name: &str) -> Option<usize> {
+        let monitors: Vec<glutin::MonitorId> = events_loop.get_available_monitors().collect();
+
+        monitors
+            .iter()
+            .position(|monitor| monitor.get_name().as_ref().map(|n| &n[..]) == Some(name))
+            .or_else(|| name.parse().ok().filter(|index| *index < monitors.len()))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn monitors(&self) -> Vec<snuff::core::MonitorInfo> {
+        let events_loop = match &self.events_loop {
+            Some(events_loop) => events_loop,
+            None => return Vec::new(),
+        };
+
+        events_loop
+            .get_available_monitors()
+            .enumerate()
+            .map(|(index, monitor)| {
+                let dimensions = monitor.get_dimensions();
+                let position = monitor.get_position();
+
+                snuff::core::MonitorInfo {
+                    index,
+                    name: monitor.get_name().unwrap_or_else(|| format!("Monitor {}", index)),
+                    width: dimensions.width as u32,
+                    height: dimensions.height as u32,
+                    x: position.x as i32,
+                    y: position.y as i32,
+                    hidpi_factor: monitor.get_hidpi_factor(),
+                }
+            })
+            .collect()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The monitor borderless and fullscreen modes go to, None is the primary monitor
+    pub fn monitor(&self) -> Option<usize> {
+        self.monitor
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn monitor_name(&self) -> Option<String> {
+        let index = self.monitor?;
+        self.monitors().into_iter().nth(index).map(|monitor| monitor.name)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn window_mode(&self) -> snuff::core::WindowMode {
+        self.window_mode
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Where the window goes when it's windowed again, in logical pixels
+    pub fn windowed_position(&self) -> Option<(i32, i32)> {
+        self.windowed_position
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Game states only get to look at the window, so changes are queued up until the next frame
+    pub fn request_window_mode(&self, mode: snuff::core::WindowMode) {
+        let monitor = self.display_request.get().map_or(self.monitor, |request| request.monitor);
+        self.display_request.set(Some(DisplayRequest { mode, monitor }));
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn request_monitor(&self, monitor: Option<usize>) {
+        let mode = self.display_request.get().map_or(self.window_mode, |request| request.mode);
+        self.display_request.set(Some(DisplayRequest { mode, monitor }));
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_window_mode(&mut self, mode: snuff::core::WindowMode) {
+        let monitor = self.monitor;
+        self.apply_display(mode, monitor);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_monitor(&mut self, monitor: Option<usize>) {
+        let mode = self.window_mode;
+        self.apply_display(mode, monitor);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn apply_display(&mut self, mode: snuff::core::WindowMode, monitor: Option<usize>) {
+        let monitor = monitor.filter(|index| *index < self.monitors().len());
+
+        let display = match self.device.display() {
+            Some(display) => display,
+            None => {
+                self.window_mode = mode;
+                self.monitor = monitor;
+                return;
+            }
+        };
+
+        let gl_window = display.gl_window();
+        let window = gl_window.window();
+
+        let target_monitor = match monitor {
+            Some(index) => window.get_available_monitors().nth(index).unwrap(),
+            None => window.get_primary_monitor(),
+        };
+
+        // Remember where the window was, so going back to windowed puts it there again
+        let mut windowed_size = self.windowed_size;
+        if self.window_mode == snuff::core::WindowMode::Windowed {
+            if let Some(size) = window.get_inner_size() {
+                windowed_size = size;
+            }
+        }
+
+        match mode {
+            snuff::core::WindowMode::Windowed => {
+                window.set_fullscreen(None);
+                window.set_decorations(true);
+                window.set_inner_size(windowed_size);
+
+                if let Some((x, y)) = self.windowed_position {
+                    window.set_position(glutin::dpi::LogicalPosition::new(f64::from(x), f64::from(y)));
+                }
+            }
+            snuff::core::WindowMode::Borderless => {
+                let hidpi_factor = target_monitor.get_hidpi_factor();
+
+                window.set_fullscreen(None);
+                window.set_decorations(false);
+                window.set_position(target_monitor.get_position().to_logical(hidpi_factor));
+                window.set_inner_size(target_monitor.get_dimensions().to_logical(hidpi_factor));
+            }
+            snuff::core::WindowMode::Fullscreen => {
+                window.set_fullscreen(Some(target_monitor));
+            }
+        }
+
+        drop(gl_window);
+
+        self.windowed_size = windowed_size;
+        self.window_mode = mode;
+        self.monitor = monitor;
+        self.update_client_size();
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Whether the window changed size during the last call to process_events
+    pub fn was_resized(&self) -> bool {
+        self.resized
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn update_client_size(&mut self) {
+        let (width, height) = match self.device.display() {
+            Some(display) => display.get_framebuffer_dimensions(),
+            None => return,
+        };
+
+        // Minimizing reports a size of zero, keep presenting at the last real size
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        if width as u16 != self.client_width || height as u16 != self.client_height {
+            self.client_width = width as u16;
+            self.client_height = height as u16;
+            self.resized = true;
+        }
+    }
+
     //---------------------------------------------------------------------------------------------------
     fn handle_key_events(&mut self, events: Vec<glium::glutin::KeyboardInput>) {
         for evt in events.iter() {
+            // Keys winit can't name still type characters, those come in separately
             let keycode = match evt.virtual_keycode {
                 Some(kc) => kc,
-                None => glutin::VirtualKeyCode::OEM102
+                None => continue
             };
 
-            let current_pressed = evt.state == glutin::ElementState::Pressed;
-            let mut new_state = KeyState::Down;
-
-            match self.key_states.get(&keycode) {
-                Some(state) => {
-                    let prev_state = *state;
+            let pressed = evt.state == glutin::ElementState::Pressed;
+            self.handle_key(keycode, pressed);
 
-                    if (prev_state == KeyState::Up || prev_state == KeyState::Released)
-                        && current_pressed
-                    {
-                        new_state = KeyState::Pressed;
-                    }
+            // Pasting turns into typed characters, so recordings replay it without touching the clipboard
+            let paste = (keycode == glutin::VirtualKeyCode::V && evt.modifiers.ctrl)
+                || (keycode == glutin::VirtualKeyCode::Insert && evt.modifiers.shift);
 
-                    if (prev_state == KeyState::Down || prev_state == KeyState::Pressed)
-                        && !current_pressed
-                    {
-                        new_state = KeyState::Released;
+            if pressed && paste && self.text_input_enabled.get() {
+                if let Some(text) = self.clipboard.text() {
+                    for character in text.chars() {
+                        self.handle_character(character);
                     }
                 }
-                None => {
-                    new_state = if current_pressed {
-                        KeyState::Pressed
-                    } else {
-                        KeyState::Released
-                    };
-                }
             }
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn handle_character(&mut self, character: char) {
+        self.tick_events.push(snuff::core::InputEvent::Character(character));
 
-            self.key_states.insert(keycode, new_state);
+        // Editing keys like backspace and enter also arrive as characters, they're left to the key states
+        if self.text_input_enabled.get() && !character.is_control() {
+            self.text_input.push(character);
         }
     }
 
+    //---------------------------------------------------------------------------------------------------
+    fn handle_key(&mut self, keycode: glutin::VirtualKeyCode, current_pressed: bool) {
+        self.tick_events.push(snuff::core::InputEvent::Key(keycode, current_pressed));
+
+        let old_state = self.key_states.get(&keycode).cloned();
+        let new_state = KeyState::after(old_state, current_pressed);
+
+        // Held keys keep sending presses at the system's repeat rate
+        let held = old_state == Some(KeyState::Down) || old_state == Some(KeyState::Pressed);
+        if current_pressed && held && !self.repeated_keys.contains(&keycode) {
+            self.repeated_keys.push(keycode);
+        }
+
+        if new_state == KeyState::Pressed && !self.is_key_pressed(keycode) {
+            self.input.borrow_mut().capture(snuff::core::Binding::Key(keycode));
+        }
+
+        self.key_states.insert(keycode, new_state);
+    }
+
     //---------------------------------------------------------------------------------------------------
     fn handle_mouse_events(&mut self, events: Vec<MouseEvent>) {
         for evt in events.iter() {
             match evt.state {
                 MouseState::Moved => {
-                    self.old_mouse_pos = self.current_mouse_pos;
-                    self.current_mouse_pos = evt.screen_pos;
+                    self.handle_mouse_position(evt.screen_pos);
                 },
                 MouseState::Scroll => {
-                    self.mouse_scroll = evt.scroll;
+                    self.handle_mouse_scroll(evt.scroll);
                 },
                 MouseState::Pressed | MouseState::Released => {
-                    let button = &evt.button;
-                    let current_pressed = evt.state == MouseState::Pressed;
-                    let mut new_state = MouseState::Down;
-
-                    match self.mouse_states.get(button) {
-                        Some(state) => {
-                            let prev_state = *state;
-
-                            if (prev_state == MouseState::Up || prev_state == MouseState::Released)
-                                && current_pressed
-                            {
-                                new_state = MouseState::Pressed;
-                            }
-
-                            if (prev_state == MouseState::Down || prev_state == MouseState::Pressed)
-                                && !current_pressed
-                            {
-                                new_state = MouseState::Released;
-                            }
-                        }
-                        None => {
-                            new_state = if current_pressed {
-                                MouseState::Pressed
-                            } else {
-                                MouseState::Released
-                            };
-                        }
-                    }
-
-                    self.mouse_states.insert(*button, new_state);
+                    self.handle_mouse_button(evt.button, evt.state == MouseState::Pressed);
                 },
                 _ => {}
             }
@@ -180,7 +495,141 @@ impl Window {
     }
 
     //---------------------------------------------------------------------------------------------------
-    fn reset_input_states(&mut self) {
+    fn handle_mouse_position(&mut self, position: nalgebra_glm::Vec2) {
+        self.tick_events.push(snuff::core::InputEvent::MousePosition(position.x, position.y));
+        self.current_mouse_pos = position;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn handle_mouse_scroll(&mut self, scroll: f32) {
+        self.tick_events.push(snuff::core::InputEvent::MouseScroll(scroll));
+        self.mouse_scroll += scroll;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn handle_mouse_button(&mut self, button: glutin::MouseButton, current_pressed: bool) {
+        self.tick_events.push(snuff::core::InputEvent::MouseButton(button, current_pressed));
+
+        let mut new_state = MouseState::Down;
+
+        match self.mouse_states.get(&button) {
+            Some(state) => {
+                let prev_state = *state;
+
+                if (prev_state == MouseState::Up
+                    || prev_state == MouseState::Released
+                    || prev_state == MouseState::Pressed)
+                    && current_pressed
+                {
+                    new_state = MouseState::Pressed;
+                }
+
+                if (prev_state == MouseState::Down || prev_state == MouseState::Pressed)
+                    && !current_pressed
+                {
+                    new_state = MouseState::Released;
+                }
+            }
+            None => {
+                new_state = if current_pressed {
+                    MouseState::Pressed
+                } else {
+                    MouseState::Released
+                };
+            }
+        }
+
+        if new_state == MouseState::Pressed && !self.is_mouse_button_pressed(button) {
+            self.input.borrow_mut().capture(snuff::core::Binding::Mouse(button));
+        }
+
+        self.mouse_states.insert(button, new_state);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn handle_gamepad_connected(&mut self, id: snuff::core::GamepadId, connected: bool) {
+        self.tick_events.push(snuff::core::InputEvent::GamepadConnected(id, connected));
+
+        if connected {
+            if !self.gamepads.contains_key(&id) {
+                let name = format!("Gamepad {}", id.0);
+                self.gamepads.insert(id, snuff::core::Gamepad::new(id, &name, self.gamepad_deadzone));
+                self.gamepad_events.push(snuff::core::GamepadEvent::Connected(id));
+            }
+        } else if let Some(gamepad) = self.gamepads.get_mut(&id) {
+            // Kept around until the end of the tick, so its buttons can still be seen being released
+            gamepad.release_all();
+            self.gamepad_events.push(snuff::core::GamepadEvent::Disconnected(id));
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn handle_gamepad_button(&mut self, id: snuff::core::GamepadId, button: snuff::core::GamepadButton, pressed: bool) {
+        self.tick_events.push(snuff::core::InputEvent::GamepadButton(id, button, pressed));
+
+        // Input from a gamepad that was never announced still counts, virtual ones may skip connecting
+        if !self.gamepads.contains_key(&id) {
+            self.handle_gamepad_connected(id, true);
+        }
+
+        let newly_pressed = match self.gamepads.get_mut(&id) {
+            Some(gamepad) => gamepad.set_button(button, pressed),
+            None => false,
+        };
+
+        if newly_pressed {
+            self.input.borrow_mut().capture(snuff::core::Binding::GamepadButton(button));
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn handle_gamepad_axis(&mut self, id: snuff::core::GamepadId, axis: snuff::core::GamepadAxis, value: f32) {
+        self.tick_events.push(snuff::core::InputEvent::GamepadAxis(id, axis, value));
+
+        if !self.gamepads.contains_key(&id) {
+            self.handle_gamepad_connected(id, true);
+        }
+
+        let gamepad = match self.gamepads.get_mut(&id) {
+            Some(gamepad) => gamepad,
+            None => return,
+        };
+
+        gamepad.set_axis(axis, value);
+
+        for direction in &[snuff::core::AxisDirection::Negative, snuff::core::AxisDirection::Positive] {
+            if gamepad.is_axis_pressed(axis, *direction) {
+                self.input.borrow_mut().capture(snuff::core::Binding::GamepadAxis(axis, *direction));
+            }
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn inject(&mut self, evt: snuff::core::InputEvent) {
+        match evt {
+            snuff::core::InputEvent::Key(key, pressed) => self.handle_key(key, pressed),
+            snuff::core::InputEvent::MouseButton(button, pressed) => self.handle_mouse_button(button, pressed),
+            snuff::core::InputEvent::MousePosition(x, y) => self.handle_mouse_position(nalgebra_glm::vec2(x, y)),
+            snuff::core::InputEvent::MouseScroll(scroll) => self.handle_mouse_scroll(scroll),
+            snuff::core::InputEvent::Character(character) => self.handle_character(character),
+            snuff::core::InputEvent::GamepadConnected(id, connected) => self.handle_gamepad_connected(id, connected),
+            snuff::core::InputEvent::GamepadButton(id, button, pressed) => self.handle_gamepad_button(id, button, pressed),
+            snuff::core::InputEvent::GamepadAxis(id, axis, value) => self.handle_gamepad_axis(id, axis, value),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_device_input_enabled(&mut self, enabled: bool) {
+        self.device_input_enabled = enabled;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn tick_events(&self) -> &Vec<snuff::core::InputEvent> {
+        &self.tick_events
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn reset_input_states(&mut self) {
         for it in self.key_states.iter_mut() {
             let old_state = *it.1;
             *it.1 = match old_state {
@@ -199,23 +648,64 @@ impl Window {
             };
         }
 
+        for gamepad in self.gamepads.values_mut() {
+            gamepad.reset_states();
+        }
+
+        // Only gamepads that are still gone by the end of the tick are dropped
+        for evt in self.gamepad_events.iter() {
+            if let snuff::core::GamepadEvent::Disconnected(id) = evt {
+                let reconnected = self.gamepad_events.iter().rev()
+                    .take_while(|later| *later != evt)
+                    .any(|later| *later == snuff::core::GamepadEvent::Connected(*id));
+
+                if !reconnected {
+                    self.gamepads.remove(id);
+                }
+            }
+        }
+
         self.mouse_scroll = 0.0;
         self.old_mouse_pos = self.current_mouse_pos;
+        self.tick_events.clear();
+        self.gamepad_events.clear();
+        self.repeated_keys.clear();
+        self.text_input.clear();
+
+        self.input.borrow_mut().release_held(self);
     }
 
     //---------------------------------------------------------------------------------------------------
     pub fn process_events(&mut self) -> bool {
         let mut closed = false;
         let mut key_events: Vec<glium::glutin::KeyboardInput> = Vec::new();
+        let mut characters: Vec<char> = Vec::new();
         let mut mouse_events: Vec<MouseEvent> = Vec::new();
+        let mut resized = false;
+        let mut moved = None;
         let old_mouse_pos = self.old_mouse_pos;
 
-        self.reset_input_states();
+        self.resized = false;
 
-        self.events_loop.poll_events(|evt| match evt {
-            glutin::Event::WindowEvent { event, .. } => match event {
+        if let Some(request) = self.display_request.take() {
+            self.apply_display(request.mode, request.monitor);
+        }
+
+        let events_loop = match self.events_loop.as_mut() {
+            Some(events_loop) => events_loop,
+            None => return true,
+        };
+
+        events_loop.poll_events(|evt| {
+            if let glutin::Event::WindowEvent { event, .. } = evt { match event {
                 glutin::WindowEvent::CloseRequested => closed = true,
                 //---------------------------------------------------------------------------------------------------
+                glutin::WindowEvent::Resized(_) | glutin::WindowEvent::HiDpiFactorChanged(_) => resized = true,
+                //---------------------------------------------------------------------------------------------------
+                glutin::WindowEvent::Moved(position) => moved = Some(position),
+                //---------------------------------------------------------------------------------------------------
+                glutin::WindowEvent::ReceivedCharacter(character) => characters.push(character),
+                //---------------------------------------------------------------------------------------------------
                 glutin::WindowEvent::KeyboardInput {
                     input,
                     ..
@@ -255,13 +745,39 @@ impl Window {
                 }),
                 //---------------------------------------------------------------------------------------------------
                 _ => (),
-            },
-            _ => (),
+            }}
         });
 
+        if resized {
+            self.update_client_size();
+        }
+
+        let mut gamepad_events = Vec::new();
+        self.joysticks.poll(&mut gamepad_events);
+
+        // Borderless and fullscreen windows sit on the monitor, only a window's own position is worth keeping
+        if let Some(position) = moved {
+            if self.window_mode == snuff::core::WindowMode::Windowed {
+                self.windowed_position = Some((position.x as i32, position.y as i32));
+            }
+        }
+
+        // Input is coming from somewhere else, e.g. an input recording
+        if !self.device_input_enabled {
+            return !closed;
+        }
+
         self.handle_key_events(key_events);
         self.handle_mouse_events(mouse_events);
 
+        for character in characters {
+            self.handle_character(character);
+        }
+
+        for evt in gamepad_events {
+            self.inject(evt);
+        }
+
         !closed
     }
 
@@ -289,6 +805,61 @@ impl Window {
         }
     }
 
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_key_repeated(&self, key: glutin::VirtualKeyCode) -> bool {
+        self.repeated_keys.contains(&key)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // What text fields should react to, the first press and every repeat after it
+    pub fn is_key_typed(&self, key: glutin::VirtualKeyCode) -> bool {
+        self.is_key_pressed(key) || self.is_key_repeated(key)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Worked out from the modifier keys themselves, so it's the same when input is replayed
+    pub fn modifiers(&self) -> glutin::ModifiersState {
+        let either = |left, right| self.is_key_down(left) || self.is_key_down(right);
+
+        glutin::ModifiersState {
+            shift: either(glutin::VirtualKeyCode::LShift, glutin::VirtualKeyCode::RShift),
+            ctrl: either(glutin::VirtualKeyCode::LControl, glutin::VirtualKeyCode::RControl),
+            alt: either(glutin::VirtualKeyCode::LAlt, glutin::VirtualKeyCode::RAlt),
+            logo: either(glutin::VirtualKeyCode::LWin, glutin::VirtualKeyCode::RWin),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Off by default, so typing into nothing doesn't paste or collect characters
+    pub fn set_text_input_enabled(&self, enabled: bool) {
+        self.text_input_enabled.set(enabled);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_text_input_enabled(&self) -> bool {
+        self.text_input_enabled.get()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Everything typed since the last tick, including text composed with an input method and pastes
+    pub fn text_input(&self) -> &str {
+        &self.text_input
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Where the input method shows its candidates, in logical pixels from the top left of the window
+    pub fn set_ime_position(&self, position: &nalgebra_glm::Vec2) {
+        if let Some(display) = self.device.display() {
+            let spot = glutin::dpi::LogicalPosition::new(f64::from(position.x), f64::from(position.y));
+            display.gl_window().window().set_ime_spot(spot);
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn clipboard(&self) -> &snuff::core::Clipboard {
+        &self.clipboard
+    }
+
     //---------------------------------------------------------------------------------------------------
     pub fn is_mouse_button_pressed(&self, button: glutin::MouseButton) -> bool {
         match self.mouse_states.get(&button) {
@@ -313,6 +884,113 @@ impl Window {
         }
     }
 
+    //---------------------------------------------------------------------------------------------------
+    pub fn gamepads(&self) -> Vec<snuff::core::GamepadId> {
+        self.gamepads.keys().cloned().collect()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn gamepad(&self, id: snuff::core::GamepadId) -> Option<&snuff::core::Gamepad> {
+        self.gamepads.get(&id)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Connects and disconnects since the last tick
+    pub fn gamepad_events(&self) -> &[snuff::core::GamepadEvent] {
+        &self.gamepad_events
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_gamepad_deadzone(&mut self, deadzone: f32) -> &mut Window {
+        self.gamepad_deadzone = deadzone.clamp(0.0, 1.0);
+
+        for gamepad in self.gamepads.values_mut() {
+            gamepad.set_deadzone(deadzone);
+        }
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn gamepad_deadzone(&self) -> f32 {
+        self.gamepad_deadzone
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The queries below look at every connected gamepad, for games that don't care which one is used
+    pub fn is_gamepad_button_pressed(&self, button: snuff::core::GamepadButton) -> bool {
+        self.gamepads.values().any(|gamepad| gamepad.is_button_pressed(button))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_gamepad_button_released(&self, button: snuff::core::GamepadButton) -> bool {
+        self.gamepads.values().any(|gamepad| gamepad.is_button_released(button))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_gamepad_button_down(&self, button: snuff::core::GamepadButton) -> bool {
+        self.gamepads.values().any(|gamepad| gamepad.is_button_down(button))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_gamepad_axis_pressed(&self, axis: snuff::core::GamepadAxis, direction: snuff::core::AxisDirection) -> bool {
+        self.gamepads.values().any(|gamepad| gamepad.is_axis_pressed(axis, direction))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_gamepad_axis_released(&self, axis: snuff::core::GamepadAxis, direction: snuff::core::AxisDirection) -> bool {
+        self.gamepads.values().any(|gamepad| gamepad.is_axis_released(axis, direction))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_gamepad_axis_down(&self, axis: snuff::core::GamepadAxis, direction: snuff::core::AxisDirection) -> bool {
+        self.gamepads.values().any(|gamepad| gamepad.is_axis_down(axis, direction))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Whichever gamepad pushes the axis furthest wins
+    pub fn gamepad_axis(&self, axis: snuff::core::GamepadAxis) -> f32 {
+        self.gamepads
+            .values()
+            .map(|gamepad| gamepad.axis(axis))
+            .fold(0.0, |strongest: f32, value| if value.abs() > strongest.abs() { value } else { strongest })
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn input(&self) -> Ref<'_, snuff::core::Input> {
+        self.input.borrow()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn input_mut(&self) -> RefMut<'_, snuff::core::Input> {
+        self.input.borrow_mut()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_input(&mut self, input: snuff::core::Input) {
+        self.input = RefCell::new(input);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_action_pressed(&self, action: &str) -> bool {
+        self.input.borrow().is_pressed(self, action)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_action_released(&self, action: &str) -> bool {
+        self.input.borrow().is_released(self, action)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_action_down(&self, action: &str) -> bool {
+        self.input.borrow().is_down(self, action)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn action_axis(&self, axis: &str) -> f32 {
+        self.input.borrow().axis(self, axis)
+    }
+
     //---------------------------------------------------------------------------------------------------
     fn absolute_to_clip_space(&self, p : nalgebra_glm::Vec2) -> nalgebra_glm::Vec2 {
         let mut clip_space = nalgebra_glm::vec2(p.x / self.client_width as f32, 1.0 - (p.y / self.client_height as f32));
@@ -325,6 +1003,16 @@ impl Window {
         self.current_mouse_pos
     }
 
+    //---------------------------------------------------------------------------------------------------
+    // The mouse in logical pixels from the top left, None while it's over the letterbox bars
+    pub fn mouse_logical_position(&self) -> Option<nalgebra_glm::Vec2> {
+        self.viewport().window_to_logical(
+            &self.current_mouse_pos,
+            u32::from(self.client_height),
+            &self.logical_size_f(),
+        )
+    }
+
     //---------------------------------------------------------------------------------------------------
     pub fn mouse_relative_position(&self) -> nalgebra_glm::Vec2 {
         self.absolute_to_clip_space(self.current_mouse_pos)
@@ -348,7 +1036,7 @@ impl Window {
     //---------------------------------------------------------------------------------------------------
     pub fn begin_frame(&mut self, time: f32) -> CommandBuffer {
         snuff::gfx::CommandBuffer::new(
-            &self.display,
+            &self.device,
             &self.default_texture,
             &self.fullscreen_quad,
             time,
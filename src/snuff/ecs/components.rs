@@ -0,0 +1,139 @@
+use crate::snuff::core;
+use crate::snuff::ecs;
+use crate::snuff::gfx;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Sprites share their texture and shader, so many entities can use the same ones
+#[derive(Clone)]
+pub struct Sprite {
+    pub texture: Rc<gfx::Texture2D>,
+    pub shader: Rc<RefCell<gfx::ShaderProgram>>,
+    // Lower layers are drawn first
+    pub layer: i32,
+    pub visible: bool,
+    // The part of the texture to show, in UVs
+    pub uv_rect: core::Rect,
+    pub tint: nalgebra_glm::Vec4,
+    pub blend_mode: gfx::BlendMode,
+}
+
+impl Sprite {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(texture: &Rc<gfx::Texture2D>, shader: &Rc<RefCell<gfx::ShaderProgram>>, layer: i32) -> Sprite {
+        Sprite {
+            texture: texture.clone(),
+            shader: shader.clone(),
+            layer,
+            visible: true,
+            uv_rect: core::Rect::new(&nalgebra_glm::vec2(0.0, 0.0), &nalgebra_glm::vec2(1.0, 1.0)),
+            tint: nalgebra_glm::vec4(1.0, 1.0, 1.0, 1.0),
+            blend_mode: gfx::BlendMode::Alpha,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Velocity {
+    pub linear: nalgebra_glm::Vec2,
+}
+
+impl Velocity {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(x: f32, y: f32) -> Velocity {
+        Velocity {
+            linear: nalgebra_glm::vec2(x, y),
+        }
+    }
+}
+
+// Entities with this are drawn between where they were on the last two ticks,
+// so they still move smoothly when the display runs faster than the simulation
+#[derive(Clone, Copy, Debug)]
+pub struct Interpolated {
+    previous: Option<nalgebra_glm::Mat4>,
+}
+
+impl Interpolated {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> Interpolated {
+        Interpolated { previous: None }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Draws the entity where it is for the next frame, e.g. after teleporting it
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn store(&mut self, local_to_world: &nalgebra_glm::Mat4) {
+        self.previous = Some(*local_to_world);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Blends the matrices as a whole, which is close enough for the little that changes in a tick.
+    // A flip would shrink the sprite to nothing halfway, so that snaps instead
+    pub fn local_to_world(&self, current: &nalgebra_glm::Mat4, alpha: f32) -> nalgebra_glm::Mat4 {
+        let previous = match &self.previous {
+            Some(previous) => previous,
+            None => return *current,
+        };
+
+        let flipped = (nalgebra_glm::determinant(previous) < 0.0) != (nalgebra_glm::determinant(current) < 0.0);
+
+        if flipped {
+            *current
+        } else {
+            previous + (current - previous) * alpha
+        }
+    }
+}
+
+// Parent and Children are kept in sync by World::attach and World::detach, so they can't be built by hand
+#[derive(Clone, Copy, Debug)]
+pub struct Parent {
+    entity: ecs::Entity,
+}
+
+impl Parent {
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn new(entity: ecs::Entity) -> Parent {
+        Parent { entity }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn entity(&self) -> ecs::Entity {
+        self.entity
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Children {
+    entities: Vec<ecs::Entity>,
+}
+
+impl Children {
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn new() -> Children {
+        Children { entities: Vec::new() }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn add(&mut self, entity: ecs::Entity) {
+        if !self.entities.contains(&entity) {
+            self.entities.push(entity);
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn remove(&mut self, entity: ecs::Entity) {
+        self.entities.retain(|child| *child != entity);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn entities(&self) -> &[ecs::Entity] {
+        &self.entities
+    }
+}
@@ -0,0 +1,23 @@
+// The generation tells a destroyed entity apart from a new one that reuses its index
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn new(index: u32, generation: u32) -> Entity {
+        Entity { index, generation }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
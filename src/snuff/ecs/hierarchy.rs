@@ -0,0 +1,220 @@
+use crate::snuff::core;
+use crate::snuff::ecs;
+
+// What stays put when an entity changes parents
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum KeepTransform {
+    // The local values are kept, so the entity jumps to the same offset from its new parent
+    Local,
+    // The local values are recalculated, so the entity stays where it is on screen
+    World,
+}
+
+impl ecs::World {
+    //---------------------------------------------------------------------------------------------------
+    pub fn parent(&self, entity: ecs::Entity) -> Option<ecs::Entity> {
+        self.storage::<ecs::Parent>().get(entity).map(|parent| parent.entity())
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn children(&self, entity: ecs::Entity) -> Vec<ecs::Entity> {
+        match self.storage::<ecs::Children>().get(entity) {
+            Some(children) => children.entities().to_vec(),
+            None => Vec::new(),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn is_ancestor(&self, ancestor: ecs::Entity, entity: ecs::Entity) -> bool {
+        let mut current = Some(entity);
+
+        while let Some(entity) = current {
+            if entity == ancestor {
+                return true;
+            }
+
+            current = self.parent(entity);
+        }
+
+        false
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn parent_to_world_of(&self, parent: Option<ecs::Entity>) -> nalgebra_glm::Mat4 {
+        let transform = parent.and_then(|parent| {
+            self.storage_mut::<core::Transform>().get_mut(parent).map(|transform| transform.child_to_world())
+        });
+
+        transform.unwrap_or_else(nalgebra_glm::identity)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Moves the child's transform into a new parent space, keeping either its local or its world values
+    fn reparent_transform(&self, child: ecs::Entity, parent: Option<ecs::Entity>, keep: KeepTransform) {
+        let parent_to_world = self.parent_to_world_of(parent);
+        let mut transforms = self.storage_mut::<core::Transform>();
+
+        if let Some(transform) = transforms.get_mut(child) {
+            let child_to_world = transform.child_to_world();
+            transform.set_parent_to_world(&parent_to_world);
+
+            if keep == KeepTransform::World {
+                transform.set_child_to_world(&child_to_world);
+            }
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn attach(&mut self, child: ecs::Entity, parent: ecs::Entity, keep: KeepTransform) -> &mut ecs::World {
+        if !self.is_alive(child) || !self.is_alive(parent) {
+            println!("[World] Can't attach {:?} to {:?}, one of them is dead", child, parent);
+            return self;
+        }
+
+        if self.is_ancestor(child, parent) {
+            println!("[World] Can't attach {:?} to {:?}, that would make a cycle", child, parent);
+            return self;
+        }
+
+        if let Some(old_parent) = self.parent(child) {
+            if let Some(children) = self.storage_mut::<ecs::Children>().get_mut(old_parent) {
+                children.remove(child);
+            }
+        }
+
+        self.reparent_transform(child, Some(parent), keep);
+
+        if !self.has::<ecs::Children>(parent) {
+            self.add(parent, ecs::Children::new());
+        }
+
+        self.storage_mut::<ecs::Children>().get_mut(parent).unwrap().add(child);
+        self.add(child, ecs::Parent::new(parent));
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn detach(&mut self, child: ecs::Entity, keep: KeepTransform) -> &mut ecs::World {
+        let parent = match self.remove::<ecs::Parent>(child) {
+            Some(parent) => parent.entity(),
+            None => return self,
+        };
+
+        if let Some(children) = self.storage_mut::<ecs::Children>().get_mut(parent) {
+            children.remove(child);
+        }
+
+        self.reparent_transform(child, None, keep);
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snuff::ecs::systems;
+
+    fn spawn_at(world: &mut ecs::World, x: f32, y: f32) -> ecs::Entity {
+        let mut transform = core::Transform::new();
+        transform.set_translation_2d_f(x, y);
+
+        let entity = world.create();
+        world.add(entity, transform);
+
+        entity
+    }
+
+    fn translations(world: &ecs::World, entity: ecs::Entity) -> (nalgebra_glm::Vec2, nalgebra_glm::Vec2) {
+        let mut transforms = world.storage_mut::<core::Transform>();
+        let transform = transforms.get_mut(entity).unwrap();
+
+        (transform.translation_2d(), transform.world_translation_2d())
+    }
+
+    #[test]
+    fn attaching_keeps_local_or_world_values() {
+        let mut world = ecs::World::new();
+        let parent = spawn_at(&mut world, 100.0, 50.0);
+        let local = spawn_at(&mut world, 10.0, 0.0);
+        let kept = spawn_at(&mut world, 10.0, 0.0);
+
+        world
+            .attach(local, parent, KeepTransform::Local)
+            .attach(kept, parent, KeepTransform::World);
+
+        assert_eq!(translations(&world, local), (nalgebra_glm::vec2(10.0, 0.0), nalgebra_glm::vec2(110.0, 50.0)));
+        assert_eq!(translations(&world, kept), (nalgebra_glm::vec2(-90.0, -50.0), nalgebra_glm::vec2(10.0, 0.0)));
+        assert_eq!(world.parent(local), Some(parent));
+        assert_eq!(world.children(parent), vec![local, kept]);
+    }
+
+    #[test]
+    fn detaching_keeps_local_or_world_values() {
+        let mut world = ecs::World::new();
+        let parent = spawn_at(&mut world, 100.0, 50.0);
+        let local = spawn_at(&mut world, 10.0, 0.0);
+        let kept = spawn_at(&mut world, 10.0, 0.0);
+
+        world
+            .attach(local, parent, KeepTransform::Local)
+            .attach(kept, parent, KeepTransform::Local)
+            .detach(local, KeepTransform::Local)
+            .detach(kept, KeepTransform::World);
+
+        assert_eq!(translations(&world, local), (nalgebra_glm::vec2(10.0, 0.0), nalgebra_glm::vec2(10.0, 0.0)));
+        assert_eq!(translations(&world, kept), (nalgebra_glm::vec2(110.0, 50.0), nalgebra_glm::vec2(110.0, 50.0)));
+        assert_eq!(world.parent(kept), None);
+        assert!(world.children(parent).is_empty());
+    }
+
+    #[test]
+    fn detaching_keeps_a_flipped_parents_mirroring() {
+        let mut world = ecs::World::new();
+        let parent = spawn_at(&mut world, 0.0, 0.0);
+        let child = spawn_at(&mut world, 10.0, 0.0);
+
+        world.storage_mut::<core::Transform>().get_mut(parent).unwrap().set_scale_2d_f(-2.0, 1.0);
+        world
+            .attach(child, parent, KeepTransform::Local)
+            .detach(child, KeepTransform::World);
+
+        let mut transforms = world.storage_mut::<core::Transform>();
+        let child = transforms.get_mut(child).unwrap();
+
+        assert_eq!(child.translation_2d(), nalgebra_glm::vec2(-20.0, 0.0));
+        assert_eq!(child.scale_2d(), nalgebra_glm::vec2(-2.0, 1.0));
+    }
+
+    #[test]
+    fn children_follow_their_parent_once_propagated() {
+        let mut world = ecs::World::new();
+        let parent = spawn_at(&mut world, 0.0, 0.0);
+        let child = spawn_at(&mut world, 10.0, 0.0);
+        let grandchild = spawn_at(&mut world, 0.0, 5.0);
+
+        world
+            .attach(child, parent, KeepTransform::Local)
+            .attach(grandchild, child, KeepTransform::Local);
+
+        world.storage_mut::<core::Transform>().get_mut(parent).unwrap().set_translation_2d_f(100.0, 0.0);
+        systems::propagate_transforms(&world);
+
+        assert_eq!(translations(&world, grandchild).1, nalgebra_glm::vec2(110.0, 5.0));
+    }
+
+    #[test]
+    fn refuses_to_make_cycles() {
+        let mut world = ecs::World::new();
+        let parent = spawn_at(&mut world, 0.0, 0.0);
+        let child = spawn_at(&mut world, 0.0, 0.0);
+
+        world
+            .attach(child, parent, KeepTransform::Local)
+            .attach(parent, child, KeepTransform::Local);
+
+        assert_eq!(world.parent(parent), None);
+        assert_eq!(world.parent(child), Some(parent));
+    }
+}
@@ -0,0 +1,11 @@
+mod components;
+mod entity;
+mod hierarchy;
+mod storage;
+mod world;
+pub mod systems;
+
+pub use {
+    components::Children, components::Interpolated, components::Parent, components::Sprite, components::Velocity, entity::Entity,
+    hierarchy::KeepTransform, storage::ComponentStorage, world::World
+};
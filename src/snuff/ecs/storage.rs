@@ -0,0 +1,167 @@
+use crate::snuff::ecs;
+
+use std::any::Any;
+use std::cell::RefCell;
+
+// Components live in a slot per entity index, which keeps lookups cheap for the entity counts we have
+pub struct ComponentStorage<T> {
+    slots: Vec<Option<(u32, T)>>,
+}
+
+impl<T> ComponentStorage<T> {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> ComponentStorage<T> {
+        ComponentStorage { slots: Vec::new() }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn insert(&mut self, entity: ecs::Entity, component: T) {
+        let index = entity.index() as usize;
+
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+
+        self.slots[index] = Some((entity.generation(), component));
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn remove(&mut self, entity: ecs::Entity) -> Option<T> {
+        if !self.contains(entity) {
+            return None;
+        }
+
+        self.slots[entity.index() as usize].take().map(|(_, component)| component)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn contains(&self, entity: ecs::Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn get(&self, entity: ecs::Entity) -> Option<&T> {
+        match self.slots.get(entity.index() as usize) {
+            Some(Some((generation, component))) if *generation == entity.generation() => Some(component),
+            _ => None,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn get_mut(&mut self, entity: ecs::Entity) -> Option<&mut T> {
+        match self.slots.get_mut(entity.index() as usize) {
+            Some(Some((generation, component))) if *generation == entity.generation() => Some(component),
+            _ => None,
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(|slot| slot.is_none())
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Iterates in entity index order
+    pub fn iter(&self) -> impl Iterator<Item = (ecs::Entity, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref()
+                .map(|(generation, component)| (ecs::Entity::new(index as u32, *generation), component))
+        })
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (ecs::Entity, &mut T)> {
+        self.slots.iter_mut().enumerate().filter_map(|(index, slot)| {
+            slot.as_mut()
+                .map(|(generation, component)| (ecs::Entity::new(index as u32, *generation), component))
+        })
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Every entity that has a component in both storages
+    pub fn join<'b, U>(&'b self, other: &'b ComponentStorage<U>) -> impl Iterator<Item = (ecs::Entity, &'b T, &'b U)> {
+        self.iter()
+            .filter_map(move |(entity, component)| other.get(entity).map(|other| (entity, component, other)))
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn join_mut<'b, U>(
+        &'b mut self,
+        other: &'b ComponentStorage<U>,
+    ) -> impl Iterator<Item = (ecs::Entity, &'b mut T, &'b U)> {
+        self.iter_mut()
+            .filter_map(move |(entity, component)| other.get(entity).map(|other| (entity, component, other)))
+    }
+}
+
+// Lets the world clean up after destroyed entities without knowing the component types
+pub(crate) trait AnyStorage {
+    fn remove_entity(&self, entity: ecs::Entity);
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: 'static> AnyStorage for RefCell<ComponentStorage<T>> {
+    //---------------------------------------------------------------------------------------------------
+    fn remove_entity(&self, entity: ecs::Entity) {
+        self.borrow_mut().remove(entity);
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn components_belong_to_one_generation() {
+        let mut storage = ComponentStorage::new();
+        storage.insert(ecs::Entity::new(2, 0), "old");
+
+        let reused = ecs::Entity::new(2, 1);
+        assert!(!storage.contains(reused));
+        assert_eq!(storage.remove(reused), None);
+
+        storage.insert(reused, "new");
+        assert_eq!(storage.get(reused), Some(&"new"));
+        assert_eq!(storage.get(ecs::Entity::new(2, 0)), None);
+        assert_eq!(storage.len(), 1);
+    }
+
+    #[test]
+    fn iterates_in_index_order() {
+        let mut storage = ComponentStorage::new();
+        storage.insert(ecs::Entity::new(5, 0), 'c');
+        storage.insert(ecs::Entity::new(0, 3), 'a');
+        storage.insert(ecs::Entity::new(2, 1), 'b');
+
+        let entities: Vec<(u32, u32, char)> =
+            storage.iter().map(|(entity, c)| (entity.index(), entity.generation(), *c)).collect();
+        assert_eq!(entities, vec![(0, 3, 'a'), (2, 1, 'b'), (5, 0, 'c')]);
+    }
+
+    #[test]
+    fn join_mut_changes_only_the_joined_components() {
+        let mut values = ComponentStorage::new();
+        let mut scales = ComponentStorage::new();
+        let (a, b) = (ecs::Entity::new(0, 0), ecs::Entity::new(1, 0));
+        values.insert(a, 1.0);
+        values.insert(b, 1.0);
+        scales.insert(b, 3.0);
+
+        for (_, value, scale) in values.join_mut(&scales) {
+            *value *= *scale;
+        }
+
+        assert_eq!(values.get(a), Some(&1.0));
+        assert_eq!(values.get(b), Some(&3.0));
+    }
+}
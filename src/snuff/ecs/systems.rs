@@ -0,0 +1,113 @@
+use crate::snuff::core;
+use crate::snuff::ecs;
+use crate::snuff::gfx;
+
+//---------------------------------------------------------------------------------------------------
+pub fn apply_velocity(world: &ecs::World, dt: f32) {
+    let _scope = core::profiler::scope("systems::apply_velocity");
+
+    let mut transforms = world.storage_mut::<core::Transform>();
+    let velocities = world.storage::<ecs::Velocity>();
+
+    for (_, transform, velocity) in transforms.join_mut(&velocities) {
+        transform.translate_2d(&(velocity.linear * dt));
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+// Run at the start of a tick, before anything moves
+pub fn store_previous_transforms(world: &ecs::World) {
+    let _scope = core::profiler::scope("systems::store_previous_transforms");
+
+    let mut transforms = world.storage_mut::<core::Transform>();
+    let mut interpolated = world.storage_mut::<ecs::Interpolated>();
+
+    for (entity, interpolated) in interpolated.iter_mut() {
+        if let Some(transform) = transforms.get_mut(entity) {
+            interpolated.store(&transform.local_to_world());
+        }
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+// Draws every visible sprite by layer, entities on the same layer keep their creation order
+// unless the batch is set to sort by texture. Interpolated entities are drawn alpha of the way into the last tick
+pub fn draw_sprites(
+    world: &ecs::World,
+    command_buffer: &gfx::CommandBuffer,
+    target: &mut gfx::RenderTarget,
+    camera: &mut core::Camera,
+    batch: &mut gfx::SpriteBatch,
+    alpha: f32,
+) {
+    let _scope = core::profiler::scope("systems::draw_sprites");
+
+    let mut transforms = world.storage_mut::<core::Transform>();
+    let sprites = world.storage::<ecs::Sprite>();
+    let interpolated = world.storage::<ecs::Interpolated>();
+
+    for (entity, sprite) in sprites.iter().filter(|(_, sprite)| sprite.visible) {
+        let transform = match transforms.get_mut(entity) {
+            Some(transform) => transform,
+            None => continue,
+        };
+
+        let local_to_world = match interpolated.get(entity) {
+            Some(interpolated) => interpolated.local_to_world(&transform.local_to_world(), alpha),
+            None => transform.local_to_world(),
+        };
+
+        batch
+            .add(&sprite.texture, &sprite.shader, &local_to_world, sprite.layer)
+            .set_uv_rect(&sprite.uv_rect)
+            .set_tint(&sprite.tint)
+            .set_blend_mode(sprite.blend_mode);
+    }
+
+    command_buffer.draw_sprite_batch_into_target(target, camera, batch);
+}
+
+//---------------------------------------------------------------------------------------------------
+fn propagate_to_children(
+    transforms: &mut ecs::ComponentStorage<core::Transform>,
+    hierarchy: &ecs::ComponentStorage<ecs::Children>,
+    entity: ecs::Entity,
+    parent_to_world: &nalgebra_glm::Mat4,
+) {
+    let child_to_world = match transforms.get_mut(entity) {
+        // Only marks the transform dirty when the parent actually moved, which then cascades down
+        Some(transform) => transform.set_parent_to_world(parent_to_world).child_to_world(),
+        None => *parent_to_world,
+    };
+
+    if let Some(children) = hierarchy.get(entity) {
+        for child in children.entities() {
+            propagate_to_children(transforms, hierarchy, *child, &child_to_world);
+        }
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+// Run after everything that moves entities and before drawing, so children are drawn where their parents are
+pub fn propagate_transforms(world: &ecs::World) {
+    let _scope = core::profiler::scope("systems::propagate_transforms");
+
+    let mut transforms = world.storage_mut::<core::Transform>();
+    let parents = world.storage::<ecs::Parent>();
+    let hierarchy = world.storage::<ecs::Children>();
+
+    for (root, children) in hierarchy.iter() {
+        if parents.contains(root) {
+            continue;
+        }
+
+        let child_to_world = match transforms.get_mut(root) {
+            Some(transform) => transform.child_to_world(),
+            None => nalgebra_glm::identity(),
+        };
+
+        for child in children.entities() {
+            propagate_to_children(&mut transforms, &hierarchy, *child, &child_to_world);
+        }
+    }
+}
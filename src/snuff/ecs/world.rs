@@ -0,0 +1,257 @@
+use crate::snuff::core;
+use crate::snuff::ecs;
+use crate::snuff::ecs::storage::AnyStorage;
+
+use std::any::TypeId;
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+
+// Storages sit behind a RefCell each, so systems can borrow several of them mutably at once
+pub struct World {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free_indices: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+}
+
+impl World {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> World {
+        let mut world = World {
+            generations: Vec::new(),
+            alive: Vec::new(),
+            free_indices: Vec::new(),
+            storages: HashMap::new(),
+        };
+
+        world
+            .register::<core::Transform>()
+            .register::<ecs::Sprite>()
+            .register::<ecs::Velocity>()
+            .register::<ecs::Interpolated>()
+            .register::<ecs::Parent>()
+            .register::<ecs::Children>();
+
+        world
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Components have to be registered before they can be queried, adding one registers it as well
+    pub fn register<T: 'static>(&mut self) -> &mut World {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(RefCell::new(ecs::ComponentStorage::<T>::new())));
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn create(&mut self) -> ecs::Entity {
+        match self.free_indices.pop() {
+            Some(index) => {
+                self.alive[index as usize] = true;
+                ecs::Entity::new(index, self.generations[index as usize])
+            }
+            None => {
+                self.generations.push(0);
+                self.alive.push(true);
+                ecs::Entity::new(self.generations.len() as u32 - 1, 0)
+            }
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Children go down with their parent, like a lantern with whoever carries it
+    pub fn destroy(&mut self, entity: ecs::Entity) {
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        for child in self.children(entity) {
+            self.destroy(child);
+        }
+
+        self.detach(entity, ecs::KeepTransform::Local);
+
+        for storage in self.storages.values() {
+            storage.remove_entity(entity);
+        }
+
+        let index = entity.index() as usize;
+        self.alive[index] = false;
+        self.generations[index] += 1;
+        self.free_indices.push(entity.index());
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_alive(&self, entity: ecs::Entity) -> bool {
+        let index = entity.index() as usize;
+
+        index < self.alive.len() && self.alive[index] && self.generations[index] == entity.generation()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn entity_count(&self) -> usize {
+        self.alive.iter().filter(|alive| **alive).count()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn add<T: 'static>(&mut self, entity: ecs::Entity, component: T) -> &mut World {
+        if !self.is_alive(entity) {
+            println!("[World] Tried to add a component to dead entity {:?}", entity);
+            return self;
+        }
+
+        self.register::<T>();
+        self.storage_mut::<T>().insert(entity, component);
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn remove<T: 'static>(&mut self, entity: ecs::Entity) -> Option<T> {
+        self.storage_mut::<T>().remove(entity)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn typed_storage<T: 'static>(&self) -> &RefCell<ecs::ComponentStorage<T>> {
+        match self.storages.get(&TypeId::of::<T>()) {
+            Some(storage) => storage.as_any().downcast_ref().unwrap(),
+            None => panic!("[World] Component {} was never registered", std::any::type_name::<T>()),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn storage<T: 'static>(&self) -> Ref<'_, ecs::ComponentStorage<T>> {
+        self.typed_storage::<T>().borrow()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn storage_mut<T: 'static>(&self) -> RefMut<'_, ecs::ComponentStorage<T>> {
+        self.typed_storage::<T>().borrow_mut()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn has<T: 'static>(&self, entity: ecs::Entity) -> bool {
+        self.storage::<T>().contains(entity)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Entities with a T, in entity index order
+    pub fn query<T: 'static>(&self) -> Vec<ecs::Entity> {
+        self.storage::<T>().iter().map(|(entity, _)| entity).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+
+    #[test]
+    fn reused_indices_reject_stale_entities() {
+        let mut world = World::new();
+        let old = world.create();
+        world.add(old, Health(3));
+        world.destroy(old);
+
+        let new = world.create();
+        world.add(new, Health(5));
+
+        assert_eq!(new.index(), old.index());
+        assert_ne!(new.generation(), old.generation());
+        assert!(!world.is_alive(old));
+        assert!(!world.has::<Health>(old));
+        assert_eq!(world.storage::<Health>().get(new), Some(&Health(5)));
+        assert_eq!(world.remove::<Health>(old), None);
+        assert_eq!(world.entity_count(), 1);
+    }
+
+    #[test]
+    fn adding_to_a_dead_entity_does_nothing() {
+        let mut world = World::new();
+        world.register::<Health>().register::<Name>();
+        let entity = world.create();
+        world.destroy(entity);
+        world.add(entity, Health(1));
+
+        assert!(world.query::<Health>().is_empty());
+
+        // Nor does it bring the entity back, or touch whoever reuses its index
+        let new = world.create();
+        world.add(entity, Name("ghost"));
+        assert!(!world.has::<Name>(new));
+    }
+
+    #[test]
+    fn destroying_removes_every_component() {
+        let mut world = World::new();
+        let entity = world.create();
+        let other = world.create();
+        world
+            .add(entity, Health(1))
+            .add(entity, Name("lantern"))
+            .add(entity, core::Transform::new())
+            .add(other, Health(2));
+
+        world.destroy(entity);
+
+        assert!(world.storage::<Health>().get(entity).is_none());
+        assert!(world.storage::<Name>().is_empty());
+        assert!(world.storage::<core::Transform>().is_empty());
+        assert_eq!(world.query::<Health>(), vec![other]);
+    }
+
+    #[test]
+    fn joins_only_visit_entities_with_both_components() {
+        let mut world = World::new();
+        let entities: Vec<ecs::Entity> = (0..4).map(|_| world.create()).collect();
+        world
+            .add(entities[0], Health(10))
+            .add(entities[1], Health(20))
+            .add(entities[1], Name("b"))
+            .add(entities[2], Name("c"))
+            .add(entities[3], Name("d"))
+            .add(entities[3], Health(40));
+
+        let health = world.storage::<Health>();
+        let names = world.storage::<Name>();
+        let joined: Vec<(ecs::Entity, u32, &str)> =
+            health.join(&names).map(|(entity, health, name)| (entity, health.0, name.0)).collect();
+
+        assert_eq!(joined, vec![(entities[1], 20, "b"), (entities[3], 40, "d")]);
+    }
+
+    #[test]
+    fn different_storages_can_be_borrowed_mutably_at_once() {
+        let mut world = World::new();
+        let entity = world.create();
+        world.add(entity, Health(1)).add(entity, Name("before"));
+
+        {
+            let mut health = world.storage_mut::<Health>();
+            let mut names = world.storage_mut::<Name>();
+
+            health.get_mut(entity).unwrap().0 += 1;
+            names.get_mut(entity).unwrap().0 = "after";
+        }
+
+        assert_eq!(world.storage::<Health>().get(entity), Some(&Health(2)));
+        assert_eq!(world.storage::<Name>().get(entity), Some(&Name("after")));
+    }
+
+    #[test]
+    #[should_panic]
+    fn the_same_storage_cannot_be_borrowed_mutably_twice() {
+        let mut world = World::new();
+        world.register::<Health>();
+
+        let _first = world.storage_mut::<Health>();
+        let _second = world.storage_mut::<Health>();
+    }
+}
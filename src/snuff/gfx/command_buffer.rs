@@ -3,6 +3,7 @@ use crate::snuff;
 use glium;
 use glium::Surface;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum BlendMode {
     Additive,
     Alpha,
@@ -10,43 +11,133 @@ pub enum BlendMode {
     None
 }
 
+enum Output<'a> {
+    Frame(glium::Frame),
+    Texture(glium::framebuffer::SimpleFrameBuffer<'a>),
+    Null,
+}
+
 pub struct CommandBuffer<'a> {
-    frame: glium::Frame,
-    display: &'a glium::Display,
+    output: Output<'a>,
+    device: &'a snuff::gfx::Device,
     default_texture: &'a snuff::gfx::Texture2D,
     fullscreen_quad: &'a snuff::gfx::Mesh,
     time: f32,
     current_blend_mode: BlendMode,
-    blend_color: nalgebra_glm::Vec4
+    blend_color: nalgebra_glm::Vec4,
+    viewport: Option<snuff::core::Viewport>
 }
 
 pub struct RenderTarget<'a> {
-    framebuffer: glium::framebuffer::MultiOutputFrameBuffer<'a>,
+    framebuffer: Option<glium::framebuffer::MultiOutputFrameBuffer<'a>>,
     textures: Vec<&'a snuff::gfx::Texture2D>,
 }
 
 impl<'a> CommandBuffer<'a> {
     //---------------------------------------------------------------------------------------------------
     pub fn new(
-        display: &'a glium::Display,
+        device: &'a snuff::gfx::Device,
         default_texture: &'a snuff::gfx::Texture2D,
         fullscreen_quad: &'a snuff::gfx::Mesh,
         time: f32,
     ) -> CommandBuffer<'a> {
-        let mut target = display.draw();
-        target.clear_color_srgb(0.1, 0.33, 1.0, 1.0);
+        let output = match device.display() {
+            Some(display) => {
+                let mut target = display.draw();
+                target.clear_color_srgb(0.1, 0.33, 1.0, 1.0);
+                Output::Frame(target)
+            }
+            None => Output::Null,
+        };
 
         CommandBuffer {
-            frame: target,
-            display,
+            output,
+            device,
             default_texture,
             fullscreen_quad,
             time,
             current_blend_mode: BlendMode::Alpha,
-            blend_color: nalgebra_glm::vec4(1.0, 1.0, 1.0, 1.0)
+            blend_color: nalgebra_glm::vec4(1.0, 1.0, 1.0, 1.0),
+            viewport: None
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn offscreen<'b>(&'b self, texture: &'b snuff::gfx::Texture2D) -> CommandBuffer<'b> {
+        let output = match self.device.display() {
+            Some(display) => {
+                let mut target =
+                    glium::framebuffer::SimpleFrameBuffer::new(display, texture.texture()).unwrap();
+                target.clear_color_srgb(0.1, 0.33, 1.0, 1.0);
+                Output::Texture(target)
+            }
+            None => Output::Null,
+        };
+
+        CommandBuffer {
+            output,
+            device: self.device,
+            default_texture: self.default_texture,
+            fullscreen_quad: self.fullscreen_quad,
+            time: self.time,
+            current_blend_mode: BlendMode::Alpha,
+            blend_color: nalgebra_glm::vec4(1.0, 1.0, 1.0, 1.0),
+            viewport: None
         }
     }
 
+    //---------------------------------------------------------------------------------------------------
+    pub fn device(&self) -> &'a snuff::gfx::Device {
+        self.device
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn dimensions(&self) -> (u32, u32) {
+        match &self.output {
+            Output::Frame(frame) => frame.get_dimensions(),
+            Output::Texture(framebuffer) => framebuffer.get_dimensions(),
+            Output::Null => (0, 0),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn is_null(&self) -> bool {
+        matches!(self.output, Output::Null)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn draw_to_output<U: glium::uniforms::Uniforms>(
+        output: &mut Output,
+        mesh: &snuff::gfx::Mesh,
+        program: &glium::Program,
+        uniforms: &U,
+        blend_mode: &BlendMode,
+        viewport: &Option<snuff::core::Viewport>,
+    ) {
+        let params = CommandBuffer::create_draw_params(blend_mode, viewport);
+        snuff::core::profiler::count_draw_call();
+
+        let result = match output {
+            Output::Frame(frame) => frame.draw(
+                mesh.vertex_buffer(),
+                mesh.index_buffer(),
+                program,
+                uniforms,
+                &params,
+            ),
+            Output::Texture(framebuffer) => framebuffer.draw(
+                mesh.vertex_buffer(),
+                mesh.index_buffer(),
+                program,
+                uniforms,
+                &params,
+            ),
+            Output::Null => Ok(()),
+        };
+
+        result.unwrap();
+    }
+
     //---------------------------------------------------------------------------------------------------
     pub fn create_sampler_uniform(
         &self,
@@ -75,6 +166,16 @@ impl<'a> CommandBuffer<'a> {
             "[CommandBuffer] Cannot create a render target with more than 4 output values"
         );
 
+        let display = match self.device.display() {
+            Some(display) => display,
+            None => {
+                return RenderTarget {
+                    framebuffer: None,
+                    textures,
+                }
+            }
+        };
+
         let mut outputs: Vec<(&'a str, &glium::texture::SrgbTexture2d)> = Vec::new();
 
         let names = vec!["output0", "output1", "output2", "output3"];
@@ -84,23 +185,46 @@ impl<'a> CommandBuffer<'a> {
         }
 
         RenderTarget {
-            framebuffer: glium::framebuffer::MultiOutputFrameBuffer::new(
-                self.display,
-                outputs.into_iter(),
-            )
-            .unwrap(),
+            framebuffer: Some(
+                glium::framebuffer::MultiOutputFrameBuffer::new(display, outputs)
+                    .unwrap(),
+            ),
             textures,
         }
     }
 
     //---------------------------------------------------------------------------------------------------
     pub fn clear(&self, target: &mut RenderTarget, r: f32, g: f32, b: f32, a: f32) {
-        target.framebuffer.clear_color_srgb(r, g, b, a);
+        if let Some(framebuffer) = target.framebuffer.as_mut() {
+            framebuffer.clear_color_srgb(r, g, b, a);
+        }
     }
 
     //---------------------------------------------------------------------------------------------------
-    fn create_draw_params<'b>(blend_mode : &BlendMode) -> glium::DrawParameters<'b> {
+    // Clears all of the output, ignoring the viewport
+    pub fn clear_output(&mut self, r: f32, g: f32, b: f32, a: f32) {
+        match &mut self.output {
+            Output::Frame(frame) => frame.clear_color_srgb(r, g, b, a),
+            Output::Texture(framebuffer) => framebuffer.clear_color_srgb(r, g, b, a),
+            Output::Null => {}
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Limits draws to the output to part of it, render targets always use all of their textures
+    pub fn set_viewport(&mut self, viewport: Option<snuff::core::Viewport>) {
+        self.viewport = viewport;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn create_draw_params<'b>(blend_mode : &BlendMode, viewport: &Option<snuff::core::Viewport>) -> glium::DrawParameters<'b> {
         glium::DrawParameters {
+            viewport: viewport.map(|viewport| glium::Rect {
+                left: viewport.x,
+                bottom: viewport.y,
+                width: viewport.width,
+                height: viewport.height,
+            }),
             blend: match blend_mode {
                 BlendMode::Additive => glium::Blend {
                     color: glium::BlendingFunction::Addition {
@@ -178,11 +302,18 @@ impl<'a> CommandBuffer<'a> {
         shader: &mut snuff::gfx::ShaderProgram,
         textures: &Vec<&snuff::gfx::Texture2D>,
     ) {
+        let _scope = snuff::core::profiler::scope("CommandBuffer::draw");
+
+        if self.is_null() {
+            return;
+        }
+
         let uniforms = uniform! {
             time: self.time,
             model: *transform.local_to_world().as_ref(),
             view: *camera.view().as_ref(),
             projection: *camera.projection().as_ref(),
+            screen_size: *camera.dimensions().as_ref(),
             blend: *self.blend_color.as_ref(),
             sampler0: self.create_sampler_uniform(0, textures),
             sampler1: self.create_sampler_uniform(1, textures),
@@ -194,15 +325,14 @@ impl<'a> CommandBuffer<'a> {
             sampler7: self.create_sampler_uniform(7, textures)
         };
 
-        self.frame
-            .draw(
-                mesh.vertex_buffer(),
-                mesh.index_buffer(),
-                shader.program(self.display),
-                &uniforms,
-                &CommandBuffer::create_draw_params(&self.current_blend_mode),
-            )
-            .unwrap();
+        CommandBuffer::draw_to_output(
+            &mut self.output,
+            mesh,
+            shader.program(self.device),
+            &uniforms,
+            &self.current_blend_mode,
+            &self.viewport,
+        );
     }
 
     //---------------------------------------------------------------------------------------------------
@@ -215,11 +345,19 @@ impl<'a> CommandBuffer<'a> {
         shader: &mut snuff::gfx::ShaderProgram,
         textures: &Vec<&snuff::gfx::Texture2D>,
     ) {
+        let _scope = snuff::core::profiler::scope("CommandBuffer::draw_into_target");
+
+        let framebuffer = match target.framebuffer.as_mut() {
+            Some(framebuffer) => framebuffer,
+            None => return,
+        };
+
         let uniforms = uniform! {
             time: self.time,
             model: *transform.local_to_world().as_ref(),
             view: *camera.view().as_ref(),
             projection: *camera.projection().as_ref(),
+            screen_size: *camera.dimensions().as_ref(),
             blend: *self.blend_color.as_ref(),
             sampler0: self.create_sampler_uniform(0, textures),
             sampler1: self.create_sampler_uniform(1, textures),
@@ -231,18 +369,121 @@ impl<'a> CommandBuffer<'a> {
             sampler7: self.create_sampler_uniform(7, textures)
         };
 
-        target
-            .framebuffer
+        snuff::core::profiler::count_draw_call();
+
+        framebuffer
             .draw(
                 mesh.vertex_buffer(),
                 mesh.index_buffer(),
-                shader.program(self.display),
+                shader.program(self.device),
                 &uniforms,
-                &CommandBuffer::create_draw_params(&self.current_blend_mode),
+                &CommandBuffer::create_draw_params(&self.current_blend_mode, &None),
             )
             .unwrap();
     }
 
+    //---------------------------------------------------------------------------------------------------
+    fn draw_batches<S: glium::Surface>(
+        &self,
+        surface: &mut S,
+        viewport: &Option<snuff::core::Viewport>,
+        camera: &mut snuff::core::Camera,
+        batch: &snuff::gfx::SpriteBatch,
+    ) {
+        let (vertex_buffer, index_buffer) = match batch.buffers() {
+            Some(buffers) => buffers,
+            None => return,
+        };
+
+        let view = *camera.view().as_ref();
+        let projection = *camera.projection().as_ref();
+        let screen_size = *camera.dimensions().as_ref();
+        let identity = *nalgebra_glm::Mat4::identity().as_ref();
+
+        for it in batch.batches() {
+            let sampler = |index: usize| {
+                let texture_handle = match it.textures.get(index) {
+                    Some(texture) => &**texture,
+                    None => self.default_texture,
+                };
+                let filtering = texture_handle.filtering();
+
+                glium::uniforms::Sampler::new(texture_handle.texture())
+                    .wrap_function(glium::uniforms::SamplerWrapFunction::Repeat)
+                    .minify_filter(filtering.0)
+                    .magnify_filter(filtering.1)
+            };
+
+            // The model matrix comes in with the vertices, identity keeps shaders that still read it working
+            let uniforms = uniform! {
+                time: self.time,
+                model: identity,
+                view: view,
+                projection: projection,
+                screen_size: screen_size,
+                blend: *self.blend_color.as_ref(),
+                sampler0: sampler(0),
+                sampler1: sampler(1),
+                sampler2: sampler(2),
+                sampler3: sampler(3),
+                sampler4: sampler(4),
+                sampler5: sampler(5),
+                sampler6: sampler(6),
+                sampler7: sampler(7)
+            };
+
+            let vertices = vertex_buffer.slice(it.first_vertex..it.first_vertex + it.quads * 4).unwrap();
+            let indices = index_buffer.slice(0..it.quads * 6).unwrap();
+
+            snuff::core::profiler::count_draw_call();
+
+            surface
+                .draw(
+                    vertices,
+                    indices,
+                    it.shader.borrow_mut().program(self.device),
+                    &uniforms,
+                    &CommandBuffer::create_draw_params(&it.blend_mode, viewport),
+                )
+                .unwrap();
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Draws everything added to the batch since it was last drawn
+    pub fn draw_sprite_batch(&mut self, camera: &mut snuff::core::Camera, batch: &mut snuff::gfx::SpriteBatch) {
+        let _scope = snuff::core::profiler::scope("CommandBuffer::draw_sprite_batch");
+
+        batch.prepare(self.device);
+
+        // Taken out for the draw, so the rest of the command buffer can still be read while drawing into it
+        let mut output = std::mem::replace(&mut self.output, Output::Null);
+
+        match &mut output {
+            Output::Frame(frame) => self.draw_batches(frame, &self.viewport, camera, batch),
+            Output::Texture(framebuffer) => self.draw_batches(framebuffer, &self.viewport, camera, batch),
+            Output::Null => {}
+        }
+
+        self.output = output;
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn draw_sprite_batch_into_target(
+        &self,
+        target: &mut RenderTarget,
+        camera: &mut snuff::core::Camera,
+        batch: &mut snuff::gfx::SpriteBatch,
+    ) {
+        let _scope = snuff::core::profiler::scope("CommandBuffer::draw_sprite_batch_into_target");
+
+        batch.prepare(self.device);
+
+        if let Some(framebuffer) = target.framebuffer.as_mut() {
+            self.draw_batches(framebuffer, &None, camera, batch);
+        }
+    }
+
     //---------------------------------------------------------------------------------------------------
     pub fn fullscreen_pass(
         &mut self,
@@ -250,10 +491,17 @@ impl<'a> CommandBuffer<'a> {
         shader: &mut snuff::gfx::ShaderProgram,
         textures: &Vec<&snuff::gfx::Texture2D>,
     ) {
+        let _scope = snuff::core::profiler::scope("CommandBuffer::fullscreen_pass");
+
+        if self.is_null() {
+            return;
+        }
+
         let uniforms = uniform! {
             time: self.time,
             view: *camera.view().as_ref(),
             projection: *camera.projection().as_ref(),
+            screen_size: *camera.dimensions().as_ref(),
             blend: *self.blend_color.as_ref(),
             sampler0: self.create_sampler_uniform(0, textures),
             sampler1: self.create_sampler_uniform(1, textures),
@@ -265,22 +513,78 @@ impl<'a> CommandBuffer<'a> {
             sampler7: self.create_sampler_uniform(7, textures)
         };
 
-        self.frame
-            .draw(
-                self.fullscreen_quad.vertex_buffer(),
-                self.fullscreen_quad.index_buffer(),
-                shader.program(self.display),
-                &uniforms,
-                &CommandBuffer::create_draw_params(&self.current_blend_mode),
-            )
-            .unwrap()
+        CommandBuffer::draw_to_output(
+            &mut self.output,
+            self.fullscreen_quad,
+            shader.program(self.device),
+            &uniforms,
+            &self.current_blend_mode,
+            &self.viewport,
+        );
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn read_frame(&self) -> Option<image::RgbaImage> {
+        if self.is_null() {
+            return None;
+        }
+
+        let (width, height) = self.dimensions();
+
+        let copy = snuff::gfx::Texture2D::empty(self.device, width as u16, height as u16);
+        let surface =
+            glium::framebuffer::SimpleFrameBuffer::new(self.device.gl_display(), copy.texture())
+                .unwrap();
+
+        let filter = glium::uniforms::MagnifySamplerFilter::Nearest;
+
+        match &self.output {
+            Output::Frame(frame) => frame.fill(&surface, filter),
+            Output::Texture(framebuffer) => framebuffer.fill(&surface, filter),
+            Output::Null => {}
+        }
+
+        drop(surface);
+
+        copy.read()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn save_frame(&self, path: &str) -> bool {
+        CommandBuffer::save_image(self.read_frame(), path)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn save_texture(&self, texture: &snuff::gfx::Texture2D, path: &str) -> bool {
+        CommandBuffer::save_image(texture.read(), path)
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn save_image(image: Option<image::RgbaImage>, path: &str) -> bool {
+        let image = match image {
+            Some(image) => image,
+            None => {
+                println!("[CommandBuffer] Nothing to save to '{}', the device is headless", path);
+                return false;
+            }
+        };
+
+        match image.save(path) {
+            Ok(_) => true,
+            Err(e) => {
+                println!("[CommandBuffer] Could not save image to '{}': {}", path, e);
+                false
+            }
+        }
     }
 
     //---------------------------------------------------------------------------------------------------
     pub fn end(self) {
-        match self.frame.finish() {
-            Ok(_) => {}
-            Err(e) => println!("[CommandBuffer] Could not swap buffers in 'end' : {}", e),
+        if let Output::Frame(frame) = self.output {
+            match frame.finish() {
+                Ok(_) => {}
+                Err(e) => println!("[CommandBuffer] Could not swap buffers in 'end' : {}", e),
+            }
         }
     }
 }
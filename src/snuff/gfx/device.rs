@@ -0,0 +1,34 @@
+pub struct Device {
+    display: Option<glium::Display>,
+}
+
+impl Device {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new(display: glium::Display) -> Device {
+        Device {
+            display: Some(display),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn null() -> Device {
+        Device { display: None }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_null(&self) -> bool {
+        self.display.is_none()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn display(&self) -> Option<&glium::Display> {
+        self.display.as_ref()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn gl_display(&self) -> &glium::Display {
+        self.display
+            .as_ref()
+            .expect("[Device] Attempted to access the display of a null device")
+    }
+}
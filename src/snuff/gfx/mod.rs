@@ -1,10 +1,13 @@
 mod command_buffer;
+mod device;
 mod shader_program;
 mod shapes;
+mod sprite_batch;
 mod texture;
 
 pub use {
-    command_buffer::CommandBuffer, command_buffer::RenderTarget,
+    command_buffer::BlendMode, command_buffer::CommandBuffer, command_buffer::RenderTarget, device::Device,
     shader_program::ShaderProgram, shapes::Mesh, shapes::Vertex2D,
+    sprite_batch::BatchSprite, sprite_batch::SpriteBatch, sprite_batch::SpriteBatchStats, sprite_batch::SpriteSort,
     texture::Texture2D,
 };
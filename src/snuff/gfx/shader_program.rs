@@ -1,9 +1,11 @@
+use crate::snuff::gfx;
+
 use notify::{RecommendedWatcher, Watcher, RecursiveMode};
 use std::sync::mpsc::channel;
 use std::time::Duration;
 
 pub struct ShaderProgram {
-    program: glium::Program,
+    program: Option<glium::Program>,
     watcher: RecommendedWatcher,
     watch_receiver: std::sync::mpsc::Receiver<notify::DebouncedEvent>,
     vs_file_path: String,
@@ -13,12 +15,25 @@ pub struct ShaderProgram {
 impl ShaderProgram {
     //---------------------------------------------------------------------------------------------------
     pub fn from_string<'a>(
-        display: &glium::Display,
+        device: &gfx::Device,
         vs_raw: &'a str,
         fs_raw: &'a str,
     ) -> Result<ShaderProgram, glium::ProgramCreationError> {
         let (sender, receiver) = channel();
 
+        let display = match device.display() {
+            Some(display) => display,
+            None => {
+                return Ok(ShaderProgram {
+                    program: None,
+                    watcher: Watcher::new(sender, Duration::from_millis(150)).unwrap(),
+                    watch_receiver: receiver,
+                    vs_file_path: String::from("null"),
+                    fs_file_path: String::from("null")
+                });
+            }
+        };
+
         match glium::Program::new(display, glium::program::ProgramCreationInput::SourceCode {
             vertex_shader: vs_raw,
             fragment_shader: fs_raw,
@@ -30,7 +45,7 @@ impl ShaderProgram {
             uses_point_size: false,
         }) {
             Ok(program) => Ok(ShaderProgram {
-                program,
+                program: Some(program),
                 watcher: Watcher::new(sender, Duration::from_millis(150)).unwrap(),
                 watch_receiver: receiver,
                 vs_file_path: String::from("null"),
@@ -45,7 +60,7 @@ impl ShaderProgram {
 
     //---------------------------------------------------------------------------------------------------
     pub fn from_source<'a>(
-        display: &glium::Display,
+        device: &gfx::Device,
         vs_file: String,
         fs_file: String,
     ) -> Result<ShaderProgram, glium::ProgramCreationError> {
@@ -63,7 +78,7 @@ impl ShaderProgram {
             )[..],
         );
 
-        let new_program = ShaderProgram::from_string(display, &vs_contents, &fs_contents);
+        let new_program = ShaderProgram::from_string(device, &vs_contents, &fs_contents);
 
         match new_program
         {
@@ -81,8 +96,8 @@ impl ShaderProgram {
     }
 
     //---------------------------------------------------------------------------------------------------
-    fn reload(&mut self, display: &glium::Display) {
-        let new_shader = ShaderProgram::from_source(display, self.vs_file_path.to_string(), self.fs_file_path.to_string());
+    fn reload(&mut self, device: &gfx::Device) {
+        let new_shader = ShaderProgram::from_source(device, self.vs_file_path.to_string(), self.fs_file_path.to_string());
 
         match new_shader {
             Ok(shader_program) => { self.program = shader_program.program; println!("[ShaderProgram] Reloaded ({}, {})", self.vs_file_path, self.fs_file_path); },
@@ -91,12 +106,12 @@ impl ShaderProgram {
     }
 
     //---------------------------------------------------------------------------------------------------
-    pub fn check_reload(&mut self, display: &glium::Display) {
+    pub fn check_reload(&mut self, device: &gfx::Device) {
         match self.watch_receiver.try_recv() {
             Ok(evt) => { 
                 match evt {
                     notify::DebouncedEvent::Write(_) => {
-                        self.reload(display)
+                        self.reload(device)
                     },
                     _ => {}
                 }
@@ -106,8 +121,10 @@ impl ShaderProgram {
     }
 
     //---------------------------------------------------------------------------------------------------
-    pub fn program(&mut self, display: &glium::Display) -> &glium::Program {
-        self.check_reload(display);
-        &self.program
+    pub fn program(&mut self, device: &gfx::Device) -> &glium::Program {
+        self.check_reload(device);
+        self.program
+            .as_ref()
+            .expect("[ShaderProgram] Attempted to access the program of a null device")
     }
 }
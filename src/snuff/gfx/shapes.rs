@@ -1,3 +1,5 @@
+use crate::snuff::gfx;
+
 #[derive(Copy, Clone)]
 pub struct Vertex2D {
     pub position: [f32; 2],
@@ -7,26 +9,30 @@ pub struct Vertex2D {
 implement_vertex!(Vertex2D, position, uv);
 
 pub struct Mesh {
-    vertex_buffer: glium::VertexBuffer<Vertex2D>,
-    index_buffer: glium::IndexBuffer<u16>,
+    vertex_buffer: Option<glium::VertexBuffer<Vertex2D>>,
+    index_buffer: Option<glium::IndexBuffer<u16>>,
 }
 
 impl Mesh {
     //---------------------------------------------------------------------------------------------------
-    pub fn new(display: &glium::Display, vertices: Vec<Vertex2D>, indices: Vec<u16>) -> Mesh {
+    pub fn new(device: &gfx::Device, vertices: Vec<Vertex2D>, indices: Vec<u16>) -> Mesh {
         Mesh {
-            vertex_buffer: glium::VertexBuffer::new(display, &vertices).unwrap(),
-            index_buffer: glium::IndexBuffer::new(
-                display,
-                glium::index::PrimitiveType::TrianglesList,
-                &indices,
-            )
-            .unwrap(),
+            vertex_buffer: device
+                .display()
+                .map(|display| glium::VertexBuffer::new(display, &vertices).unwrap()),
+            index_buffer: device.display().map(|display| {
+                glium::IndexBuffer::new(
+                    display,
+                    glium::index::PrimitiveType::TrianglesList,
+                    &indices,
+                )
+                .unwrap()
+            }),
         }
     }
 
     //---------------------------------------------------------------------------------------------------
-    pub fn create_quad(display: &glium::Display, half_size: bool) -> Mesh {
+    pub fn create_quad(device: &gfx::Device, half_size: bool) -> Mesh {
         let size = if half_size { 0.5 } else { 1.0 };
         let vertices = vec![
             Vertex2D {
@@ -49,16 +55,20 @@ impl Mesh {
 
         let indices = vec![0, 1, 2, 2, 1, 3];
 
-        Mesh::new(display, vertices, indices)
+        Mesh::new(device, vertices, indices)
     }
 
     //---------------------------------------------------------------------------------------------------
     pub fn vertex_buffer(&self) -> &glium::VertexBuffer<Vertex2D> {
-        &self.vertex_buffer
+        self.vertex_buffer
+            .as_ref()
+            .expect("[Mesh] Attempted to access the vertex buffer of a null device")
     }
 
     //---------------------------------------------------------------------------------------------------
     pub fn index_buffer(&self) -> &glium::IndexBuffer<u16> {
-        &self.index_buffer
+        self.index_buffer
+            .as_ref()
+            .expect("[Mesh] Attempted to access the index buffer of a null device")
     }
 }
@@ -0,0 +1,464 @@
+use crate::snuff::core;
+use crate::snuff::gfx;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// The most textures a single draw call can use, matching the samplers every draw provides
+pub const MAX_BATCH_TEXTURES: usize = 8;
+// Quads are indexed with u16, so a batch can't go past this many
+const MAX_BATCH_QUADS: usize = 16384;
+
+// Every vertex carries its sprite's 2D model matrix, so batched shaders can still do per-sprite work
+#[derive(Copy, Clone)]
+pub struct SpriteVertex {
+    // The corner of the unit quad, before the model matrix is applied
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub tint: [f32; 4],
+    // The x and y axes of the model matrix
+    pub basis: [f32; 4],
+    pub offset: [f32; 3],
+    pub texture_slot: f32,
+}
+
+implement_vertex!(SpriteVertex, position, uv, tint, basis, offset, texture_slot);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpriteSort {
+    // Keeps the order sprites were added in within a layer, overlapping sprites always look the same
+    Layer,
+    // Also groups each layer by blend mode, shader and texture, for fewer draw calls when the order doesn't matter
+    Texture,
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SpriteBatchStats {
+    pub sprites: usize,
+    pub draw_calls: usize,
+    pub vertices: usize,
+    pub largest_batch: usize,
+}
+
+pub struct BatchSprite {
+    texture: Rc<gfx::Texture2D>,
+    shader: Rc<RefCell<gfx::ShaderProgram>>,
+    model: nalgebra_glm::Mat4,
+    layer: i32,
+    uv_rect: core::Rect,
+    tint: nalgebra_glm::Vec4,
+    blend_mode: gfx::BlendMode,
+}
+
+pub(crate) struct Batch {
+    pub(crate) shader: Rc<RefCell<gfx::ShaderProgram>>,
+    pub(crate) blend_mode: gfx::BlendMode,
+    pub(crate) textures: Vec<Rc<gfx::Texture2D>>,
+    pub(crate) first_vertex: usize,
+    pub(crate) quads: usize,
+}
+
+// Collects sprites over a frame and draws them with as few draw calls as it can
+pub struct SpriteBatch {
+    sprites: Vec<BatchSprite>,
+    sort: SpriteSort,
+    vertices: Vec<SpriteVertex>,
+    batches: Vec<Batch>,
+    vertex_buffer: Option<glium::VertexBuffer<SpriteVertex>>,
+    index_buffer: Option<glium::IndexBuffer<u16>>,
+    stats: SpriteBatchStats,
+}
+
+impl BatchSprite {
+    //---------------------------------------------------------------------------------------------------
+    // The part of the texture to show, in UVs from the bottom left
+    pub fn set_uv_rect(&mut self, uv_rect: &core::Rect) -> &mut BatchSprite {
+        self.uv_rect = *uv_rect;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_tint(&mut self, tint: &nalgebra_glm::Vec4) -> &mut BatchSprite {
+        self.tint = *tint;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_blend_mode(&mut self, blend_mode: gfx::BlendMode) -> &mut BatchSprite {
+        self.blend_mode = blend_mode;
+
+        self
+    }
+}
+
+//---------------------------------------------------------------------------------------------------
+fn quad_indices(quads: usize) -> Vec<u16> {
+    let mut indices = Vec::with_capacity(quads * 6);
+
+    for quad in 0..quads {
+        let first = (quad * 4) as u16;
+        indices.extend_from_slice(&[first, first + 1, first + 2, first + 2, first + 1, first + 3]);
+    }
+
+    indices
+}
+
+impl Batch {
+    //---------------------------------------------------------------------------------------------------
+    // Which sampler the texture ends up in, None when the batch can't take it
+    fn texture_slot(&mut self, texture: &Rc<gfx::Texture2D>) -> Option<usize> {
+        if let Some(slot) = self.textures.iter().position(|it| Rc::ptr_eq(it, texture)) {
+            return Some(slot);
+        }
+
+        if self.textures.len() == MAX_BATCH_TEXTURES {
+            return None;
+        }
+
+        self.textures.push(texture.clone());
+        Some(self.textures.len() - 1)
+    }
+}
+
+impl SpriteBatch {
+    //---------------------------------------------------------------------------------------------------
+    pub fn new() -> SpriteBatch {
+        SpriteBatch {
+            sprites: Vec::new(),
+            sort: SpriteSort::Layer,
+            vertices: Vec::new(),
+            batches: Vec::new(),
+            vertex_buffer: None,
+            index_buffer: None,
+            stats: SpriteBatchStats::default(),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn set_sort(&mut self, sort: SpriteSort) -> &mut SpriteBatch {
+        self.sort = sort;
+
+        self
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn sort(&self) -> SpriteSort {
+        self.sort
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Draws the whole texture untinted with alpha blending, unless told otherwise.
+    // The shader has to read the sprite attributes, see sprite.vs
+    pub fn add(
+        &mut self,
+        texture: &Rc<gfx::Texture2D>,
+        shader: &Rc<RefCell<gfx::ShaderProgram>>,
+        local_to_world: &nalgebra_glm::Mat4,
+        layer: i32,
+    ) -> &mut BatchSprite {
+        self.sprites.push(BatchSprite {
+            texture: texture.clone(),
+            shader: shader.clone(),
+            model: *local_to_world,
+            layer,
+            uv_rect: core::Rect::new(&nalgebra_glm::vec2(0.0, 0.0), &nalgebra_glm::vec2(1.0, 1.0)),
+            tint: nalgebra_glm::vec4(1.0, 1.0, 1.0, 1.0),
+            blend_mode: gfx::BlendMode::Alpha,
+        });
+
+        self.sprites.last_mut().unwrap()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // What the last draw of the batch took
+    pub fn stats(&self) -> SpriteBatchStats {
+        self.stats
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    fn sort_sprites(&mut self) {
+        // Sorting is stable, so sprites that compare equal keep the order they were added in
+        match self.sort {
+            SpriteSort::Layer => self.sprites.sort_by_key(|sprite| sprite.layer),
+            SpriteSort::Texture => self.sprites.sort_by_key(|sprite| {
+                (
+                    sprite.layer,
+                    sprite.blend_mode as u8,
+                    Rc::as_ptr(&sprite.shader) as usize,
+                    Rc::as_ptr(&sprite.texture) as usize,
+                )
+            }),
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Turns the sprites into vertices and splits them into batches wherever the shader, blend mode or textures have to change
+    fn build(&mut self) {
+        self.sort_sprites();
+        self.vertices.clear();
+        self.batches.clear();
+
+        let corners = [(-0.5, -0.5, 0.0, 0.0), (0.5, -0.5, 1.0, 0.0), (-0.5, 0.5, 0.0, 1.0), (0.5, 0.5, 1.0, 1.0)];
+
+        for sprite in self.sprites.iter() {
+            let fits = match self.batches.last_mut() {
+                Some(batch) => {
+                    Rc::ptr_eq(&batch.shader, &sprite.shader)
+                        && batch.blend_mode == sprite.blend_mode
+                        && batch.quads < MAX_BATCH_QUADS
+                        && batch.texture_slot(&sprite.texture).is_some()
+                }
+                None => false,
+            };
+
+            if !fits {
+                self.batches.push(Batch {
+                    shader: sprite.shader.clone(),
+                    blend_mode: sprite.blend_mode,
+                    textures: Vec::new(),
+                    first_vertex: self.vertices.len(),
+                    quads: 0,
+                });
+            }
+
+            let batch = self.batches.last_mut().unwrap();
+            let slot = batch.texture_slot(&sprite.texture).unwrap_or(0);
+            batch.quads += 1;
+
+            let model = &sprite.model;
+            let min = sprite.uv_rect.min;
+            let max = sprite.uv_rect.max;
+
+            for (x, y, u, v) in corners.iter() {
+                self.vertices.push(SpriteVertex {
+                    position: [*x, *y],
+                    uv: [min.x + (max.x - min.x) * u, min.y + (max.y - min.y) * v],
+                    tint: [sprite.tint.x, sprite.tint.y, sprite.tint.z, sprite.tint.w],
+                    basis: [model[(0, 0)], model[(1, 0)], model[(0, 1)], model[(1, 1)]],
+                    offset: [model[(0, 3)], model[(1, 3)], model[(2, 3)]],
+                    texture_slot: slot as f32,
+                });
+            }
+        }
+
+        self.stats = SpriteBatchStats {
+            sprites: self.sprites.len(),
+            draw_calls: self.batches.len(),
+            vertices: self.vertices.len(),
+            largest_batch: self.batches.iter().map(|batch| batch.quads).max().unwrap_or(0),
+        };
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // The buffers only ever grow, so after the first few frames nothing new is allocated
+    fn upload(&mut self, device: &gfx::Device) {
+        let display = match device.display() {
+            Some(display) => display,
+            None => return,
+        };
+
+        let vertex_capacity = self.vertex_buffer.as_ref().map(|buffer| buffer.len()).unwrap_or(0);
+
+        if self.vertices.len() > vertex_capacity {
+            let capacity = self.vertices.len().next_power_of_two();
+            let mut vertices = self.vertices.clone();
+            vertices.resize(capacity, self.vertices[0]);
+
+            self.vertex_buffer = Some(glium::VertexBuffer::dynamic(display, &vertices).unwrap());
+        } else if let Some(buffer) = self.vertex_buffer.as_ref() {
+            buffer.slice(0..self.vertices.len()).unwrap().write(&self.vertices);
+        }
+
+        if self.index_buffer.is_none() {
+            self.index_buffer = Some(
+                glium::IndexBuffer::new(
+                    display,
+                    glium::index::PrimitiveType::TrianglesList,
+                    &quad_indices(MAX_BATCH_QUADS),
+                )
+                .unwrap(),
+            );
+        }
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    // Called by the command buffer right before drawing, also clears the sprites for the next frame
+    pub(crate) fn prepare(&mut self, device: &gfx::Device) {
+        let _scope = core::profiler::scope("SpriteBatch::prepare");
+
+        self.build();
+        core::profiler::count_sprite_batch(self.stats.sprites, self.stats.draw_calls, self.stats.largest_batch);
+
+        if !self.vertices.is_empty() {
+            self.upload(device);
+        }
+
+        self.sprites.clear();
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn batches(&self) -> &[Batch] {
+        &self.batches
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub(crate) fn buffers(&self) -> Option<(&glium::VertexBuffer<SpriteVertex>, &glium::IndexBuffer<u16>)> {
+        match (self.vertex_buffer.as_ref(), self.index_buffer.as_ref()) {
+            (Some(vertices), Some(indices)) => Some((vertices, indices)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(device: &gfx::Device) -> Rc<gfx::Texture2D> {
+        Rc::new(gfx::Texture2D::empty(device, 4, 4))
+    }
+
+    fn shader(device: &gfx::Device) -> Rc<RefCell<gfx::ShaderProgram>> {
+        Rc::new(RefCell::new(gfx::ShaderProgram::from_string(device, "", "").unwrap()))
+    }
+
+    fn at(x: f32) -> nalgebra_glm::Mat4 {
+        nalgebra_glm::translation(&nalgebra_glm::vec3(x, 0.0, 0.0))
+    }
+
+    #[test]
+    fn matching_sprites_share_a_batch() {
+        let device = gfx::Device::null();
+        let (texture, shader) = (texture(&device), shader(&device));
+
+        let mut batch = SpriteBatch::new();
+        for i in 0..3 {
+            batch.add(&texture, &shader, &at(i as f32), 0);
+        }
+        batch.build();
+
+        let stats = batch.stats();
+        assert_eq!((stats.sprites, stats.draw_calls, stats.vertices, stats.largest_batch), (3, 1, 12, 3));
+    }
+
+    #[test]
+    fn empty_batches_draw_nothing() {
+        let mut batch = SpriteBatch::new();
+        batch.build();
+
+        let stats = batch.stats();
+        assert_eq!((stats.sprites, stats.draw_calls, stats.vertices, stats.largest_batch), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn textures_get_a_slot_each() {
+        let device = gfx::Device::null();
+        let (first, second, shader) = (texture(&device), texture(&device), shader(&device));
+
+        let mut batch = SpriteBatch::new();
+        batch.add(&first, &shader, &at(0.0), 0);
+        batch.add(&second, &shader, &at(1.0), 0);
+        batch.add(&first, &shader, &at(2.0), 0);
+        batch.build();
+
+        let slots: Vec<f32> = batch.vertices.iter().step_by(4).map(|vertex| vertex.texture_slot).collect();
+        assert_eq!(slots, vec![0.0, 1.0, 0.0]);
+        assert_eq!(batch.batches().len(), 1);
+        assert_eq!(batch.batches()[0].textures.len(), 2);
+    }
+
+    #[test]
+    fn running_out_of_texture_slots_splits() {
+        let device = gfx::Device::null();
+        let shader = shader(&device);
+        let textures: Vec<_> = (0..MAX_BATCH_TEXTURES + 1).map(|_| texture(&device)).collect();
+
+        let mut batch = SpriteBatch::new();
+        for texture in textures.iter() {
+            batch.add(texture, &shader, &at(0.0), 0);
+        }
+        batch.build();
+
+        let quads: Vec<usize> = batch.batches().iter().map(|batch| batch.quads).collect();
+        assert_eq!(quads, vec![MAX_BATCH_TEXTURES, 1]);
+        assert_eq!(batch.batches()[1].first_vertex, MAX_BATCH_TEXTURES * 4);
+    }
+
+    #[test]
+    fn shader_and_blend_mode_changes_split() {
+        let device = gfx::Device::null();
+        let (texture, first, second) = (texture(&device), shader(&device), shader(&device));
+
+        let mut batch = SpriteBatch::new();
+        batch.add(&texture, &first, &at(0.0), 0);
+        batch.add(&texture, &second, &at(0.0), 0);
+        batch.add(&texture, &second, &at(0.0), 0).set_blend_mode(gfx::BlendMode::Additive);
+        batch.build();
+
+        assert_eq!(batch.stats().draw_calls, 3);
+    }
+
+    #[test]
+    fn too_many_quads_split() {
+        let device = gfx::Device::null();
+        let (texture, shader) = (texture(&device), shader(&device));
+
+        let mut batch = SpriteBatch::new();
+        for _ in 0..MAX_BATCH_QUADS + 1 {
+            batch.add(&texture, &shader, &at(0.0), 0);
+        }
+        batch.build();
+
+        let stats = batch.stats();
+        assert_eq!((stats.draw_calls, stats.largest_batch), (2, MAX_BATCH_QUADS));
+    }
+
+    #[test]
+    fn layers_are_drawn_back_to_front_in_the_order_added() {
+        let device = gfx::Device::null();
+        let (texture, shader) = (texture(&device), shader(&device));
+
+        let mut batch = SpriteBatch::new();
+        batch.add(&texture, &shader, &at(0.0), 1);
+        batch.add(&texture, &shader, &at(1.0), 0);
+        batch.add(&texture, &shader, &at(2.0), 1);
+        batch.add(&texture, &shader, &at(3.0), 0);
+        batch.build();
+
+        let order: Vec<f32> = batch.vertices.iter().step_by(4).map(|vertex| vertex.offset[0]).collect();
+        assert_eq!(order, vec![1.0, 3.0, 0.0, 2.0]);
+    }
+
+    #[test]
+    fn texture_sort_groups_within_a_layer() {
+        let device = gfx::Device::null();
+        let (texture, first, second) = (texture(&device), shader(&device), shader(&device));
+
+        let mut batch = SpriteBatch::new();
+        batch.add(&texture, &first, &at(0.0), 0);
+        batch.add(&texture, &second, &at(0.0), 0);
+        batch.add(&texture, &first, &at(0.0), 0);
+        batch.build();
+        assert_eq!(batch.stats().draw_calls, 3);
+
+        batch.set_sort(SpriteSort::Texture).build();
+        assert_eq!(batch.stats().draw_calls, 2);
+    }
+}
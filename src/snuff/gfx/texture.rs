@@ -1,7 +1,9 @@
+use crate::snuff::gfx;
+
 use std::io::Read;
 
 pub struct Texture2D {
-    texture: glium::texture::SrgbTexture2d,
+    texture: Option<glium::texture::SrgbTexture2d>,
     dimensions: nalgebra_glm::U16Vec2,
     min_filter: glium::uniforms::MinifySamplerFilter,
     max_filter: glium::uniforms::MagnifySamplerFilter,
@@ -11,10 +13,11 @@ type ImageData = Vec<u8>;
 
 impl Texture2D {
     //---------------------------------------------------------------------------------------------------
-    pub fn empty(display: &glium::Display, width: u16, height: u16) -> Texture2D {
+    pub fn empty(device: &gfx::Device, width: u16, height: u16) -> Texture2D {
         Texture2D {
-            texture: glium::texture::SrgbTexture2d::empty(display, width as u32, height as u32)
-                .unwrap(),
+            texture: device.display().map(|display| {
+                glium::texture::SrgbTexture2d::empty(display, width as u32, height as u32).unwrap()
+            }),
             dimensions: nalgebra_glm::vec2(width, height),
             min_filter: glium::uniforms::MinifySamplerFilter::Linear,
             max_filter: glium::uniforms::MagnifySamplerFilter::Linear,
@@ -23,17 +26,19 @@ impl Texture2D {
 
     //---------------------------------------------------------------------------------------------------
     pub fn from_data(
-        display: &glium::Display,
+        device: &gfx::Device,
         data: &ImageData,
         width: u16,
         height: u16,
     ) -> Texture2D {
-        let image = glium::texture::RawImage2d::from_raw_rgba_reversed(
-            data,
-            (width as u32, height as u32).into(),
-        );
         Texture2D {
-            texture: glium::texture::SrgbTexture2d::new(display, image).unwrap(),
+            texture: device.display().map(|display| {
+                let image = glium::texture::RawImage2d::from_raw_rgba_reversed(
+                    data,
+                    (width as u32, height as u32),
+                );
+                glium::texture::SrgbTexture2d::new(display, image).unwrap()
+            }),
             dimensions: nalgebra_glm::vec2(width, height),
             min_filter: glium::uniforms::MinifySamplerFilter::Linear,
             max_filter: glium::uniforms::MagnifySamplerFilter::Linear,
@@ -51,7 +56,7 @@ impl Texture2D {
     }
 
     //---------------------------------------------------------------------------------------------------
-    pub fn from_image<'a>(display: &glium::Display, path: &'a str) -> Texture2D {
+    pub fn from_image(device: &gfx::Device, path: &str) -> Texture2D {
         let mut fin = std::fs::File::open(path)
             .expect(&format!("[Texture2D] Could not open file '{}'", path)[..]);
         let mut data: Vec<u8> = vec![];
@@ -75,7 +80,7 @@ impl Texture2D {
         }
 
         Texture2D::from_data(
-            display,
+            device,
             &image_data.into_raw(),
             dimensions.0 as u16,
             dimensions.1 as u16,
@@ -84,7 +89,19 @@ impl Texture2D {
 
     //---------------------------------------------------------------------------------------------------
     pub fn texture(&self) -> &glium::texture::SrgbTexture2d {
-        &self.texture
+        self.texture
+            .as_ref()
+            .expect("[Texture2D] Attempted to access the texture of a null device")
+    }
+
+    //---------------------------------------------------------------------------------------------------
+    pub fn read(&self) -> Option<image::RgbaImage> {
+        let texture = self.texture.as_ref()?;
+        let raw: glium::texture::RawImage2d<u8> = texture.read();
+        let image = image::RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned())?;
+
+        // OpenGL stores the rows from the bottom up
+        Some(image::imageops::flip_vertical(&image))
     }
 
     //---------------------------------------------------------------------------------------------------
@@ -1,2 +1,3 @@
 pub mod core;
+pub mod ecs;
 pub mod gfx;
@@ -0,0 +1,33 @@
+use firefly_reflection::firefly::{self, StateKey};
+use firefly_reflection::snuff::core;
+
+use glium::glutin::VirtualKeyCode;
+
+// Runs the game without a window, from the menu through to the first story text
+#[test]
+fn skipping_the_menu_and_hopping_right_progresses_the_story() {
+    let mut config = core::Config::default();
+    firefly::settings::add_defaults(&mut config);
+
+    let mut game_loop = core::GameLoop::<StateKey>::headless(1280, 720);
+    game_loop.mute();
+    game_loop.window()
+        .set_logical_size(1280, 720)
+        .set_input(core::Input::from_config(&config));
+
+    game_loop.register_states(|key, window, audio_device| firefly::create_state(key, window, audio_device, &config, 1));
+    game_loop.game_state_manager().switch(StateKey::Menu);
+
+    let mut script = core::InputScript::new();
+    script
+        .tap_key(10, VirtualKeyCode::Space)
+        .hold_key(30, 900, VirtualKeyCode::Right);
+
+    game_loop.exec_headless(script.last_tick(), &script);
+
+    let game_state_manager = game_loop.game_state_manager();
+    assert_eq!(game_state_manager.current_state(), Some(StateKey::Main));
+
+    let main_state = game_state_manager.state::<firefly::MainState>(StateKey::Main).unwrap();
+    assert!(main_state.current_text() >= 1, "still on text {}", main_state.current_text());
+}